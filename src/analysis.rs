@@ -0,0 +1,564 @@
+use std::collections::VecDeque;
+use std::f64::consts::TAU;
+use std::fmt;
+
+use egui_plot::PlotPoint;
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+use serde::{Deserialize, Serialize};
+
+/// Sample rate in Hz, if none is known
+pub const DEFAULT_SAMPLE_RATE: f64 = 50.0;
+
+/// Post processing applied to the evaluated values of a plot
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Processing {
+    pub unit: Unit,
+    pub derivative: bool,
+    pub spectrum: Option<SpectrumConfig>,
+    pub histogram_bins: Option<usize>,
+    /// Window size of the moving average in samples
+    pub smoothing: Option<usize>,
+    /// Samples outside of `(min, max)` are excluded, see [`Series::invalid`]
+    pub valid_range: Option<(f64, f64)>,
+}
+
+impl Processing {
+    pub fn apply(&self, mut values: Vec<PlotPoint>) -> Series {
+        if self.unit != Unit::Unchanged {
+            for v in values.iter_mut() {
+                v.y = self.unit.convert(v.y);
+            }
+        }
+        let mut invalid = Vec::new();
+        if let Some((min, max)) = self.valid_range {
+            let (valid, outside) = values.into_iter().partition(|v| !(v.y < min || v.y > max));
+            values = valid;
+            invalid = outside;
+        }
+        if self.derivative {
+            values = derivative(&values);
+            values.retain(|p| p.y.is_finite());
+        }
+        let points = match (&self.spectrum, self.histogram_bins) {
+            (Some(cfg), _) => spectrum(&values, cfg),
+            (None, Some(bins)) => histogram(&values, bins),
+            (None, None) => values,
+        };
+        let smoothed = self.smoothing.map(|w| moving_average(&points, w));
+
+        Series {
+            points,
+            smoothed,
+            invalid,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Series {
+    pub points: Vec<PlotPoint>,
+    /// Moving average of the points, drawn as an overlay
+    pub smoothed: Option<Vec<PlotPoint>>,
+    /// Samples outside of the valid range, e.g. sentinel values of a faulty sensor
+    pub invalid: Vec<PlotPoint>,
+}
+
+impl Series {
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+}
+
+/// Conversion of the y values for display, from the unit the values are logged in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Unit {
+    #[default]
+    Unchanged,
+    Kilowatt,
+    Fahrenheit,
+    KilometersPerHour,
+    Degrees,
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unchanged => write!(f, "Unchanged"),
+            Self::Kilowatt => write!(f, "W → kW"),
+            Self::Fahrenheit => write!(f, "°C → °F"),
+            Self::KilometersPerHour => write!(f, "m/s → km/h"),
+            Self::Degrees => write!(f, "rad → °"),
+        }
+    }
+}
+
+impl Unit {
+    pub const ALL: [Self; 5] = [
+        Self::Unchanged,
+        Self::Kilowatt,
+        Self::Fahrenheit,
+        Self::KilometersPerHour,
+        Self::Degrees,
+    ];
+
+    /// Symbol of the converted unit
+    pub fn symbol(&self) -> Option<&'static str> {
+        match self {
+            Self::Unchanged => None,
+            Self::Kilowatt => Some("kW"),
+            Self::Fahrenheit => Some("°F"),
+            Self::KilometersPerHour => Some("km/h"),
+            Self::Degrees => Some("°"),
+        }
+    }
+
+    pub fn convert(&self, value: f64) -> f64 {
+        match self {
+            Self::Unchanged => value,
+            Self::Kilowatt => value / 1000.0,
+            Self::Fahrenheit => value * 1.8 + 32.0,
+            Self::KilometersPerHour => value * 3.6,
+            Self::Degrees => value.to_degrees(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SpectrumConfig {
+    pub window: WindowFn,
+    /// Rate in Hz the values are resampled to before the transform
+    pub sample_rate: f64,
+    /// Use the sample rate of the run instead of `sample_rate`, off for tabs saved before this
+    /// existed so they keep their rate
+    #[serde(default)]
+    pub run_rate: bool,
+}
+
+impl Default for SpectrumConfig {
+    fn default() -> Self {
+        Self {
+            window: WindowFn::Hann,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            run_rate: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WindowFn {
+    Rectangular,
+    Hann,
+    Hamming,
+}
+
+impl fmt::Display for WindowFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rectangular => write!(f, "Rectangular"),
+            Self::Hann => write!(f, "Hann"),
+            Self::Hamming => write!(f, "Hamming"),
+        }
+    }
+}
+
+impl WindowFn {
+    pub const ALL: [Self; 3] = [Self::Rectangular, Self::Hann, Self::Hamming];
+
+    fn weight(&self, i: usize, len: usize) -> f64 {
+        let phase = TAU * i as f64 / (len - 1) as f64;
+        match self {
+            Self::Rectangular => 1.0,
+            Self::Hann => 0.5 - 0.5 * phase.cos(),
+            Self::Hamming => 0.54 - 0.46 * phase.cos(),
+        }
+    }
+}
+
+/// Values on a grid with a fixed interval
+pub struct Resampled {
+    pub start: f64,
+    pub dt: f64,
+    pub values: Vec<f64>,
+}
+
+impl Resampled {
+    pub fn x(&self, i: usize) -> f64 {
+        self.start + i as f64 * self.dt
+    }
+}
+
+/// Linearly interpolates the values onto a grid with a fixed interval of `dt`, from the first to
+/// the last x value. The values have to be sorted by x.
+pub fn resample(values: &[PlotPoint], dt: f64) -> Resampled {
+    match (values.first(), values.last()) {
+        (Some(first), Some(last)) => resample_range(values, first.x, last.x, dt),
+        _ => resample_range(values, 0.0, 0.0, dt),
+    }
+}
+
+/// Linearly interpolates the values onto a grid with a fixed interval of `dt` covering
+/// `start..=end`. Grid points before the first or after the last value are clamped to it. The
+/// values have to be sorted by x.
+pub fn resample_range(values: &[PlotPoint], start: f64, end: f64, dt: f64) -> Resampled {
+    let mut resampled = Resampled {
+        start,
+        dt,
+        values: Vec::new(),
+    };
+    if values.is_empty() || dt <= 0.0 || !dt.is_finite() || end < start {
+        return resampled;
+    }
+
+    let len = ((end - start) / dt).floor() as usize + 1;
+    resampled.values.reserve(len);
+    let mut j = 0;
+    for i in 0..len {
+        let x = resampled.x(i);
+        while j + 1 < values.len() && values[j + 1].x <= x {
+            j += 1;
+        }
+
+        let a = values[j];
+        let y = match values.get(j + 1) {
+            Some(b) if x > a.x && b.x > a.x => a.y + (x - a.x) / (b.x - a.x) * (b.y - a.y),
+            _ => a.y,
+        };
+        resampled.values.push(y);
+    }
+
+    resampled
+}
+
+/// Linearly interpolated y value at `x`, or `None` if `x` lies outside of the values. The values
+/// have to be sorted by x.
+pub fn interpolate(values: &[PlotPoint], x: f64) -> Option<f64> {
+    let (first, last) = (values.first()?, values.last()?);
+    if x < first.x || x > last.x {
+        return None;
+    }
+
+    let i = values.partition_point(|v| v.x <= x);
+    let a = values[i.saturating_sub(1)];
+    let y = match values.get(i) {
+        Some(b) if b.x > a.x => a.y + (x - a.x) / (b.x - a.x) * (b.y - a.y),
+        _ => a.y,
+    };
+    Some(y)
+}
+
+/// Indices of the values after which the x distance to the next value exceeds `threshold`
+pub fn gaps(values: &[PlotPoint], threshold: f64) -> Vec<usize> {
+    (values.windows(2).enumerate())
+        .filter(|(_, w)| w[1].x - w[0].x > threshold)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Single sided amplitude spectrum of the values, with the frequency in Hz as x
+pub fn spectrum(values: &[PlotPoint], cfg: &SpectrumConfig) -> Vec<PlotPoint> {
+    let samples = resample(values, 1.0 / cfg.sample_rate).values;
+    let len = samples.len();
+    if len < 2 {
+        return Vec::new();
+    }
+
+    let weights: Vec<f64> = (0..len).map(|i| cfg.window.weight(i, len)).collect();
+    let weight_sum: f64 = weights.iter().sum();
+    if weight_sum <= 0.0 {
+        return Vec::new();
+    }
+
+    let mut buffer: Vec<Complex<f64>> = (samples.iter().zip(weights.iter()))
+        .map(|(s, w)| Complex::new(s * w, 0.0))
+        .collect();
+    FftPlanner::<f64>::new()
+        .plan_fft_forward(len)
+        .process(&mut buffer);
+
+    (0..=len / 2)
+        .map(|k| {
+            let factor = if k == 0 { 1.0 } else { 2.0 };
+            let frequency = k as f64 * cfg.sample_rate / len as f64;
+            PlotPoint::new(frequency, factor * buffer[k].norm() / weight_sum)
+        })
+        .collect()
+}
+
+/// Derivative of y with respect to x, using the difference between the adjacent samples. Points
+/// whose neighbours share the same x value have a y value of NaN.
+pub fn derivative(values: &[PlotPoint]) -> Vec<PlotPoint> {
+    (0..values.len())
+        .map(|i| {
+            let a = values[i.saturating_sub(1)];
+            let b = values[(i + 1).min(values.len() - 1)];
+            let dx = b.x - a.x;
+            let y = if dx != 0.0 {
+                (b.y - a.y) / dx
+            } else {
+                f64::NAN
+            };
+            PlotPoint::new(values[i].x, y)
+        })
+        .collect()
+}
+
+/// Sample count of the y values in `bins` evenly sized bins, with the bin center as x
+pub fn histogram(values: &[PlotPoint], bins: usize) -> Vec<PlotPoint> {
+    let finite = || values.iter().map(|v| v.y).filter(|y| y.is_finite());
+    let min = finite().fold(f64::INFINITY, f64::min);
+    let max = finite().fold(f64::NEG_INFINITY, f64::max);
+    if bins == 0 || min > max {
+        return Vec::new();
+    }
+
+    // a single bin of width 1 if all values are the same
+    let width = if max > min {
+        (max - min) / bins as f64
+    } else {
+        1.0
+    };
+    let bins = if max > min { bins } else { 1 };
+    let mut counts = vec![0usize; bins];
+    for y in finite() {
+        let i = ((y - min) / width) as usize;
+        counts[i.min(bins - 1)] += 1;
+    }
+
+    (counts.iter().enumerate())
+        .map(|(i, &c)| PlotPoint::new(min + (i as f64 + 0.5) * width, c as f64))
+        .collect()
+}
+
+#[derive(Clone, Copy)]
+pub struct Crossing {
+    /// Time in seconds, interpolated between the samples
+    pub time: f64,
+    /// From below to at or above the threshold
+    pub rising: bool,
+}
+
+/// Where the y values cross `threshold`, ignoring values that aren't finite
+pub fn crossings(values: &[PlotPoint], threshold: f64) -> Vec<Crossing> {
+    let finite = values.iter().filter(|v| v.y.is_finite());
+    let mut crossings = Vec::new();
+    let mut prev: Option<&PlotPoint> = None;
+    for b in finite {
+        if let Some(a) = prev {
+            let rising = a.y < threshold && b.y >= threshold;
+            let falling = a.y >= threshold && b.y < threshold;
+            if rising || falling {
+                let time = a.x + (threshold - a.y) / (b.y - a.y) * (b.x - a.x);
+                crossings.push(Crossing { time, rising });
+            }
+        }
+        prev = Some(b);
+    }
+    crossings
+}
+
+pub struct Stats {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub std_dev: f64,
+}
+
+/// Statistics of the y values, ignoring values that aren't finite
+pub fn stats(values: &[PlotPoint]) -> Option<Stats> {
+    let mut count = 0;
+    let mut min = f64::INFINITY;
+    let mut max = f64::NEG_INFINITY;
+    let mut sum = 0.0;
+    for v in values.iter().filter(|v| v.y.is_finite()) {
+        count += 1;
+        min = min.min(v.y);
+        max = max.max(v.y);
+        sum += v.y;
+    }
+    if count == 0 {
+        return None;
+    }
+
+    let mean = sum / count as f64;
+    let variance = (values.iter().filter(|v| v.y.is_finite()))
+        .map(|v| (v.y - mean).powi(2))
+        .sum::<f64>()
+        / count as f64;
+
+    Some(Stats {
+        min,
+        max,
+        mean,
+        std_dev: variance.sqrt(),
+    })
+}
+
+/// Linearly interpolated percentile `p` in `0..=100` of the finite values
+pub fn percentile(values: &[f64], p: f64) -> Option<f64> {
+    let mut sorted: Vec<f64> = values.iter().copied().filter(|v| v.is_finite()).collect();
+    if sorted.is_empty() {
+        return None;
+    }
+    sorted.sort_by(f64::total_cmp);
+
+    let rank = p.clamp(0.0, 100.0) / 100.0 * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    let factor = rank - lower as f64;
+    Some(sorted[lower] + factor * (sorted[upper] - sorted[lower]))
+}
+
+/// Trapezoidal integral of the values whose x lies within `start..=end`
+pub fn integral(values: &[PlotPoint], start: f64, end: f64) -> f64 {
+    (values.windows(2))
+        .filter(|w| w[0].x >= start && w[1].x <= end)
+        .map(trapezoid)
+        .filter(|a| a.is_finite())
+        .sum()
+}
+
+/// Running trapezoidal integral from the first value on
+pub fn cumulative_integral(values: &[PlotPoint]) -> Vec<PlotPoint> {
+    let mut sum = 0.0;
+    let mut integral = Vec::with_capacity(values.len());
+    for (i, v) in values.iter().enumerate() {
+        if i > 0 {
+            let area = trapezoid(&values[i - 1..=i]);
+            if area.is_finite() {
+                sum += area;
+            }
+        }
+        integral.push(PlotPoint::new(v.x, sum));
+    }
+    integral
+}
+
+fn trapezoid(w: &[PlotPoint]) -> f64 {
+    0.5 * (w[0].y + w[1].y) * (w[1].x - w[0].x)
+}
+
+/// Centered rolling maximum over `window` samples, the window shrinks towards the edges.
+/// Values that aren't finite are ignored.
+pub fn rolling_max(values: &[f64], window: usize) -> Vec<f64> {
+    rolling_extreme(values, window, |a, b| a >= b)
+}
+
+/// Centered rolling minimum over `window` samples, see [`rolling_max`]
+pub fn rolling_min(values: &[f64], window: usize) -> Vec<f64> {
+    rolling_extreme(values, window, |a, b| a <= b)
+}
+
+/// Keeps the indices of candidates in a monotonic queue, whose front is the extreme of the
+/// window. `dominates(a, b)` returns true if `b` can never be the extreme while `a` is in the
+/// window.
+fn rolling_extreme(values: &[f64], window: usize, dominates: fn(f64, f64) -> bool) -> Vec<f64> {
+    let before = window / 2;
+    let after = window.saturating_sub(1) - before;
+
+    let mut queue = VecDeque::new();
+    let mut next = 0;
+    (0..values.len())
+        .map(|i| {
+            let end = (i + after + 1).min(values.len());
+            for (j, &v) in values.iter().enumerate().take(end).skip(next) {
+                if !v.is_finite() {
+                    continue;
+                }
+                while queue.back().map_or(false, |&b| dominates(v, values[b])) {
+                    queue.pop_back();
+                }
+                queue.push_back(j);
+            }
+            next = end;
+
+            let start = i.saturating_sub(before);
+            while queue.front().map_or(false, |&f| f < start) {
+                queue.pop_front();
+            }
+            queue.front().map_or(f64::NAN, |&f| values[f])
+        })
+        .collect()
+}
+
+/// Centered moving average over `window` samples, the window shrinks towards the edges.
+pub fn moving_average(values: &[PlotPoint], window: usize) -> Vec<PlotPoint> {
+    let before = window / 2;
+    let after = window.saturating_sub(1) - before;
+
+    let mut sums = Vec::with_capacity(values.len() + 1);
+    let mut sum = 0.0;
+    sums.push(sum);
+    for v in values.iter() {
+        sum += v.y;
+        sums.push(sum);
+    }
+
+    (values.iter().enumerate())
+        .map(|(i, v)| {
+            let start = i.saturating_sub(before);
+            let end = (i + after + 1).min(values.len());
+            let mean = (sums[end] - sums[start]) / (end - start) as f64;
+            PlotPoint::new(v.x, mean)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compares element wise, with `NaN` equal to itself
+    fn assert_values(actual: Vec<f64>, expected: &[f64]) {
+        let same = |a: f64, b: f64| a == b || (a.is_nan() && b.is_nan());
+        assert!(
+            actual.len() == expected.len()
+                && actual.iter().zip(expected).all(|(&a, &b)| same(a, b)),
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    const VALUES: [f64; 5] = [1.0, 3.0, 2.0, 5.0, 4.0];
+
+    #[test]
+    fn rolling_odd_window() {
+        assert_values(rolling_max(&VALUES, 3), &[3.0, 3.0, 5.0, 5.0, 5.0]);
+        assert_values(rolling_min(&VALUES, 3), &[1.0, 1.0, 2.0, 2.0, 4.0]);
+    }
+
+    #[test]
+    fn rolling_even_window() {
+        // two samples before and one after
+        assert_values(rolling_max(&VALUES, 4), &[3.0, 3.0, 5.0, 5.0, 5.0]);
+        assert_values(rolling_min(&VALUES, 4), &[1.0, 1.0, 1.0, 2.0, 2.0]);
+    }
+
+    #[test]
+    fn rolling_window_of_one() {
+        assert_values(rolling_max(&VALUES, 1), &VALUES);
+        assert_values(rolling_min(&VALUES, 1), &VALUES);
+    }
+
+    #[test]
+    fn rolling_window_longer_than_values() {
+        assert_values(rolling_max(&VALUES, 10), &[5.0; 5]);
+        assert_values(rolling_min(&VALUES, 10), &[1.0; 5]);
+        assert_values(rolling_max(&[], 3), &[]);
+    }
+
+    #[test]
+    fn rolling_ignores_non_finite() {
+        let values = [1.0, f64::NAN, 3.0, f64::INFINITY, 2.0];
+        assert_values(rolling_max(&values, 3), &[1.0, 3.0, 3.0, 3.0, 2.0]);
+        assert_values(rolling_min(&values, 3), &[1.0, 1.0, 3.0, 2.0, 2.0]);
+    }
+
+    #[test]
+    fn rolling_only_non_finite_is_nan() {
+        let values = [f64::NAN, f64::NAN, 1.0, f64::NEG_INFINITY];
+        let (nan, inf) = (f64::NAN, f64::INFINITY);
+        assert_values(rolling_max(&values, 1), &[nan, nan, 1.0, nan]);
+        assert_values(rolling_min(&values, 3), &[nan, 1.0, 1.0, 1.0]);
+        assert_values(rolling_max(&[inf, nan, -inf], 5), &[nan; 3]);
+    }
+}
@@ -1,47 +1,259 @@
-use std::path::Path;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::thread::JoinHandle;
+use std::time::Instant;
 
 use chrono::{Duration, Local, TimeZone};
 use egui::{
-    menu, Align2, CentralPanel, Color32, Key, Modifiers, RichText, TopBottomPanel, Ui, Vec2, Window,
+    menu, Align2, Area, Button, CentralPanel, Color32, ComboBox, DragValue, Event, Frame, Key,
+    KeyboardShortcut, Label, Modifiers, ProgressBar, RichText, Slider, TopBottomPanel, Ui, Vec2,
+    Visuals, Window,
 };
 use egui_extras::{Column, TableBuilder};
-use egui_plot::PlotPoint;
 use serde::{Deserialize, Serialize};
 
+use crate::analysis::{Processing, Series};
 use crate::data::LogStream;
-use crate::eval::{self, Expr, ExprError};
-use crate::fs::{ErrorFile, Files, SelectableFile, SelectableFiles};
-use crate::plot::{self, Config};
+use crate::eval::{self, Expr, ExprError, Globals};
+use crate::export::{PendingExport, DEFAULT_EXPORT_DPI};
+use crate::fs::{
+    BatchExport, ErrorFile, FilePoller, FileWatcher, Files, Loading, SelectableFile,
+    SelectableFiles, StdinStream, DEFAULT_POLL_INTERVAL, MAX_POLL_INTERVAL,
+};
+use crate::plot::{self, Config, LineStyle, Palette, PlotLayout};
 use crate::util;
 
-const DATE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+const OPEN_DIR: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::O);
+const REOPEN_DIR: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::O);
+const RELOAD_FILES: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::R);
+const EXPORT_PNG: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::S);
+/// Only used to display the shortcut, egui turns it into an [`Event::Copy`]
+const COPY_PNG: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::C);
+/// Only used to display the shortcut, an [`Event::Copy`] while shift is held
+const COPY_VALUES: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::C);
+const UNDO: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+const REDO: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Y);
+const REDO_ALT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::Z);
+
+const TOAST_DURATION: std::time::Duration = std::time::Duration::from_secs(2);
+const DEFAULT_AUTOSAVE_INTERVAL: f32 = 30.0;
+const MAX_AUTOSAVE_INTERVAL: f32 = 600.0;
+/// Storage key of the state saved while the app is running, the state of a clean exit is stored
+/// under [`eframe::APP_KEY`]
+const AUTOSAVE_KEY: &str = "autosave";
+/// Storage key of a marker that is set while the app is running and cleared on a clean exit
+const RUNNING_KEY: &str = "running";
 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(default)]
 pub struct PlotApp {
     pub config: Config,
     pub files: Option<Files>,
+    /// Recently opened paths, most recent first
+    pub recent: Vec<PathBuf>,
+    /// Recurse into subdirectories when opening a directory
+    pub scan_subfolders: bool,
+    /// Reload the opened files when they change on disk
+    pub auto_reload: bool,
+    /// Check the files for changes in an interval instead of watching them
+    pub poll_reload: bool,
+    /// Seconds between checking the files when polling
+    pub poll_interval: f32,
+    /// Handling of timestamps that go backwards when loading files
+    pub timestamp_order: TimestampOrder,
+    /// Resolution stored in the metadata of exported PNGs, which sets their print size but not
+    /// their number of pixels
+    pub export_dpi: f32,
+    /// Include the markers of the run in exports
+    pub export_markers: bool,
+    /// Seconds between saving the state, so little is lost if the app is killed
+    pub autosave_interval: f32,
+    pub theme: Theme,
     #[serde(skip)]
     pub selectable_files: Option<SelectableFiles>,
     #[serde(skip)]
     pub data: Option<PlotData>,
+    #[serde(skip)]
+    pub loading: Option<Loading>,
+    #[serde(skip)]
+    pub watcher: Option<FileWatcher>,
+    #[serde(skip)]
+    pub poller: Option<FilePoller>,
+    #[serde(skip)]
+    pub stdin: Option<StdinStream>,
+    #[serde(skip)]
+    pub pending_export: Option<PendingExport>,
+    #[serde(skip)]
+    pub batch_export: Option<BatchExport>,
+    /// Kept for the whole session, on some platforms the copied content is lost when it's dropped
+    #[serde(skip)]
+    pub clipboard: Option<arboard::Clipboard>,
+    #[serde(skip)]
+    pub error: Option<String>,
+    #[serde(skip)]
+    pub warnings: Vec<String>,
+    /// Short confirmation that is hidden after [`TOAST_DURATION`]
+    #[serde(skip)]
+    pub toast: Option<(String, Instant)>,
+    /// Set once the window is closed, so the last save is stored as clean exit
+    #[serde(skip)]
+    pub closing: bool,
+    /// Autosave of the last session if it wasn't closed properly, until restoring it is accepted
+    /// or declined
+    #[serde(skip)]
+    pub crashed_session: Option<Box<PlotApp>>,
+}
+
+impl Default for PlotApp {
+    fn default() -> Self {
+        Self {
+            config: Config::default(),
+            files: None,
+            recent: Vec::new(),
+            scan_subfolders: false,
+            auto_reload: false,
+            poll_reload: false,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            timestamp_order: TimestampOrder::default(),
+            export_dpi: DEFAULT_EXPORT_DPI,
+            export_markers: true,
+            autosave_interval: DEFAULT_AUTOSAVE_INTERVAL,
+            theme: Theme::default(),
+            selectable_files: None,
+            data: None,
+            loading: None,
+            watcher: None,
+            poller: None,
+            stdin: None,
+            pending_export: None,
+            batch_export: None,
+            clipboard: None,
+            error: None,
+            warnings: Vec::new(),
+            toast: None,
+            closing: false,
+            crashed_session: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    /// Follow the system preference, if it can be detected
+    #[default]
+    System,
+    Dark,
+    Light,
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::System => write!(f, "System"),
+            Self::Dark => write!(f, "Dark"),
+            Self::Light => write!(f, "Light"),
+        }
+    }
+}
+
+impl Theme {
+    pub const ALL: [Self; 3] = [Self::System, Self::Dark, Self::Light];
+
+    fn is_dark(&self, frame: &eframe::Frame) -> bool {
+        match self {
+            Self::System => frame.info().system_theme != Some(eframe::Theme::Light),
+            Self::Dark => true,
+            Self::Light => false,
+        }
+    }
+}
+
+/// What to do with samples whose timestamp is smaller than the one of the previous sample
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimestampOrder {
+    /// Keep the order and add a warning
+    #[default]
+    Warn,
+    Sort,
+    Keep,
+}
+
+impl fmt::Display for TimestampOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Warn => write!(f, "Warn"),
+            Self::Sort => write!(f, "Sort"),
+            Self::Keep => write!(f, "Leave as is"),
+        }
+    }
+}
+
+impl TimestampOrder {
+    pub const ALL: [Self; 3] = [Self::Warn, Self::Sort, Self::Keep];
 }
 
 pub struct PlotData {
+    /// Directory of the loaded run
+    pub dir: PathBuf,
     pub streams: Arc<[LogStream]>,
     pub plots: Vec<Vec<PlotValues>>,
+    /// Times in seconds where appended runs start
+    pub seams: Vec<f64>,
+    /// Index of a stream and a time range in ms where its timestamps go backwards, marked when
+    /// warning about them
+    pub backwards: Vec<(usize, u32, u32)>,
+    /// Number of samples read from each of the opened files, or `None` if samples written to it
+    /// later can't be appended, because it's compressed or other files follow it in its stream
+    pub file_samples: Vec<Option<usize>>,
+    pub comparison: Option<Comparison>,
+    /// Faintly drawn behind the plots, kept when loading other runs
+    pub baseline: Option<Comparison>,
+}
+
+/// Another run whose plots are drawn along the loaded ones
+pub struct Comparison {
+    pub name: String,
+    /// Directory of the run, which its settings are keyed by
+    pub dir: PathBuf,
+    pub streams: Arc<[LogStream]>,
+    /// Values keyed by tab id and plot index, evaluated once the tab is shown
+    pub plots: HashMap<(u64, usize), (JobInputs, PlotValues)>,
+}
+
+impl Comparison {
+    pub fn new(dir: &Path, streams: Arc<[LogStream]>) -> Self {
+        let name = (dir.file_name())
+            .map_or_else(|| "comparison".into(), |n| n.to_string_lossy().into_owned());
+        Self {
+            name,
+            dir: dir.to_path_buf(),
+            streams,
+            plots: HashMap::new(),
+        }
+    }
+}
+
+/// Everything the values of a job depend on
+#[derive(PartialEq)]
+pub struct JobInputs {
+    pub expr: Expr,
+    pub processing: Processing,
+    pub globals: Globals,
 }
 
 pub enum PlotValues {
     Job(Job),
-    Result(Result<Vec<PlotPoint>, Box<ExprError>>),
+    Result(Result<Series, Box<ExprError>>),
 }
 
 impl PlotValues {
     pub const fn empty() -> Self {
-        Self::Result(Ok(Vec::new()))
+        Self::Result(Ok(Series::default()))
     }
 
     pub fn into_job(self) -> Option<Job> {
@@ -51,14 +263,14 @@ impl PlotValues {
         }
     }
 
-    pub fn x_err(&self) -> Option<&cods::Error> {
+    pub fn x_err(&self) -> Option<&eval::Error> {
         match self {
             PlotValues::Result(Err(e)) => e.x.as_ref(),
             _ => None,
         }
     }
 
-    pub fn y_err(&self) -> Option<&cods::Error> {
+    pub fn y_err(&self) -> Option<&eval::Error> {
         match self {
             PlotValues::Result(Err(e)) => e.y.as_ref(),
             _ => None,
@@ -67,12 +279,19 @@ impl PlotValues {
 }
 
 pub struct Job {
-    handle: JoinHandle<Result<Vec<PlotPoint>, Box<ExprError>>>,
+    handle: JoinHandle<Result<Series, Box<ExprError>>>,
 }
 
 impl Job {
-    pub fn start(expr: Expr, data: Arc<[LogStream]>) -> Self {
-        let handle = std::thread::spawn(move || eval::eval(&expr, data));
+    pub fn start(
+        expr: Expr,
+        data: Arc<[LogStream]>,
+        globals: Globals,
+        processing: Processing,
+    ) -> Self {
+        let handle = std::thread::spawn(move || {
+            eval::eval(&expr, data, &globals).map(|v| processing.apply(v))
+        });
         Self { handle }
     }
 
@@ -80,36 +299,167 @@ impl Job {
         self.handle.is_finished()
     }
 
-    pub fn join(self) -> Result<Vec<PlotPoint>, Box<ExprError>> {
+    pub fn join(self) -> Result<Series, Box<ExprError>> {
         self.handle.join().expect("failed to join worker thread")
     }
 }
 
 impl eframe::App for PlotApp {
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
-        eframe::set_value(storage, eframe::APP_KEY, self);
+        let key = if self.closing {
+            eframe::APP_KEY
+        } else {
+            AUTOSAVE_KEY
+        };
+        eframe::set_value(storage, key, self);
+        eframe::set_value(storage, RUNNING_KEY, &!self.closing);
     }
 
-    fn update(&mut self, ctx: &egui::Context, _: &mut eframe::Frame) {
-        if ctx.input_mut(|i| i.consume_key(Modifiers::CTRL, Key::O)) {
-            self.open_dir_dialog();
+    fn auto_save_interval(&self) -> std::time::Duration {
+        let secs = self.autosave_interval.clamp(1.0, MAX_AUTOSAVE_INTERVAL);
+        std::time::Duration::from_secs_f32(secs)
+    }
+
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        if ctx.input(|i| i.viewport().close_requested()) {
+            self.closing = true;
+        }
+
+        let dark = self.theme.is_dark(frame);
+        if ctx.style().visuals.dark_mode != dark {
+            let visuals = if dark {
+                Visuals::dark()
+            } else {
+                Visuals::light()
+            };
+            ctx.set_visuals(visuals);
         }
-        if ctx.input_mut(|i| i.consume_key(Modifiers::CTRL | Modifiers::SHIFT, Key::O)) {
+
+        ctx.tessellation_options_mut(|o| o.feathering = self.config.antialiasing);
+
+        self.update_loading(ctx);
+        self.update_stdin();
+        self.update_export(ctx);
+        self.update_batch_export(ctx);
+        self.config.hide_markers = self.pending_export.is_some() && !self.export_markers;
+        self.update_watcher(ctx);
+
+        // check the shortcut with more modifiers first, so it isn't consumed by the other one
+        if ctx.input_mut(|i| i.consume_shortcut(&REOPEN_DIR)) {
             if let Some(files) = &self.files {
-                self.try_open_dir(files.dir.clone());
+                self.try_open(files.dir.clone());
+            }
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&OPEN_DIR)) {
+            self.open_dir_dialog();
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&RELOAD_FILES)) {
+            if let Some(files) = self.files.clone() {
+                self.try_open_files(files, false);
+            }
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&EXPORT_PNG)) {
+            self.export_png_dialog(ctx);
+        }
+        // egui turns the copy shortcut into an event, even with shift held
+        let (copy, shift) = ctx.input(|i| {
+            let copy = i.events.iter().any(|e| matches!(e, Event::Copy));
+            (copy, i.modifiers.shift)
+        });
+        let plot_hovered = (self.config.plot_rect)
+            .zip(ctx.pointer_hover_pos())
+            .is_some_and(|(r, p)| r.contains(p));
+        // don't take over copying text
+        if copy && ctx.memory(|m| m.focused().is_none()) {
+            if shift {
+                self.copy_values();
+            } else if plot_hovered {
+                self.copy_png(ctx);
+            }
+        }
+        // text inputs have their own undo
+        if ctx.memory(|m| m.focused().is_none()) {
+            if let Some(data) = &mut self.data {
+                let redo =
+                    ctx.input_mut(|i| i.consume_shortcut(&REDO_ALT) || i.consume_shortcut(&REDO));
+                if redo {
+                    plot::redo(data, &mut self.config);
+                } else if ctx.input_mut(|i| i.consume_shortcut(&UNDO)) {
+                    plot::undo(data, &mut self.config);
+                }
             }
         }
 
         TopBottomPanel::top("top_panel").show(ctx, |ui| {
             menu::bar(ui, |ui| {
                 ui.menu_button("File", |ui| {
-                    if ui.button("Open dir").clicked() {
+                    if shortcut_button(ui, "Open dir", &OPEN_DIR).clicked() {
                         self.open_dir_dialog();
                         ui.close_menu();
                     }
-                    if ui.button("Reopen dir").clicked() {
+                    if ui.button("Open file").clicked() {
+                        self.open_file_dialog();
+                        ui.close_menu();
+                    }
+                    ui.menu_button("Recent", |ui| {
+                        self.recent.retain(|p| p.exists());
+                        if self.recent.is_empty() {
+                            ui.label("No recent files");
+                        }
+
+                        let mut clicked = None;
+                        for p in self.recent.iter() {
+                            if ui.button(p.display().to_string()).clicked() {
+                                clicked = Some(p.clone());
+                            }
+                        }
+                        if let Some(p) = clicked {
+                            self.try_open(p);
+                            ui.close_menu();
+                        }
+                    });
+                    if ui.button("Append dir").clicked() {
+                        self.append_dir_dialog();
+                        ui.close_menu();
+                    }
+                    if ui.button("Compare dir").clicked() {
+                        self.compare_dir_dialog();
+                        ui.close_menu();
+                    }
+                    let comparing = self.data.as_ref().is_some_and(|d| d.comparison.is_some());
+                    if ui
+                        .add_enabled(comparing, Button::new("Close comparison"))
+                        .clicked()
+                    {
+                        if let Some(data) = &mut self.data {
+                            data.comparison = None;
+                        }
+                        ui.close_menu();
+                    }
+                    let loaded = self.data.is_some();
+                    if ui
+                        .add_enabled(loaded, Button::new("Use as baseline"))
+                        .clicked()
+                    {
+                        if let Some(data) = &mut self.data {
+                            let streams = Arc::clone(&data.streams);
+                            data.baseline = Some(Comparison::new(&data.dir, streams));
+                        }
+                        ui.close_menu();
+                    }
+                    let has_baseline = self.data.as_ref().is_some_and(|d| d.baseline.is_some());
+                    if ui
+                        .add_enabled(has_baseline, Button::new("Clear baseline"))
+                        .clicked()
+                    {
+                        if let Some(data) = &mut self.data {
+                            data.baseline = None;
+                        }
+                        ui.close_menu();
+                    }
+                    if shortcut_button(ui, "Reopen dir", &REOPEN_DIR).clicked() {
                         if let Some(files) = &self.files {
-                            self.try_open_dir(files.dir.clone());
+                            self.try_open(files.dir.clone());
                         }
                         ui.close_menu();
                     }
@@ -119,6 +469,201 @@ impl eframe::App for PlotApp {
                         }
                         ui.close_menu();
                     }
+                    if shortcut_button(ui, "Reload files", &RELOAD_FILES).clicked() {
+                        if let Some(files) = self.files.clone() {
+                            self.try_open_files(files, false);
+                        }
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui.button("Save workspace").clicked() {
+                        self.save_workspace_dialog();
+                        ui.close_menu();
+                    }
+                    if ui.button("Load workspace").clicked() {
+                        self.load_workspace_dialog();
+                        ui.close_menu();
+                    }
+                    if ui.button("Export plots").clicked() {
+                        self.export_plots_dialog();
+                        ui.close_menu();
+                    }
+                    if ui.button("Import plots").clicked() {
+                        self.import_plots_dialog();
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    ui.checkbox(&mut self.auto_reload, "Auto reload");
+                    ui.add_enabled_ui(self.auto_reload, |ui| {
+                        ui.checkbox(&mut self.poll_reload, "Poll").on_hover_text(
+                            "Check for changes in an interval, e.g. on network shares. Samples \
+                            written to the end of the files are appended without reloading.",
+                        );
+                        let interval = &mut self.poll_interval;
+                        let slider = Slider::new(interval, 1.0..=MAX_POLL_INTERVAL)
+                            .logarithmic(true)
+                            .suffix(" s")
+                            .text("poll interval");
+                        ui.add_enabled(self.poll_reload, slider);
+                    });
+                    ComboBox::from_label("backwards timestamps")
+                        .selected_text(self.timestamp_order.to_string())
+                        .show_ui(ui, |ui| {
+                            for o in TimestampOrder::ALL {
+                                ui.selectable_value(&mut self.timestamp_order, o, o.to_string());
+                            }
+                        })
+                        .response
+                        .on_hover_text("Applied when files are loaded");
+                });
+                ui.menu_button("Edit", |ui| {
+                    if shortcut_button(ui, "Undo", &UNDO).clicked() {
+                        if let Some(data) = &mut self.data {
+                            plot::undo(data, &mut self.config);
+                        }
+                        ui.close_menu();
+                    }
+                    if shortcut_button(ui, "Redo", &REDO).clicked() {
+                        if let Some(data) = &mut self.data {
+                            plot::redo(data, &mut self.config);
+                        }
+                        ui.close_menu();
+                    }
+                });
+                ui.menu_button("Export", |ui| {
+                    if shortcut_button(ui, "PNG", &EXPORT_PNG).clicked() {
+                        self.export_png_dialog(ui.ctx());
+                        ui.close_menu();
+                    }
+                    if ui.button("SVG").clicked() {
+                        self.export_svg_dialog();
+                        ui.close_menu();
+                    }
+                    if shortcut_button(ui, "Copy PNG", &COPY_PNG).clicked() {
+                        self.copy_png(ui.ctx());
+                        ui.close_menu();
+                    }
+                    let copy_values = shortcut_button(ui, "Copy values at cursor", &COPY_VALUES)
+                        .on_hover_text(
+                            "At the inspected or last pinned time while not hovering a plot",
+                        );
+                    if copy_values.clicked() {
+                        self.copy_values();
+                        ui.close_menu();
+                    }
+                    if ui.button("CSV").clicked() {
+                        self.export_csv_dialog();
+                        ui.close_menu();
+                    }
+                    if ui.button("CSV of all runs in a folder").clicked() {
+                        self.batch_export_csv_dialog(ui.ctx());
+                        ui.close_menu();
+                    }
+                    ui.add(Slider::new(&mut self.export_dpi, 72.0..=1200.0).text("print dpi"))
+                        .on_hover_text(
+                            "Stored in exported PNGs, they keep the pixels of the plot on screen",
+                        );
+                    ui.checkbox(&mut self.export_markers, "Include markers");
+                });
+                ui.menu_button("View", |ui| {
+                    let threshold = &mut self.config.decimation_threshold;
+                    let slider = Slider::new(threshold, 1000..=1_000_000)
+                        .logarithmic(true)
+                        .text("points before decimation");
+                    ui.add(slider);
+                    ui.horizontal(|ui| {
+                        let max_points = &mut self.config.max_points;
+                        let mut limit = max_points.is_some();
+                        let checkbox = ui
+                            .checkbox(&mut limit, "Limit points")
+                            .on_hover_text("Draw fewer points per plot for a higher frame rate");
+                        if checkbox.changed() {
+                            *max_points = limit.then_some(plot::DEFAULT_MAX_POINTS);
+                        }
+                        if let Some(max) = max_points {
+                            ui.add(DragValue::new(max).speed(10).clamp_range(100..=100_000));
+                        }
+                    });
+                    ui.checkbox(&mut self.config.antialiasing, "Antialiasing")
+                        .on_hover_text(
+                            "Smooth the edges of lines, turn off for a higher frame rate",
+                        );
+                    ui.checkbox(&mut self.config.show_fps, "Show frame rate");
+                    let width = &mut self.config.line_width;
+                    ui.add(Slider::new(width, 0.1..=plot::MAX_LINE_WIDTH).text("line width"));
+                    ComboBox::from_label("line style")
+                        .selected_text(self.config.line_style.to_string())
+                        .show_ui(ui, |ui| {
+                            for s in LineStyle::ALL {
+                                ui.selectable_value(&mut self.config.line_style, s, s.to_string());
+                            }
+                        });
+                    ComboBox::from_label("palette")
+                        .selected_text(self.config.palette.to_string())
+                        .show_ui(ui, |ui| {
+                            for p in Palette::ALL {
+                                ui.selectable_value(&mut self.config.palette, p, p.to_string());
+                            }
+                        });
+                    ComboBox::from_label("layout")
+                        .selected_text(self.config.layout.to_string())
+                        .show_ui(ui, |ui| {
+                            for l in PlotLayout::ALL {
+                                ui.selectable_value(&mut self.config.layout, l, l.to_string());
+                            }
+                        })
+                        .response
+                        .on_hover_text("Show the plots of multiple tabs at once");
+                    ui.checkbox(&mut self.config.show_overview, "Show overview");
+                    ui.checkbox(&mut self.config.show_coordinates, "Show coordinates")
+                        .on_hover_text("Show the pointer position in the corner of the plot");
+                    ui.menu_button("Grid", |ui| {
+                        plot::grid_editor(ui, &mut self.config.grid);
+                    });
+                    let number_format = &mut self.config.number_format;
+                    let digits = &mut number_format.significant_digits;
+                    ui.add(
+                        Slider::new(digits, 1..=util::MAX_SIGNIFICANT_DIGITS)
+                            .text("significant digits"),
+                    );
+                    ui.checkbox(&mut number_format.scientific, "Scientific notation")
+                        .on_hover_text("Use scientific notation for very large and small values");
+                    ui.checkbox(&mut self.config.align_comparison, "Align comparison start")
+                        .on_hover_text(
+                            "Shift the compared and baseline runs to start at the same time",
+                        );
+                    ui.horizontal(|ui| {
+                        let threshold = &mut self.config.gap_threshold;
+                        let mut break_gaps = threshold.is_some();
+                        if ui
+                            .checkbox(&mut break_gaps, "Break lines at gaps")
+                            .changed()
+                        {
+                            *threshold = break_gaps.then_some(plot::DEFAULT_GAP_THRESHOLD);
+                        }
+                        if let Some(t) = threshold {
+                            let value = DragValue::new(t).speed(0.01).clamp_range(0.001..=f64::MAX);
+                            ui.add(value.suffix(" s"));
+                        }
+                    });
+                    ui.checkbox(&mut self.config.shade_gaps, "Shade gaps");
+                    ui.checkbox(&mut self.config.wall_clock, "Wall clock time")
+                        .on_hover_text("Show the time of day in time plots, see the start time");
+                    ui.checkbox(&mut self.config.show_seams, "Show seams")
+                        .on_hover_text("Mark where appended runs start");
+                    ui.checkbox(&mut self.config.link_x, "Link x-axis")
+                        .on_hover_text("Share the time range between all tabs in time mode");
+                    let interval = &mut self.autosave_interval;
+                    ui.add(
+                        Slider::new(interval, 1.0..=MAX_AUTOSAVE_INTERVAL)
+                            .logarithmic(true)
+                            .suffix(" s")
+                            .text("autosave interval"),
+                    );
+                    ui.separator();
+                    for t in Theme::ALL {
+                        ui.radio_value(&mut self.theme, t, t.to_string());
+                    }
                 });
 
                 ui.add_space(40.0);
@@ -142,6 +687,63 @@ impl eframe::App for PlotApp {
             });
         });
 
+        if self.error.is_some() || !self.warnings.is_empty() {
+            let mut dismiss_error = false;
+            let mut dismiss_warnings = false;
+            TopBottomPanel::bottom("message_panel").show(ctx, |ui| {
+                if let Some(error) = &self.error {
+                    ui.horizontal(|ui| {
+                        dismiss_error = ui.add(Button::new("🗙").frame(false)).clicked();
+                        let error = RichText::new(error).color(Color32::RED);
+                        ui.add(Label::new(error).selectable(true));
+                    });
+                }
+                if !self.warnings.is_empty() {
+                    ui.horizontal(|ui| {
+                        dismiss_warnings = ui.add(Button::new("🗙").frame(false)).clicked();
+                        ui.vertical(|ui| {
+                            for w in self.warnings.iter() {
+                                let warning = RichText::new(w).color(Color32::YELLOW);
+                                ui.add(Label::new(warning).selectable(true));
+                            }
+                        });
+                    });
+                }
+            });
+            if dismiss_error {
+                self.error = None;
+            }
+            if dismiss_warnings {
+                self.warnings.clear();
+            }
+        }
+
+        if let Some((text, shown)) = &self.toast {
+            let remaining = TOAST_DURATION.saturating_sub(shown.elapsed());
+            if remaining.is_zero() {
+                self.toast = None;
+            } else {
+                Area::new("toast".into())
+                    .anchor(Align2::RIGHT_BOTTOM, [-10.0, -10.0])
+                    .show(ctx, |ui| {
+                        Frame::popup(ui.style()).show(ui, |ui| ui.label(text.as_str()));
+                    });
+                ctx.request_repaint_after(remaining);
+            }
+        }
+
+        if self.config.show_fps {
+            let fps = 1.0 / ctx.input(|i| i.stable_dt).max(f32::EPSILON);
+            let mut text = format!("{fps:.0} fps");
+            if let Some(cpu) = frame.info().cpu_usage {
+                text.push_str(&format!(", {:.1} ms per frame", 1000.0 * cpu));
+            }
+            Area::new("fps".into())
+                .anchor(Align2::LEFT_BOTTOM, [10.0, -10.0])
+                .interactable(false)
+                .show(ctx, |ui| ui.weak(text));
+        }
+
         CentralPanel::default().show(ctx, |ui| {
             if self.selectable_files.is_some() {
                 ui.label("...");
@@ -149,12 +751,15 @@ impl eframe::App for PlotApp {
                 plot::keybindings(ui, data, &mut self.config);
                 plot::tab_bar(ui, data, &mut self.config);
                 plot::tab_plot(ui, data, &mut self.config);
+            } else if self.stdin.is_some() {
+                ui.label("Waiting for samples on stdin");
             } else {
                 ui.label("Open or drag and drop a directory");
             }
         });
 
         if let Some(files) = &mut self.selectable_files {
+            let scan_subfolders = &mut self.scan_subfolders;
             let mut open = true;
             let r = Window::new("Select files")
                 .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
@@ -162,24 +767,137 @@ impl eframe::App for PlotApp {
                 .open(&mut open)
                 .collapsible(false)
                 .resizable(false)
-                .show(ctx, |ui| select_files_dialog(ui, files));
+                .show(ctx, |ui| select_files_dialog(ui, files, scan_subfolders));
 
             match r {
-                Some(r) if open => {
-                    if let Some(true) = r.inner {
+                Some(r) if open => match r.inner.flatten() {
+                    Some(SelectFilesAction::Confirm) => {
                         let files = self.selectable_files.take().unwrap();
                         self.concat_and_show(files);
                     }
-                }
+                    Some(SelectFilesAction::SelectRun(run)) => self.select_run(run),
+                    Some(SelectFilesAction::Rescan) => {
+                        let root = self.selectable_files.take().unwrap().root;
+                        self.try_open(root);
+                    }
+                    None => (),
+                },
                 _ => self.selectable_files = None,
             }
         }
 
+        if let Some(loading) = &self.loading {
+            let mut cancelled = false;
+            Window::new("Loading")
+                .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    let bar = ProgressBar::new(loading.progress())
+                        .desired_width(300.0)
+                        .show_percentage();
+                    ui.add(bar);
+                    cancelled = ui.button("Cancel").clicked();
+                });
+            if cancelled {
+                self.cancel_loading();
+            }
+        }
+
+        if let Some(batch) = &self.batch_export {
+            let mut cancelled = false;
+            Window::new("Exporting CSV")
+                .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    let bar = ProgressBar::new(batch.progress())
+                        .desired_width(300.0)
+                        .text(batch.text());
+                    ui.add(bar);
+                    cancelled = ui.button("Cancel").clicked();
+                });
+            if cancelled {
+                self.cancel_batch_export();
+            }
+        }
+
+        if self.crashed_session.is_some() {
+            let mut restore = None;
+            Window::new("Restore session")
+                .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label("The last session wasn't closed properly.");
+                    ui.label("Restore the plots and settings of its last autosave?");
+                    ui.horizontal(|ui| {
+                        if ui.button("Restore").clicked() {
+                            restore = Some(true);
+                        }
+                        if ui.button("Discard").clicked() {
+                            restore = Some(false);
+                        }
+                    });
+                });
+            if let Some(restore) = restore {
+                let session = self.crashed_session.take().unwrap();
+                if restore {
+                    self.restore_session(*session);
+                }
+            }
+        }
+
         self.detect_files_being_dropped(ctx);
     }
 }
 
-pub fn select_files_dialog(ui: &mut Ui, opened_files: &mut SelectableFiles) -> bool {
+fn shortcut_button(ui: &mut Ui, text: &str, shortcut: &KeyboardShortcut) -> egui::Response {
+    let shortcut_text = ui.ctx().format_shortcut(shortcut);
+    ui.add(Button::new(text).shortcut_text(shortcut_text))
+}
+
+pub enum SelectFilesAction {
+    Confirm,
+    SelectRun(usize),
+    Rescan,
+}
+
+pub fn select_files_dialog(
+    ui: &mut Ui,
+    opened_files: &mut SelectableFiles,
+    scan_subfolders: &mut bool,
+) -> Option<SelectFilesAction> {
+    let mut action = None;
+
+    ui.horizontal(|ui| {
+        if ui.checkbox(scan_subfolders, "scan subfolders").changed() {
+            action = Some(SelectFilesAction::Rescan);
+        }
+
+        if opened_files.runs.len() > 1 {
+            let root = opened_files.root.as_path();
+            let run_name = |dir: &Path| match dir.strip_prefix(root) {
+                Ok(p) if p.as_os_str().is_empty() => ".".to_string(),
+                Ok(p) => p.display().to_string(),
+                Err(_) => dir.display().to_string(),
+            };
+
+            let selected = &opened_files.runs[opened_files.selected_run];
+            ComboBox::from_label("Run")
+                .selected_text(run_name(&selected.dir))
+                .show_ui(ui, |ui| {
+                    for (i, r) in opened_files.runs.iter().enumerate() {
+                        let is_selected = i == opened_files.selected_run;
+                        if ui.selectable_label(is_selected, run_name(&r.dir)).clicked() {
+                            action = Some(SelectFilesAction::SelectRun(i));
+                        }
+                    }
+                });
+        }
+    });
+    ui.add_space(10.0);
+
     let common_prefix = opened_files.dir.as_path();
 
     for (i, group) in opened_files.by_header.iter_mut().enumerate() {
@@ -189,11 +907,15 @@ pub fn select_files_dialog(ui: &mut Ui, opened_files: &mut SelectableFiles) -> b
         ui.add_space(20.0);
     }
 
-    error_files_table(ui, &opened_files.with_error, common_prefix);
+    error_files_table(ui, &opened_files.with_error);
 
     ui.add_space(20.0);
 
-    ui.horizontal(|ui| ui.button("Ok").clicked()).inner
+    if ui.horizontal(|ui| ui.button("Ok").clicked()).inner {
+        action = Some(SelectFilesAction::Confirm);
+    }
+
+    action
 }
 
 enum MoveDirection {
@@ -280,9 +1002,10 @@ fn select_files_table(ui: &mut Ui, files: &mut Vec<SelectableFile>, common_prefi
 
                                         let local_start = Local
                                             .from_utc_datetime(&start)
-                                            .format(DATE_TIME_FORMAT);
-                                        let local_end =
-                                            Local.from_utc_datetime(&end).format(DATE_TIME_FORMAT);
+                                            .format(util::DATE_TIME_FORMAT);
+                                        let local_end = Local
+                                            .from_utc_datetime(&end)
+                                            .format(util::DATE_TIME_FORMAT);
 
                                         ui.label(format!("{local_start} - {local_end}"));
                                     }
@@ -322,13 +1045,17 @@ fn select_files_table(ui: &mut Ui, files: &mut Vec<SelectableFile>, common_prefi
     }
 }
 
-fn error_files_table(ui: &mut Ui, files: &[ErrorFile], common_prefix: &Path) {
+fn error_files_table(ui: &mut Ui, files: &[ErrorFile]) {
     TableBuilder::new(ui)
-        .column(Column::exact(400.0)) // file name
-        .column(Column::exact(500.0)) // error
-        .resizable(false)
+        .column(Column::exact(80.0)) // version
+        .column(Column::initial(400.0).at_least(100.0).clip(true)) // file path
+        .column(Column::remainder().at_least(100.0).clip(true)) // error
+        .resizable(true)
         .striped(true)
         .header(20.0, |mut header| {
+            header.col(|ui| {
+                ui.heading("Version");
+            });
             header.col(|ui| {
                 ui.heading("File");
             });
@@ -339,15 +1066,23 @@ fn error_files_table(ui: &mut Ui, files: &[ErrorFile], common_prefix: &Path) {
         .body(|mut body| {
             for e in files.iter() {
                 body.row(20.0, |mut row| {
+                    row.col(|ui| {
+                        ui.horizontal_centered(|ui| match e.version {
+                            Some(v) => ui.label(v.to_string()),
+                            None => ui.weak("-"),
+                        });
+                    });
                     row.col(|ui| {
                         ui.horizontal_centered(|ui| {
-                            let name = e.file.strip_prefix(common_prefix).unwrap();
-                            ui.label(name.display().to_string());
+                            // the whole text is shown on hover
+                            let path = Label::new(e.file.display().to_string()).truncate();
+                            ui.add(path.selectable(true));
                         });
                     });
                     row.col(|ui| {
                         ui.horizontal_centered(|ui| {
-                            ui.label(RichText::new(e.error.to_string()).color(Color32::RED));
+                            let error = RichText::new(e.error.to_string()).color(Color32::RED);
+                            ui.add(Label::new(error).truncate().selectable(true));
                         });
                     });
                 });
@@ -356,15 +1091,87 @@ fn error_files_table(ui: &mut Ui, files: &[ErrorFile], common_prefix: &Path) {
 }
 
 impl PlotApp {
-    pub fn new(context: &eframe::CreationContext) -> Self {
-        let mut app = context
-            .storage
+    /// Open `path` if passed, otherwise reopen the files of the last session. If the last session
+    /// wasn't closed properly, restoring its autosave is offered.
+    pub fn new(context: &eframe::CreationContext, path: Option<PathBuf>) -> Self {
+        let storage = context.storage;
+        let mut app = storage
             .and_then(|s| eframe::get_value::<PlotApp>(s, eframe::APP_KEY))
             .unwrap_or_default();
+        let crashed = storage.and_then(|s| eframe::get_value::<bool>(s, RUNNING_KEY));
+        if crashed == Some(true) {
+            app.crashed_session = storage
+                .and_then(|s| eframe::get_value::<PlotApp>(s, AUTOSAVE_KEY))
+                .map(Box::new);
+        }
 
-        if let Some(f) = app.files.clone() {
+        if path.as_deref() == Some(Path::new("-")) {
+            app.open_stdin(&context.egui_ctx);
+        } else if let Some(p) = path {
+            app.try_open_direct(p);
+        } else if let Some(f) = app.files.clone() {
             app.try_open_files(f, false);
         }
         app
     }
+
+    /// Replaces the saved state with the one of a crashed session, its files are opened unless
+    /// they already are
+    fn restore_session(&mut self, session: PlotApp) {
+        let PlotApp {
+            config,
+            files,
+            recent,
+            scan_subfolders,
+            auto_reload,
+            poll_reload,
+            poll_interval,
+            timestamp_order,
+            export_dpi,
+            export_markers,
+            autosave_interval,
+            theme,
+            // the state of this session
+            selectable_files: _,
+            data: _,
+            loading: _,
+            watcher: _,
+            poller: _,
+            stdin: _,
+            pending_export: _,
+            batch_export: _,
+            clipboard: _,
+            error: _,
+            warnings: _,
+            toast: _,
+            closing: _,
+            crashed_session: _,
+        } = session;
+        self.config = config;
+        if self.config.tabs.is_empty() {
+            self.config = Config::default();
+        }
+        self.config.selected_tab = self.config.selected_tab.min(self.config.tabs.len() - 1);
+        self.recent = recent;
+        self.scan_subfolders = scan_subfolders;
+        self.auto_reload = auto_reload;
+        self.poll_reload = poll_reload;
+        self.poll_interval = poll_interval;
+        self.timestamp_order = timestamp_order;
+        self.export_dpi = export_dpi;
+        self.export_markers = export_markers;
+        self.autosave_interval = autosave_interval;
+        self.theme = theme;
+
+        // the plots have to match the restored tabs until the files are loaded
+        if let Some(data) = &mut self.data {
+            self.config
+                .migrate_stream_settings(&data.dir, &data.streams);
+            data.plots = plot::start_all_jobs(&self.config, &data.dir, &data.streams);
+        }
+        let files = files.filter(|f| f.items.iter().all(|p| p.exists()));
+        if let Some(files) = files.filter(|f| self.files.as_ref() != Some(f)) {
+            self.try_open_files(files, false);
+        }
+    }
 }
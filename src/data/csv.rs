@@ -0,0 +1,131 @@
+use std::io::{self, BufRead};
+
+use super::{DataEntry, EntryKind, Error, LogStream, Version};
+
+/// Reads a CSV file with a `time` column in seconds and a column per entry, as written by the
+/// CSV export. Empty cells are read as NaN, columns which contain values that aren't numbers are
+/// skipped and returned as warnings. Cells can be quoted, e.g. to contain commas.
+pub fn read_csv(reader: impl BufRead) -> Result<(LogStream, Vec<String>), Error> {
+    let mut records = records(reader.lines());
+    let header = match records.next() {
+        Some(record) => record?.1,
+        None => return Err(Error::InvalidCsv("missing header".into())),
+    };
+    let names: Vec<&str> = header.iter().map(|n| n.trim()).collect();
+    let time_column = (names.iter())
+        .position(|n| n.eq_ignore_ascii_case("time"))
+        .ok_or_else(|| Error::InvalidCsv("missing time column".into()))?;
+
+    let mut time = Vec::new();
+    let mut columns: Vec<Option<Vec<f64>>> = vec![Some(Vec::new()); names.len()];
+    for record in records {
+        let (i, cells) = record?;
+        if cells.iter().all(|c| c.trim().is_empty()) {
+            continue;
+        }
+
+        let cells: Vec<&str> = cells.iter().map(|c| c.trim()).collect();
+        let t = (cells.get(time_column))
+            .and_then(|c| c.parse::<f64>().ok())
+            .filter(|t| t.is_finite() && *t >= 0.0)
+            .ok_or_else(|| Error::InvalidCsv(format!("invalid time in line {}", i + 1)))?;
+        time.push((t * 1000.0).round() as u32);
+
+        for (j, column) in columns.iter_mut().enumerate() {
+            let Some(values) = column else {
+                continue;
+            };
+            match cells.get(j).copied().unwrap_or("") {
+                "" => values.push(f64::NAN),
+                c => match c.parse() {
+                    Ok(v) => values.push(v),
+                    Err(_) => *column = None,
+                },
+            }
+        }
+    }
+
+    let mut warnings = Vec::new();
+    let mut entries = Vec::new();
+    for (j, (name, column)) in names.iter().zip(columns).enumerate() {
+        if j == time_column {
+            continue;
+        }
+        match column {
+            Some(values) => entries.push(DataEntry {
+                name: name.to_string(),
+                kind: EntryKind::F64(values),
+            }),
+            None => warnings.push(format!("Ignored column '{name}', it contains non numbers")),
+        }
+    }
+
+    let stream = LogStream {
+        version: Version::V2,
+        start: None,
+        time,
+        entries,
+    };
+    Ok((stream, warnings))
+}
+
+/// The cells of the records and the index of the line they start at. A quoted cell can contain
+/// commas, line breaks and quotes, which are escaped by doubling them.
+fn records(
+    mut lines: impl Iterator<Item = io::Result<String>>,
+) -> impl Iterator<Item = Result<(usize, Vec<String>), Error>> {
+    let mut index = 0;
+    std::iter::from_fn(move || {
+        let start = index;
+        let mut cells = Vec::new();
+        let mut cell = String::new();
+        let mut quoted = false;
+        loop {
+            let line = match lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(e)) => return Some(Err(e.into())),
+                // a quote that isn't closed ends with the file
+                None if index > start => break,
+                None => return None,
+            };
+            index += 1;
+
+            let mut chars = line.chars().peekable();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' if quoted && chars.next_if_eq(&'"').is_some() => cell.push('"'),
+                    '"' => quoted = !quoted,
+                    ',' if !quoted => cells.push(std::mem::take(&mut cell)),
+                    _ => cell.push(c),
+                }
+            }
+            if !quoted {
+                break;
+            }
+            cell.push('\n');
+        }
+        cells.push(cell);
+        Some(Ok((start, cells)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_cells() {
+        let csv = "time,\"a, b\",marker\n0,1,\"say \"\"hi\"\"\"\n1,2,\"two\nlines\"\n2,3,\n";
+        let (stream, warnings) = read_csv(csv.as_bytes()).unwrap();
+        assert_eq!(stream.time, [0, 1000, 2000]);
+        assert_eq!(stream.entries[0].name, "a, b");
+        match &stream.entries[0].kind {
+            EntryKind::F64(v) => assert_eq!(v, &[1.0, 2.0, 3.0]),
+            _ => panic!("expected f64 values"),
+        }
+        assert_eq!(
+            warnings,
+            ["Ignored column 'marker', it contains non numbers"]
+        );
+    }
+}
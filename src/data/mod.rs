@@ -1,15 +1,22 @@
+use std::path::PathBuf;
 use std::string::FromUtf8Error;
 use std::{fmt, io};
 
 use chrono::NaiveDateTime;
 
-pub use crate::data::read::read_file;
+pub use crate::data::csv::read_csv;
+pub use crate::data::read::{
+    read_file, read_file_from, read_stream, read_stream_live, read_version,
+};
 pub use crate::data::sanity::sanity_check;
 
+mod csv;
 mod read;
 mod sanity;
 
-#[derive(Debug)]
+/// Samples of one or more files with the same header. Every entry is kept at full resolution,
+/// since any of them can be plotted or used in an expression without reading the files again.
+#[derive(Clone, Debug)]
 pub struct LogStream {
     pub version: Version,
     pub start: Option<NaiveDateTime>,
@@ -23,6 +30,58 @@ impl LogStream {
         self.time.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.time.is_empty()
+    }
+
+    /// The samples in the order they were logged
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = Sample<'_>> + '_ {
+        (0..self.len()).map(|index| Sample {
+            stream: self,
+            index,
+        })
+    }
+
+    pub fn entry(&self, name: &str) -> Option<&DataEntry> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+
+    /// Names of the entries, in the order of the values of a [`Sample`]
+    pub fn entry_names(&self) -> impl ExactSizeIterator<Item = &str> + '_ {
+        self.entries.iter().map(|e| e.name.as_str())
+    }
+
+    /// Average number of samples per second, `None` if it can't be determined
+    pub fn sample_rate(&self) -> Option<f64> {
+        let (first, last) = (self.time.first()?, self.time.last()?);
+        let duration = last.checked_sub(*first).filter(|&d| d > 0)?;
+        Some((self.time.len() - 1) as f64 * 1000.0 / duration as f64)
+    }
+
+    /// Number of timestamps smaller than the previous one
+    pub fn backwards(&self) -> usize {
+        self.time.windows(2).filter(|w| w[1] < w[0]).count()
+    }
+
+    /// Time ranges in ms the samples fold back over, from a timestamp that goes backwards to the
+    /// one before it
+    pub fn backwards_ranges(&self) -> Vec<(u32, u32)> {
+        (self.time.windows(2))
+            .filter(|w| w[1] < w[0])
+            .map(|w| (w[1], w[0]))
+            .collect()
+    }
+
+    /// Stable sort of the samples by their timestamp
+    pub fn sort_by_time(&mut self) {
+        let mut order: Vec<usize> = (0..self.time.len()).collect();
+        order.sort_by_key(|&i| self.time[i]);
+        self.time = order.iter().map(|&i| self.time[i]).collect();
+        for e in self.entries.iter_mut() {
+            e.kind.permute(&order);
+        }
+    }
+
     pub fn header_matches(&self, other: &Self) -> bool {
         if self.entries.len() != other.entries.len() {
             return false;
@@ -44,6 +103,13 @@ impl LogStream {
         }
     }
 
+    pub fn truncate(&mut self, len: usize) {
+        self.time.truncate(len);
+        for e in self.entries.iter_mut() {
+            e.kind.truncate(len);
+        }
+    }
+
     pub fn extend(&mut self, other: &Self) {
         self.time.extend_from_slice(&other.time);
         for (e, o) in self.entries.iter_mut().zip(other.entries.iter()) {
@@ -52,6 +118,35 @@ impl LogStream {
     }
 }
 
+/// The values of all entries at one timestamp of a [`LogStream`]
+#[derive(Clone, Copy)]
+pub struct Sample<'a> {
+    stream: &'a LogStream,
+    index: usize,
+}
+
+impl<'a> Sample<'a> {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Time in ms since the logger started
+    pub fn time(&self) -> u32 {
+        self.stream.time[self.index]
+    }
+
+    /// Value of the entry at `entry`, bools are 0 or 1
+    pub fn value(&self, entry: usize) -> f64 {
+        self.stream.entries[entry].kind.get_f64(self.index)
+    }
+
+    /// Name and value of each entry
+    pub fn values(&self) -> impl ExactSizeIterator<Item = (&'a str, f64)> + 'a {
+        let index = self.index;
+        (self.stream.entries.iter()).map(move |e| (e.name.as_str(), e.kind.get_f64(index)))
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Version {
     V1,
@@ -67,7 +162,7 @@ impl fmt::Display for Version {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct DataEntry {
     pub name: String,
     pub kind: EntryKind,
@@ -91,7 +186,34 @@ pub enum EntryKind {
     F64(Vec<f64>),
 }
 
+impl DataEntry {
+    /// All values as floats, bools are 0 or 1
+    pub fn values(&self) -> impl ExactSizeIterator<Item = f64> + '_ {
+        (0..self.kind.len()).map(|i| self.kind.get_f64(i))
+    }
+}
+
 impl EntryKind {
+    pub fn len(&self) -> usize {
+        match self {
+            EntryKind::Bool(v) => v.len(),
+            EntryKind::U8(v) => v.len(),
+            EntryKind::U16(v) => v.len(),
+            EntryKind::U32(v) => v.len(),
+            EntryKind::U64(v) => v.len(),
+            EntryKind::I8(v) => v.len(),
+            EntryKind::I16(v) => v.len(),
+            EntryKind::I32(v) => v.len(),
+            EntryKind::I64(v) => v.len(),
+            EntryKind::F32(v) => v.len(),
+            EntryKind::F64(v) => v.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     pub fn reserve(&mut self, additional: usize) {
         match self {
             EntryKind::Bool(v) => v.reserve(additional),
@@ -108,6 +230,42 @@ impl EntryKind {
         }
     }
 
+    pub fn truncate(&mut self, len: usize) {
+        match self {
+            EntryKind::Bool(v) => v.truncate(len),
+            EntryKind::U8(v) => v.truncate(len),
+            EntryKind::U16(v) => v.truncate(len),
+            EntryKind::U32(v) => v.truncate(len),
+            EntryKind::U64(v) => v.truncate(len),
+            EntryKind::I8(v) => v.truncate(len),
+            EntryKind::I16(v) => v.truncate(len),
+            EntryKind::I32(v) => v.truncate(len),
+            EntryKind::I64(v) => v.truncate(len),
+            EntryKind::F32(v) => v.truncate(len),
+            EntryKind::F64(v) => v.truncate(len),
+        }
+    }
+
+    /// Reorders the values, so that the value at `i` is the one previously at `order[i]`
+    pub fn permute(&mut self, order: &[usize]) {
+        fn permute<T: Copy>(v: &mut Vec<T>, order: &[usize]) {
+            *v = order.iter().map(|&i| v[i]).collect();
+        }
+        match self {
+            EntryKind::Bool(v) => permute(v, order),
+            EntryKind::U8(v) => permute(v, order),
+            EntryKind::U16(v) => permute(v, order),
+            EntryKind::U32(v) => permute(v, order),
+            EntryKind::U64(v) => permute(v, order),
+            EntryKind::I8(v) => permute(v, order),
+            EntryKind::I16(v) => permute(v, order),
+            EntryKind::I32(v) => permute(v, order),
+            EntryKind::I64(v) => permute(v, order),
+            EntryKind::F32(v) => permute(v, order),
+            EntryKind::F64(v) => permute(v, order),
+        }
+    }
+
     pub fn matches(&self, other: &Self) -> bool {
         matches!(
             (self, other),
@@ -167,6 +325,10 @@ pub enum Error {
     UnknownVersion(u16),
     UnknownDatatype(u8),
     InvalidTimestamp(i64),
+    InvalidCsv(String),
+    /// The same log is stored in multiple forms, e.g. `0.s3lg` and `0.s3lg.gz`
+    DuplicateFiles(Vec<PathBuf>),
+    NoFiles(PathBuf),
 }
 
 impl std::error::Error for Error {}
@@ -183,6 +345,15 @@ impl fmt::Display for Error {
             Self::UnknownVersion(version) => write!(f, "Unknown version: {version}"),
             Self::UnknownDatatype(code) => write!(f, "Unknown datatype code: {code}"),
             Self::InvalidTimestamp(timestamp) => write!(f, "Invalid unix timestamp: {timestamp}"),
+            Self::InvalidCsv(msg) => write!(f, "Invalid csv file: {msg}"),
+            Self::NoFiles(dir) => write!(f, "No log files found in '{}'", dir.display()),
+            Self::DuplicateFiles(paths) => {
+                write!(f, "Multiple files of the same log:")?;
+                for p in paths {
+                    write!(f, "\n{}", p.display())?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -201,3 +372,46 @@ impl From<FromUtf8Error> for Error {
 
 #[derive(Debug)]
 pub struct SanityError(pub String);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sort_by_time_keeps_entries_aligned() {
+        let mut stream = LogStream {
+            version: Version::V1,
+            start: None,
+            time: vec![30, 10, 20, 10],
+            entries: vec![
+                DataEntry {
+                    name: "a".into(),
+                    kind: EntryKind::U16(vec![3, 1, 2, 11]),
+                },
+                DataEntry {
+                    name: "b".into(),
+                    kind: EntryKind::Bool(vec![true, false, true, true]),
+                },
+                DataEntry {
+                    name: "c".into(),
+                    kind: EntryKind::F32(vec![0.3, 0.1, 0.2, 1.1]),
+                },
+            ],
+        };
+        assert_eq!(stream.backwards(), 2);
+        assert_eq!(stream.backwards_ranges(), [(10, 30), (10, 20)]);
+
+        stream.sort_by_time();
+        assert_eq!(stream.backwards(), 0);
+        assert_eq!(stream.time, [10, 10, 20, 30]);
+        // equal timestamps keep their order
+        match &stream.entries[..] {
+            [a, b, c] => {
+                assert!(matches!(&a.kind, EntryKind::U16(v) if v == &[1, 11, 2, 3]));
+                assert!(matches!(&b.kind, EntryKind::Bool(v) if v == &[false, true, true, true]));
+                assert!(matches!(&c.kind, EntryKind::F32(v) if v == &[0.1, 1.1, 0.2, 0.3]));
+            }
+            _ => panic!("entries were lost"),
+        }
+    }
+}
@@ -1,4 +1,7 @@
+use std::fmt::Write as _;
 use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 
 use chrono::DateTime;
 
@@ -49,20 +52,134 @@ struct BoolContext {
     mask: u8,
 }
 
-pub fn read_file(reader: &mut (impl Read + Seek)) -> Result<LogStream, Error> {
-    let stream_len = reader.len()?;
-
+/// Reads the magic number and version at the start of a file
+pub fn read_version(reader: &mut impl Read) -> Result<Version, Error> {
     let mut magic = [0; 4];
     reader.read_exact(&mut magic)?;
     if &magic != b"s3lg" {
         return Err(Error::InvalidMagic(magic));
     }
 
-    let version = match read_u16(reader)? {
-        1 => Version::V1,
-        2 => Version::V2,
-        v => return Err(Error::UnknownVersion(v)),
-    };
+    match read_u16(reader)? {
+        1 => Ok(Version::V1),
+        2 => Ok(Version::V2),
+        v => Err(Error::UnknownVersion(v)),
+    }
+}
+
+pub fn read_file(reader: &mut (impl Read + Seek)) -> Result<(LogStream, Vec<String>), Error> {
+    let stream_len = reader.len()?;
+    read_stream(reader, Some(stream_len))
+}
+
+/// Reads a file without seeking, e.g. while it is decompressed, so it doesn't have to be
+/// buffered in memory. If the length of the stream is known, the arrays are preallocated and
+/// only the samples that fit are read, otherwise samples are read until the end of the stream
+/// and a trailing incomplete one is ignored.
+///
+/// A length that doesn't fit whole samples is reported as warning, e.g. a file that is still
+/// written, which names the other version if the length would fit its header.
+pub fn read_stream(
+    reader: &mut impl Read,
+    stream_len: Option<u64>,
+) -> Result<(LogStream, Vec<String>), Error> {
+    let (mut log_file, pos) = read_header(reader)?;
+    let version = log_file.version;
+
+    let mut warnings = Vec::new();
+    let sample_size = sample_size(&log_file.entries);
+    if let Some(len) = stream_len {
+        let remainder = len.saturating_sub(pos) % sample_size;
+        if remainder != 0 {
+            let other = match version {
+                Version::V1 => Version::V2,
+                Version::V2 => Version::V1,
+            };
+            let other_pos = pos - version_header_len(version) + version_header_len(other);
+            let mut warning = format!(
+                "Ignored {remainder} trailing bytes, which are less than a sample of {sample_size} bytes"
+            );
+            if len.saturating_sub(other_pos) % sample_size == 0 {
+                let _ = write!(
+                    warning,
+                    ", the file might be a {other} file with a wrong version in its header"
+                );
+            }
+            warnings.push(warning);
+        }
+    }
+    let num_data_entries = stream_len.map(|len| len.saturating_sub(pos) / sample_size);
+    if let Some(n) = num_data_entries {
+        // preallocate data arrays
+        log_file.reserve(n as usize);
+    }
+
+    while num_data_entries.map_or(true, |n| (log_file.len() as u64) < n) {
+        let len = log_file.len();
+        match read_sample(reader, &mut log_file) {
+            Ok(()) => (),
+            Err(Error::IO(e))
+                if stream_len.is_none() && e.kind() == io::ErrorKind::UnexpectedEof =>
+            {
+                log_file.truncate(len);
+                break;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok((log_file, warnings))
+}
+
+/// Reads the samples of a file after the first `skip` ones, e.g. the ones written since it was
+/// read before. A trailing incomplete sample is left for the next read.
+pub fn read_file_from(reader: &mut (impl Read + Seek), skip: usize) -> Result<LogStream, Error> {
+    let stream_len = reader.len()?;
+    let (mut log_file, pos) = read_header(reader)?;
+    let sample_size = sample_size(&log_file.entries);
+    let start = pos + skip as u64 * sample_size;
+    reader.seek(SeekFrom::Start(start))?;
+
+    let num_samples = stream_len.saturating_sub(start) / sample_size;
+    log_file.reserve(num_samples as usize);
+    for _ in 0..num_samples {
+        read_sample(reader, &mut log_file)?;
+    }
+    Ok(log_file)
+}
+
+/// Reads a stream while it is written, e.g. from a pipe. Each sample is added to `pending` as soon
+/// as it is complete, which starts as a stream of its own whenever it was taken by another thread.
+/// Reading stops at the end of the stream, without a trailing incomplete sample, or once `stop` is
+/// set.
+pub fn read_stream_live(
+    reader: &mut impl Read,
+    pending: &Mutex<Option<LogStream>>,
+    stop: &AtomicBool,
+) -> Result<(), Error> {
+    let (mut log_file, _) = read_header(reader)?;
+
+    while !stop.load(Ordering::Relaxed) {
+        match read_sample(reader, &mut log_file) {
+            Ok(()) => (),
+            Err(Error::IO(e)) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        }
+
+        let mut pending = pending.lock().unwrap();
+        match pending.as_mut() {
+            Some(p) => p.extend(&log_file),
+            None => *pending = Some(log_file.clone()),
+        }
+        log_file.truncate(0);
+    }
+    Ok(())
+}
+
+/// Reads everything before the samples, returns a stream without samples and the length of the
+/// header in bytes
+fn read_header(reader: &mut impl Read) -> Result<(LogStream, u64), Error> {
+    let version = read_version(reader)?;
 
     let num_entries = read_u16(reader)?;
 
@@ -84,7 +201,7 @@ pub fn read_file(reader: &mut (impl Read + Seek)) -> Result<LogStream, Error> {
         entries: Vec::with_capacity(num_entries as usize),
     };
 
-    let mut pos: u64 = 8;
+    let mut pos: u64 = 8 + version_header_len(version);
     for _ in 0..num_entries {
         let code = read_u8(reader)?;
         let kind = EntryKind::try_from(code)?;
@@ -97,67 +214,90 @@ pub fn read_file(reader: &mut (impl Read + Seek)) -> Result<LogStream, Error> {
         pos += 2 + name_len as u64;
     }
 
-    // preallocate data arrays
-    let mut data_entry_size = 4;
-    for e in log_file.entries.iter() {
-        data_entry_size += e.kind.size() as u64;
+    Ok((log_file, pos))
+}
+
+/// Length of the header fields that only exist in some versions
+fn version_header_len(version: Version) -> u64 {
+    match version {
+        Version::V1 => 0,
+        // start of the run as unix timestamp
+        Version::V2 => 8,
     }
-    let num_data_entries = (stream_len - pos) / data_entry_size;
-    log_file.time.reserve(num_data_entries as usize);
+}
+
+/// Bytes per sample, including the timestamp. Up to 8 consecutive bools share a byte.
+fn sample_size(entries: &[DataEntry]) -> u64 {
+    let mut size = 4;
+    let mut bools = 0;
+    for e in entries {
+        match e.kind {
+            EntryKind::Bool(_) => {
+                if bools % 8 == 0 {
+                    size += 1;
+                }
+                bools += 1;
+            }
+            _ => {
+                bools = 0;
+                size += e.kind.size() as u64;
+            }
+        }
+    }
+    size
+}
+
+/// Consecutive bools are packed into shared bytes, which start over with every sample, see
+/// [`sample_size`]
+fn read_sample(reader: &mut impl Read, log_file: &mut LogStream) -> Result<(), Error> {
+    log_file.time.push(read_u32(reader)?);
+
+    let mut bool_ctx: Option<BoolContext> = None;
     for e in log_file.entries.iter_mut() {
-        e.kind.reserve(num_data_entries as usize);
-    }
-
-    let mut bool_ctx = None;
-    for _ in 0..num_data_entries {
-        log_file.time.push(read_u32(reader)?);
-
-        for e in log_file.entries.iter_mut() {
-            let mut is_bool_entry = false;
-
-            match &mut e.kind {
-                EntryKind::Bool(v) => {
-                    let ctx = match &mut bool_ctx {
-                        Some(ctx) => ctx,
-                        None => {
-                            let bit_fields = read_u8(reader)?;
-                            bool_ctx.insert(BoolContext {
-                                bit_fields,
-                                mask: 1,
-                            })
-                        }
-                    };
-
-                    let masked = ctx.bit_fields & ctx.mask;
-                    v.push(masked != 0);
-
-                    if ctx.mask >= 0x80 {
-                        bool_ctx = None;
-                    } else {
-                        ctx.mask <<= 1;
+        let mut is_bool_entry = false;
+
+        match &mut e.kind {
+            EntryKind::Bool(v) => {
+                let ctx = match &mut bool_ctx {
+                    Some(ctx) => ctx,
+                    None => {
+                        let bit_fields = read_u8(reader)?;
+                        bool_ctx.insert(BoolContext {
+                            bit_fields,
+                            mask: 1,
+                        })
                     }
+                };
 
-                    is_bool_entry = true;
+                let masked = ctx.bit_fields & ctx.mask;
+                v.push(masked != 0);
+
+                if ctx.mask >= 0x80 {
+                    bool_ctx = None;
+                } else {
+                    ctx.mask <<= 1;
                 }
-                EntryKind::U8(v) => v.push(read_u8(reader)?),
-                EntryKind::U16(v) => v.push(read_u16(reader)?),
-                EntryKind::U32(v) => v.push(read_u32(reader)?),
-                EntryKind::U64(v) => v.push(read_u64(reader)?),
-                EntryKind::I8(v) => v.push(read_i8(reader)?),
-                EntryKind::I16(v) => v.push(read_i16(reader)?),
-                EntryKind::I32(v) => v.push(read_i32(reader)?),
-                EntryKind::I64(v) => v.push(read_i64(reader)?),
-                EntryKind::F32(v) => v.push(read_f32(reader)?),
-                EntryKind::F64(v) => v.push(read_f64(reader)?),
-            }
 
-            if !is_bool_entry {
-                bool_ctx = None;
+                is_bool_entry = true;
             }
+            EntryKind::U8(v) => v.push(read_u8(reader)?),
+            EntryKind::U16(v) => v.push(read_u16(reader)?),
+            EntryKind::U32(v) => v.push(read_u32(reader)?),
+            EntryKind::U64(v) => v.push(read_u64(reader)?),
+            EntryKind::I8(v) => v.push(read_i8(reader)?),
+            EntryKind::I16(v) => v.push(read_i16(reader)?),
+            EntryKind::I32(v) => v.push(read_i32(reader)?),
+            EntryKind::I64(v) => v.push(read_i64(reader)?),
+            EntryKind::F32(v) => v.push(read_f32(reader)?),
+            EntryKind::F64(v) => v.push(read_f64(reader)?),
+        }
+
+        if !is_bool_entry {
+            bool_ctx = None;
         }
     }
 
-    Ok(log_file)
+    Ok(())
 }
 
 impl<T: Seek> SeekUtils for T {}
@@ -195,3 +335,166 @@ fn read_string(reader: &mut impl Read, len: usize) -> Result<String, Error> {
     reader.read_exact(&mut buf)?;
     Ok(String::from_utf8(buf)?)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// Entries `b0: bool`, `a: u16`, `b1: bool`, `c: u8`, the bools don't share a byte
+    const ENTRIES: [(u8, &str); 4] = [(0, "b0"), (2, "a"), (0, "b1"), (1, "c")];
+    const SAMPLE_SIZE: usize = 9;
+
+    fn header(version: Version, start: i64) -> Vec<u8> {
+        let mut buf = b"s3lg".to_vec();
+        let version: u16 = match version {
+            Version::V1 => 1,
+            Version::V2 => 2,
+        };
+        buf.extend(version.to_be_bytes());
+        buf.extend((ENTRIES.len() as u16).to_be_bytes());
+        if version == 2 {
+            buf.extend(start.to_be_bytes());
+        }
+        for (code, name) in ENTRIES {
+            buf.push(code);
+            buf.push(name.len() as u8);
+            buf.extend(name.as_bytes());
+        }
+        buf
+    }
+
+    fn push_sample(buf: &mut Vec<u8>, time: u32, b0: bool, a: u16, b1: bool, c: u8) {
+        buf.extend(time.to_be_bytes());
+        buf.push(b0 as u8);
+        buf.extend(a.to_be_bytes());
+        buf.push(b1 as u8);
+        buf.push(c);
+    }
+
+    fn two_samples(version: Version) -> Vec<u8> {
+        let mut buf = header(version, 0);
+        push_sample(&mut buf, 10, true, 300, false, 7);
+        push_sample(&mut buf, 20, false, 400, true, 8);
+        buf
+    }
+
+    #[test]
+    fn sample_size_packs_bools_per_sample() {
+        let (stream, _) = read_file(&mut Cursor::new(two_samples(Version::V1))).unwrap();
+        assert_eq!(sample_size(&stream.entries), SAMPLE_SIZE as u64);
+        match (&stream.entries[0].kind, &stream.entries[2].kind) {
+            (EntryKind::Bool(b0), EntryKind::Bool(b1)) => {
+                assert_eq!(b0, &[true, false]);
+                assert_eq!(b1, &[false, true]);
+            }
+            _ => panic!("expected bool entries"),
+        }
+    }
+
+    #[test]
+    fn trailing_bytes_are_ignored_with_warning() {
+        let mut buf = two_samples(Version::V1);
+        buf.extend([0xff; 3]);
+
+        let (stream, warnings) = read_file(&mut Cursor::new(buf)).unwrap();
+        assert_eq!(stream.time, [10, 20]);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("Ignored 3 trailing bytes"));
+        assert!(!warnings[0].contains("v2"));
+    }
+
+    #[test]
+    fn length_fitting_other_version_is_named() {
+        // the 8 bytes of a v2 start timestamp, but a v1 header
+        let mut buf = two_samples(Version::V1);
+        buf.extend([0; 8]);
+        assert_ne!((buf.len() - header(Version::V1, 0).len()) % SAMPLE_SIZE, 0);
+
+        let (stream, warnings) = read_file(&mut Cursor::new(buf)).unwrap();
+        assert_eq!(stream.len(), 2);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("Ignored 8 trailing bytes"));
+        assert!(warnings[0].contains("might be a v2 file"));
+    }
+
+    fn assert_samples(stream: &LogStream) {
+        assert_eq!(stream.time, [10, 20]);
+        let names: Vec<_> = stream.entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["b0", "a", "b1", "c"]);
+        let kinds = stream.entries.iter().map(|e| &e.kind).collect::<Vec<_>>();
+        match kinds[..] {
+            [EntryKind::Bool(b0), EntryKind::U16(a), EntryKind::Bool(b1), EntryKind::U8(c)] => {
+                assert_eq!(b0, &[true, false]);
+                assert_eq!(a, &[300, 400]);
+                assert_eq!(b1, &[false, true]);
+                assert_eq!(c, &[7, 8]);
+            }
+            _ => panic!("unexpected entry kinds"),
+        }
+    }
+
+    #[test]
+    fn round_trip_v1() {
+        let buf = two_samples(Version::V1);
+        assert_eq!(read_version(&mut Cursor::new(&buf)).unwrap(), Version::V1);
+
+        let (stream, warnings) = read_file(&mut Cursor::new(buf)).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(stream.version, Version::V1);
+        assert_eq!(stream.start, None);
+        assert_samples(&stream);
+    }
+
+    #[test]
+    fn round_trip_v2() {
+        let start = 1_700_000_000;
+        let mut buf = header(Version::V2, start);
+        push_sample(&mut buf, 10, true, 300, false, 7);
+        push_sample(&mut buf, 20, false, 400, true, 8);
+        assert_eq!(buf.len(), two_samples(Version::V1).len() + 8);
+        assert_eq!(read_version(&mut Cursor::new(&buf)).unwrap(), Version::V2);
+
+        let (stream, warnings) = read_file(&mut Cursor::new(buf)).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(stream.version, Version::V2);
+        let expected = DateTime::from_timestamp(start, 0).unwrap().naive_utc();
+        assert_eq!(stream.start, Some(expected));
+        assert_samples(&stream);
+    }
+
+    #[test]
+    fn read_from_skips_samples() {
+        let mut buf = two_samples(Version::V2);
+        buf.extend([0; 4]);
+
+        let stream = read_file_from(&mut Cursor::new(&buf), 1).unwrap();
+        assert_eq!(stream.time, [20]);
+        match &stream.entries[1].kind {
+            EntryKind::U16(a) => assert_eq!(a, &[400]),
+            _ => panic!("expected a u16 entry"),
+        }
+        assert!(read_file_from(&mut Cursor::new(&buf), 2)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn live_samples_are_pending_without_incomplete_ones() {
+        let mut buf = two_samples(Version::V2);
+        buf.extend([0; 4]);
+
+        let pending = Mutex::new(None);
+        read_stream_live(&mut Cursor::new(buf), &pending, &AtomicBool::new(false)).unwrap();
+        let stream = pending.into_inner().unwrap().unwrap();
+        assert_samples(&stream);
+    }
+
+    #[test]
+    fn whole_samples_have_no_warning() {
+        let (stream, warnings) = read_file(&mut Cursor::new(two_samples(Version::V2))).unwrap();
+        assert_eq!(stream.len(), 2);
+        assert!(warnings.is_empty());
+    }
+}
@@ -1,12 +1,19 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Range;
 use std::sync::Arc;
 
-use cods::{Asts, Checker, Context, Funs, Ident, IdentSpan, Span, Stack, Val, VarRef};
+use cods::{
+    Asts, BuiltinConst, BuiltinFun, Checker, Context, DataType, Funs, Ident, IdentSpan, Pos, Span,
+    Stack, Val, VarRef,
+};
 use egui_plot::PlotPoint;
 use serde::{Deserialize, Serialize};
 
+use crate::analysis;
 use crate::data::LogStream;
 
-#[derive(Default, Clone, Serialize, Deserialize)]
+#[derive(Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Expr {
     pub x: String,
     pub y: String,
@@ -21,25 +28,284 @@ impl Expr {
     }
 }
 
+/// User defined constant that can be referenced by all expressions
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Constant {
+    pub name: String,
+    pub value: f64,
+}
+
+impl Constant {
+    pub fn new(name: impl Into<String>, value: f64) -> Self {
+        Self {
+            name: name.into(),
+            value,
+        }
+    }
+}
+
+/// Settings shared by all expressions
+#[derive(Clone, Default, PartialEq)]
+pub struct Globals {
+    pub constants: Vec<Constant>,
+    /// Time offset in ms added to the timestamps of each stream
+    pub time_offsets: Vec<i32>,
+}
+
 #[derive(Default)]
 pub struct ExprError {
-    pub x: Option<cods::Error>,
-    pub y: Option<cods::Error>,
+    pub x: Option<Error>,
+    pub y: Option<Error>,
+}
+
+pub enum Error {
+    Cods(cods::Error),
+    /// Invalid call of a series function
+    Series {
+        msg: String,
+        start: Pos,
+        end: Pos,
+    },
+    /// Identifier that is neither an entry, a constant nor a builtin
+    UndefinedVar {
+        name: String,
+        start: Pos,
+        end: Pos,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cods(e) => write!(f, "{e}"),
+            Self::Series { msg, .. } => write!(f, "{msg}"),
+            Self::UndefinedVar { name, .. } => write!(f, "undefined variable: {name}"),
+        }
+    }
+}
+
+impl Error {
+    pub fn spans(&self) -> Vec<(Pos, Pos)> {
+        match self {
+            Self::Cods(e) => e.spans().iter().map(|s| (s.start, s.end)).collect(),
+            Self::Series { start, end, .. } | Self::UndefinedVar { start, end, .. } => {
+                vec![(*start, *end)]
+            }
+        }
+    }
+}
+
+/// Functions that operate on a whole series instead of single samples. Calls are evaluated before
+/// the rest of the expression and replaced by a variable holding the result. Aggregations reduce
+/// the series to a constant that is repeated at every sample.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SeriesFun {
+    Derivative,
+    Integral,
+    /// Selects the second argument where the first one is greater than zero, otherwise the third
+    If,
+    Mean,
+    Median,
+    StdDev,
+    Percentile,
+    RollingMax,
+    RollingMin,
+    /// Limits the first argument to the range of the second and third one
+    Clamp,
+}
+
+impl fmt::Display for SeriesFun {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Derivative => write!(f, "derivative"),
+            Self::Integral => write!(f, "integral"),
+            Self::If => write!(f, "if"),
+            Self::Mean => write!(f, "mean"),
+            Self::Median => write!(f, "median"),
+            Self::StdDev => write!(f, "stddev"),
+            Self::Percentile => write!(f, "percentile"),
+            Self::RollingMax => write!(f, "rolling_max"),
+            Self::RollingMin => write!(f, "rolling_min"),
+            Self::Clamp => write!(f, "clamp"),
+        }
+    }
+}
+
+impl SeriesFun {
+    pub const ALL: [Self; 10] = [
+        Self::Derivative,
+        Self::Integral,
+        Self::If,
+        Self::Mean,
+        Self::Median,
+        Self::StdDev,
+        Self::Percentile,
+        Self::RollingMax,
+        Self::RollingMin,
+        Self::Clamp,
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.to_string() == name)
+    }
+
+    /// Number of leading arguments that are series
+    pub fn num_series(&self) -> usize {
+        match self {
+            Self::Derivative
+            | Self::Integral
+            | Self::Mean
+            | Self::Median
+            | Self::StdDev
+            | Self::Percentile
+            | Self::RollingMax
+            | Self::RollingMin => 1,
+            Self::If | Self::Clamp => 3,
+        }
+    }
+
+    /// Constant number parameters following the series
+    pub fn params(&self) -> &'static [&'static str] {
+        match self {
+            Self::Derivative
+            | Self::Integral
+            | Self::If
+            | Self::Mean
+            | Self::Median
+            | Self::StdDev
+            | Self::Clamp => &[],
+            Self::Percentile => &["p"],
+            Self::RollingMax | Self::RollingMin => &["n"],
+        }
+    }
+
+    fn apply(&self, time: &[f64], series: &[Vec<f64>], params: &[f64]) -> Vec<f64> {
+        let points = || -> Vec<PlotPoint> {
+            (time.iter().zip(series[0].iter()))
+                .map(|(&t, &v)| PlotPoint::new(t, v))
+                .collect()
+        };
+        let ys = |points: Vec<PlotPoint>| -> Vec<f64> { points.iter().map(|p| p.y).collect() };
+        let constant = |v: Option<f64>| vec![v.unwrap_or(f64::NAN); time.len()];
+        match self {
+            Self::Derivative => ys(analysis::derivative(&points())),
+            Self::Integral => ys(analysis::cumulative_integral(&points())),
+            Self::If => (series[0].iter().zip(series[1].iter()).zip(series[2].iter()))
+                .map(|((&c, &a), &b)| {
+                    if c.is_nan() {
+                        f64::NAN
+                    } else if c > 0.0 {
+                        a
+                    } else {
+                        b
+                    }
+                })
+                .collect(),
+            Self::Mean => constant(analysis::stats(&points()).map(|s| s.mean)),
+            Self::Median => constant(analysis::percentile(&series[0], 50.0)),
+            Self::StdDev => constant(analysis::stats(&points()).map(|s| s.std_dev)),
+            Self::Percentile => constant(analysis::percentile(&series[0], params[0])),
+            Self::RollingMax => analysis::rolling_max(&series[0], params[0].max(1.0) as usize),
+            Self::RollingMin => analysis::rolling_min(&series[0], params[0].max(1.0) as usize),
+            Self::Clamp => (series[0].iter().zip(series[1].iter()).zip(series[2].iter()))
+                .map(|((&v, &lo), &hi)| {
+                    if v.is_nan() || lo.is_nan() || hi.is_nan() {
+                        f64::NAN
+                    } else {
+                        // an empty range results in `hi`, like `min(max(v, lo), hi)`
+                        v.max(lo).min(hi)
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Var {
+    Entry(usize, usize),
+    Time,
+    Const(usize),
+    Series(usize),
+}
+
+struct Env<'a> {
+    data: &'a [LogStream],
+    /// Time in seconds of the first stream, including its offset
+    time: Vec<f64>,
+    lerp: Vec<Vec<Lerp>>,
+    /// Constants whose name isn't already taken by a variable
+    constants: Vec<&'a Constant>,
+}
+
+impl<'a> Env<'a> {
+    fn new(data: &'a [LogStream], globals: &'a Globals) -> Self {
+        let offsets: Vec<i64> = (0..data.len())
+            .map(|i| globals.time_offsets.get(i).copied().unwrap_or(0) as i64)
+            .collect();
+        let time = (data[0].time.iter())
+            .map(|&t| (t as i64 + offsets[0]) as f64 / 1000.0)
+            .collect();
+
+        Self {
+            data,
+            time,
+            lerp: lerp_table(data, &offsets),
+            constants: valid_constants(data, &globals.constants),
+        }
+    }
+}
+
+/// Names of all variables that can be referenced by expressions
+pub fn variable_names(data: &[LogStream], constants: &[Constant]) -> Vec<String> {
+    let entries = data.iter().flat_map(|g| g.entries.iter());
+    let mut names: Vec<String> = entries.map(|e| e.name.clone()).collect();
+    names.push("time".into());
+    names.extend(
+        valid_constants(data, constants)
+            .iter()
+            .map(|c| c.name.clone()),
+    );
+    names
+}
+
+fn valid_constants<'a>(data: &[LogStream], constants: &'a [Constant]) -> Vec<&'a Constant> {
+    let mut names: HashSet<&str> = (data.iter().flat_map(|g| g.entries.iter()))
+        .map(|e| e.name.as_str())
+        .collect();
+    names.insert("time");
+    (constants.iter())
+        .filter(|c| !c.name.is_empty() && names.insert(&c.name))
+        .collect()
 }
 
-pub fn eval(expr: &Expr, data: Arc<[LogStream]>) -> Result<Vec<PlotPoint>, Box<ExprError>> {
-    let mut ctx_x = Context::default();
-    let mut ctx_y = Context::default();
+/// How a value of another stream is sampled at a timestamp of the first one
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Lerp {
+    Exact(usize),
+    Between(usize, f64),
+    Missing,
+}
 
-    // number of all entries plus the always present time entry
-    let num_vars = data.iter().map(|g| g.entries.len()).sum::<usize>() + 1;
-    let mut vars_x = Vec::with_capacity(num_vars);
-    let mut vars_y = Vec::with_capacity(num_vars);
+struct Call {
+    fun: SeriesFun,
+    range: Range<usize>,
+    args: Vec<Range<usize>>,
+}
 
-    let asts_x = parse(&data, &mut ctx_x, &mut vars_x, &expr.x);
-    let asts_y = parse(&data, &mut ctx_y, &mut vars_y, &expr.y);
+/// Evaluates the expression at every timestamp of the first stream, so operands always have the
+/// same length. Entries of other streams are linearly interpolated between their two closest
+/// samples, before their first sample and after their last one the nearest sample is used.
+pub fn eval(
+    expr: &Expr,
+    data: Arc<[LogStream]>,
+    globals: &Globals,
+) -> Result<Vec<PlotPoint>, Box<ExprError>> {
+    let env = Env::new(&data, globals);
+    let x = eval_series(&env, &expr.x);
+    let y = eval_series(&env, &expr.y);
 
-    let ((funs_x, asts_x), (funs_y, asts_y)) = match (asts_x, asts_y) {
+    let (x, y) = match (x, y) {
         (Ok(x), Ok(y)) => (x, y),
         (x, y) => {
             return Err(Box::new(ExprError {
@@ -49,97 +315,91 @@ pub fn eval(expr: &Expr, data: Arc<[LogStream]>) -> Result<Vec<PlotPoint>, Box<E
         }
     };
 
-    let mut values = Vec::with_capacity(data.len());
-    let mut stack_x = Stack::default();
-    let mut stack_y = Stack::default();
-    stack_x.resize(vars_x.len());
-    stack_y.resize(vars_y.len());
-
-    let mut lerp_values = Vec::with_capacity(data.len() - 1);
-    for d in data.iter().skip(1) {
-        lerp_values.push((0, &d.time[0..1]));
-    }
-    for (i, &time) in data[0].time.iter().enumerate() {
-        for (j, d) in data.iter().skip(1).enumerate() {
-            let mut d_index = 0;
-            while let Some(&t) = d.time.get(d_index) {
-                if t == time || t > time && d_index == 0 {
-                    lerp_values[j] = (d_index, &d.time[d_index..d_index + 1]);
-                } else if t > time {
-                    lerp_values[j] = (d_index - 1, &d.time[d_index - 1..d_index + 1]);
-                } else if d_index + 1 == d.len() {
-                    lerp_values[j] = (d_index, &d.time[d_index..d_index + 1]);
-                } else {
-                    d_index += 1;
-                    continue;
-                }
-                break;
-            }
-        }
+    let values = (x.into_iter().zip(y))
+        .filter_map(|(x, y)| Some(PlotPoint::new(x?, y?)))
+        .collect();
+    Ok(values)
+}
 
-        for (var, id) in vars_x.iter() {
-            let val = get_value(&data, *id, i, time, &lerp_values);
-            stack_x.set(var, val);
-        }
-        for (var, id) in vars_y.iter() {
-            let val = get_value(&data, *id, i, time, &lerp_values);
-            stack_y.set(var, val);
-        }
+/// Evaluates the input at every timestamp of the first stream
+fn eval_series(env: &Env, input: &str) -> Result<Vec<Option<f64>>, Error> {
+    let data = env.data;
+    let (input, series) = expand_series_funs(env, input)?;
 
-        let x = cods::eval_with(&mut stack_x, &funs_x, &asts_x);
-        let y = cods::eval_with(&mut stack_y, &funs_y, &asts_y);
+    let mut ctx = Context::default();
+    // number of all entries, the always present time entry, the constants and the series
+    // function results
+    let num_vars = data.iter().map(|g| g.entries.len()).sum::<usize>()
+        + 1
+        + env.constants.len()
+        + series.len();
+    let mut vars = Vec::with_capacity(num_vars);
+    let (funs, asts) = parse(env, &mut ctx, &mut vars, &input, series.len())
+        .map_err(|e| undefined_var(env, &input, series.len(), e))?;
 
-        if let (Ok(x), Ok(y)) = (x, y) {
-            if let (Some(x), Some(y)) = (cast_float(x), cast_float(y)) {
-                values.push(PlotPoint::new(x, y));
-            }
-        };
+    let mut values = Vec::with_capacity(data[0].len());
+    let mut stack = Stack::default();
+    stack.resize(vars.len());
+    for i in 0..data[0].len() {
+        for (var, v) in vars.iter() {
+            let val = get_value(env, &series, *v, i);
+            stack.set(var, val);
+        }
+
+        let val = cods::eval_with(&mut stack, &funs, &asts).ok();
+        values.push(val.and_then(cast_float));
     }
 
     Ok(values)
 }
 
 fn parse(
-    data: &[LogStream],
+    env: &Env,
     ctx: &mut Context,
-    vars: &mut Vec<(VarRef, (usize, usize))>,
+    vars: &mut Vec<(VarRef, Var)>,
     input: &str,
+    num_series: usize,
 ) -> cods::Result<(Funs, Asts)> {
+    let data = env.data;
     for v in data.iter().flat_map(|g| g.entries.iter()) {
         ctx.idents.push(&v.name);
     }
     ctx.idents.push("time");
+    for c in env.constants.iter() {
+        ctx.idents.push(&c.name);
+    }
+    for i in 0..num_series {
+        ctx.idents.push(&series_ident(i));
+    }
 
     let tokens = ctx.lex(input)?;
     let items = ctx.group(tokens)?;
     let csts = ctx.parse(items)?;
 
     let mut checker = Checker::default();
-    let mut id = 0;
+    let mut def_var = |ctx: &mut Context, vars: &mut Vec<(VarRef, Var)>, var: Var| {
+        let ident = IdentSpan::new(Ident(vars.len()), Span::pos(0, 0));
+        let inner = ctx.def_var(
+            &mut checker.scopes,
+            ident,
+            cods::DataType::Float,
+            true,
+            false,
+        );
+        vars.push((inner, var));
+    };
     for (i, group) in data.iter().enumerate() {
         for j in 0..group.entries.len() {
-            let ident = IdentSpan::new(Ident(id), Span::pos(0, 0));
-            let inner = ctx.def_var(
-                &mut checker.scopes,
-                ident,
-                cods::DataType::Float,
-                true,
-                false,
-            );
-            vars.push((inner, (i, j)));
-
-            id += 1;
-        }
-    }
-    let ident = IdentSpan::new(Ident(vars.len()), Span::pos(0, 0));
-    let inner = ctx.def_var(
-        &mut checker.scopes,
-        ident,
-        cods::DataType::Float,
-        true,
-        false,
-    );
-    vars.push((inner, (data.len(), 0)));
+            def_var(ctx, vars, Var::Entry(i, j));
+        }
+    }
+    def_var(ctx, vars, Var::Time);
+    for i in 0..env.constants.len() {
+        def_var(ctx, vars, Var::Const(i));
+    }
+    for i in 0..num_series {
+        def_var(ctx, vars, Var::Series(i));
+    }
 
     let asts = ctx.check_with(&mut checker, csts)?;
     if !ctx.errors.is_empty() {
@@ -149,37 +409,442 @@ fn parse(
     Ok((checker.funs, asts))
 }
 
+/// Names the identifier an error of cods points at if it isn't defined, otherwise keeps the error
+fn undefined_var(env: &Env, input: &str, num_series: usize, error: cods::Error) -> Error {
+    let spans: Vec<Range<usize>> = (error.spans().iter())
+        .map(|s| byte_at(input, s.start)..byte_at(input, s.end))
+        .collect();
+    let is_defined = |name: &str| {
+        let entries = env.data.iter().flat_map(|g| g.entries.iter());
+        matches!(name, "time" | "true" | "false")
+            || entries.map(|e| &e.name).any(|n| n == name)
+            || env.constants.iter().any(|c| c.name == name)
+            || (0..num_series).any(|i| series_ident(i) == name)
+            || BuiltinFun::members().iter().any(|f| f.to_string() == name)
+            || BuiltinConst::members()
+                .iter()
+                .any(|c| c.to_string() == name)
+            || DataType::members().iter().any(|d| d.to_string() == name)
+    };
+    let undefined = ident_ranges(input).into_iter().find(|r| {
+        // an empty span still points at the identifier it starts at
+        let overlaps = (spans.iter()).any(|s| s.start < r.end && r.start < s.end.max(s.start + 1));
+        overlaps && !is_defined(&input[r.clone()])
+    });
+    match undefined {
+        Some(range) => Error::UndefinedVar {
+            name: input[range.clone()].to_string(),
+            start: pos_at(input, range.start),
+            end: pos_at(input, range.end),
+        },
+        None => Error::Cods(error),
+    }
+}
+
+/// Byte ranges of the identifiers in `input`, outside of strings and number literals
+fn ident_ranges(input: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c == '"' {
+            for (_, c) in chars.by_ref() {
+                if c == '"' {
+                    break;
+                }
+            }
+            continue;
+        }
+        if !(c.is_alphanumeric() || c == '_') {
+            continue;
+        }
+
+        let mut end = start + c.len_utf8();
+        while let Some((i, c)) = chars.next_if(|(_, c)| c.is_alphanumeric() || *c == '_') {
+            end = i + c.len_utf8();
+        }
+        if !c.is_numeric() {
+            ranges.push(start..end);
+        }
+    }
+    ranges
+}
+
 fn cast_float(val: Val) -> Option<f64> {
     match val {
         Val::Int(i) => Some(i as f64),
         Val::Float(f) => Some(f),
+        // comparisons evaluate to 0 or 1
+        Val::Bool(b) => Some(b as u8 as f64),
         _ => None,
     }
 }
 
-fn get_value(
-    data: &[LogStream],
-    id: (usize, usize),
-    index: usize,
-    time: u32,
-    lerp_values: &[(usize, &[u32])],
-) -> Val {
-    if id.0 == 0 {
-        Val::Float(data[id.0].entries[id.1].kind.get_f64(index))
-    } else if id.0 < data.len() {
-        match lerp_values[id.0 - 1] {
-            (index, [_time]) => Val::Float(data[id.0].entries[id.1].kind.get_f64(index)),
-            (index, [time0, time1]) => {
-                let range = time1 - time0;
-                let pos = time - time0;
-                let factor = pos as f64 / range as f64;
-                let val0 = data[id.0].entries[id.1].kind.get_f64(index);
-                let val1 = data[id.0].entries[id.1].kind.get_f64(index + 1);
-                Val::Float(val0 + factor * (val1 - val0))
+fn get_value(env: &Env, series: &[Vec<f64>], var: Var, index: usize) -> Val {
+    match var {
+        Var::Entry(0, j) => Val::Float(env.data[0].entries[j].kind.get_f64(index)),
+        Var::Entry(i, j) => {
+            let kind = &env.data[i].entries[j].kind;
+            match env.lerp[i - 1][index] {
+                Lerp::Exact(k) => Val::Float(kind.get_f64(k)),
+                Lerp::Between(k, factor) => {
+                    let val0 = kind.get_f64(k);
+                    let val1 = kind.get_f64(k + 1);
+                    Val::Float(val0 + factor * (val1 - val0))
+                }
+                Lerp::Missing => Val::Float(f64::NAN),
+            }
+        }
+        Var::Time => Val::Float(env.time[index]),
+        Var::Const(c) => Val::Float(env.constants[c].value),
+        Var::Series(s) => Val::Float(series[s][index]),
+    }
+}
+
+/// For every stream except the first one, how its values are sampled at the timestamps of the
+/// first stream. The timestamps of each stream are shifted by their offset in ms.
+fn lerp_table(data: &[LogStream], offsets: &[i64]) -> Vec<Vec<Lerp>> {
+    let mut table = Vec::with_capacity(data.len().saturating_sub(1));
+    for (d, offset) in data.iter().zip(offsets).skip(1) {
+        let offset = offset - offsets[0];
+        let shifted = |i: usize| d.time.get(i).map(|&t| t as i64 + offset);
+
+        let mut lerp = Vec::with_capacity(data[0].len());
+        let mut d_index = 0;
+        let mut prev_time = 0;
+        for &time in data[0].time.iter() {
+            let time = time as i64;
+            if time < prev_time {
+                d_index = 0;
+            }
+            prev_time = time;
+
+            // first timestamp that isn't before `time`
+            while shifted(d_index).is_some_and(|t| t < time) {
+                d_index += 1;
+            }
+
+            let l = match shifted(d_index) {
+                None if d.time.is_empty() => Lerp::Missing,
+                None => Lerp::Exact(d.time.len() - 1),
+                Some(t) if t == time || d_index == 0 => Lerp::Exact(d_index),
+                Some(t) => {
+                    let t0 = shifted(d_index - 1).unwrap_or(t);
+                    let factor = (time - t0) as f64 / (t - t0) as f64;
+                    Lerp::Between(d_index - 1, factor)
+                }
+            };
+            lerp.push(l);
+        }
+        table.push(lerp);
+    }
+    table
+}
+
+/// Evaluates all calls of series functions and replaces them with variables. The replacements
+/// keep the line and column of everything else, so error spans still match the original input.
+fn expand_series_funs(env: &Env, input: &str) -> Result<(String, Vec<Vec<f64>>), Error> {
+    let calls = find_series_calls(input);
+    if calls.is_empty() {
+        return Ok((input.to_string(), Vec::new()));
+    }
+
+    let mut output = String::with_capacity(input.len());
+    let mut series = Vec::with_capacity(calls.len());
+    let mut last = 0;
+    for call in calls {
+        let num_series = call.fun.num_series();
+        let params = call.fun.params();
+        let num_args = num_series + params.len();
+        if call.args.len() != num_args {
+            return Err(series_error(
+                input,
+                call.range,
+                format!(
+                    "{} expects {num_args} arguments, found {}",
+                    call.fun,
+                    call.args.len()
+                ),
+            ));
+        }
+
+        let mut args = Vec::with_capacity(num_series);
+        for arg in call.args[..num_series].iter() {
+            let values = eval_series(env, &mask(input, arg.clone()))?;
+            args.push(values.into_iter().map(|v| v.unwrap_or(f64::NAN)).collect());
+        }
+
+        let mut param_values = Vec::with_capacity(params.len());
+        for (arg, name) in call.args[num_series..].iter().zip(params) {
+            let text = input[arg.clone()].trim();
+            match text.parse::<f64>() {
+                Ok(v) => param_values.push(v),
+                Err(_) => {
+                    let msg = format!("expected a number for {name}, found '{text}'");
+                    return Err(series_error(input, arg.clone(), msg));
+                }
+            }
+        }
+
+        output.push_str(&input[last..call.range.start]);
+        output.push_str(&placeholder(series.len(), &input[call.range.clone()]));
+        last = call.range.end;
+
+        series.push(call.fun.apply(&env.time, &args, &param_values));
+    }
+    output.push_str(&input[last..]);
+
+    Ok((output, series))
+}
+
+/// Finds the outermost calls of series functions
+fn find_series_calls(input: &str) -> Vec<Call> {
+    let mut calls = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c == '"' {
+            for (_, c) in chars.by_ref() {
+                if c == '"' {
+                    break;
+                }
+            }
+            continue;
+        }
+        if !(c.is_alphabetic() || c == '_') {
+            continue;
+        }
+
+        let mut end = start + c.len_utf8();
+        while let Some(&(i, c)) = chars.peek() {
+            if !(c.is_alphanumeric() || c == '_') {
+                break;
+            }
+            end = i + c.len_utf8();
+            chars.next();
+        }
+        let Some(fun) = SeriesFun::from_name(&input[start..end]) else {
+            continue;
+        };
+
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        let Some((open, _)) = chars.next_if(|(_, c)| *c == '(') else {
+            continue;
+        };
+
+        let mut depth = 1;
+        let mut args = Vec::new();
+        let mut arg_start = open + 1;
+        for (i, c) in chars.by_ref() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                ',' if depth == 1 => {
+                    args.push(arg_start..i);
+                    arg_start = i + 1;
+                }
+                _ => (),
+            }
+            if depth == 0 {
+                args.push(arg_start..i);
+                // `if (cond) { .. }` is an if expression of the evaluator itself
+                let next = chars.clone().find(|(_, c)| !c.is_whitespace());
+                if matches!(next, Some((_, '{'))) {
+                    let offset = open + 1;
+                    calls.extend(find_series_calls(&input[offset..i]).into_iter().map(|c| {
+                        Call {
+                            fun: c.fun,
+                            range: c.range.start + offset..c.range.end + offset,
+                            args: (c.args.iter())
+                                .map(|a| a.start + offset..a.end + offset)
+                                .collect(),
+                        }
+                    }));
+                } else {
+                    calls.push(Call {
+                        fun,
+                        range: start..i + 1,
+                        args,
+                    });
+                }
+                break;
+            }
+        }
+    }
+    calls
+}
+
+fn series_ident(index: usize) -> String {
+    let mut ident = String::from("sf_");
+    let mut i = index;
+    loop {
+        ident.push((b'a' + (i % 26) as u8) as char);
+        i /= 26;
+        if i == 0 {
+            break;
+        }
+    }
+    ident
+}
+
+/// The identifier of the series padded to the same lines and columns as `call`
+fn placeholder(index: usize, call: &str) -> String {
+    let ident = series_ident(index);
+    let mut text = ident.clone();
+    for (i, c) in call.chars().enumerate() {
+        match c {
+            '\n' => text.push('\n'),
+            _ if i < ident.len() => (),
+            _ => text.push(' '),
+        }
+    }
+    text
+}
+
+/// Replaces everything outside of `range` with whitespace, keeping lines and columns
+fn mask(input: &str, range: Range<usize>) -> String {
+    (input.char_indices())
+        .map(|(i, c)| match c {
+            '\n' => '\n',
+            _ if range.contains(&i) => c,
+            _ => ' ',
+        })
+        .collect()
+}
+
+fn pos_at(input: &str, byte: usize) -> Pos {
+    let mut pos = Pos::new(0, 0);
+    for c in input[..byte].chars() {
+        match c {
+            '\n' => {
+                pos.line += 1;
+                pos.col = 0;
+            }
+            _ => pos.col += 1,
+        }
+    }
+    pos
+}
+
+/// Inverse of [`pos_at`], the end of the input if `pos` is past it
+fn byte_at(input: &str, pos: Pos) -> usize {
+    let mut p = Pos::new(0, 0);
+    for (i, c) in input.char_indices() {
+        if p == pos {
+            return i;
+        }
+        match c {
+            '\n' => {
+                p.line += 1;
+                p.col = 0;
             }
-            _ => Val::Float(f64::NAN),
+            _ => p.col += 1,
+        }
+    }
+    input.len()
+}
+
+fn series_error(input: &str, range: Range<usize>, msg: String) -> Error {
+    Error::Series {
+        msg,
+        start: pos_at(input, range.start),
+        end: pos_at(input, range.end),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{DataEntry, EntryKind, Version};
+
+    use super::*;
+
+    fn stream(name: &str, time: &[u32], values: &[f64]) -> LogStream {
+        LogStream {
+            version: Version::V1,
+            start: None,
+            time: time.to_vec(),
+            entries: vec![DataEntry {
+                name: name.into(),
+                kind: EntryKind::F64(values.to_vec()),
+            }],
+        }
+    }
+
+    fn lerp(first: &[u32], other: &[u32], offsets: &[i64]) -> Vec<Lerp> {
+        let data = [stream("a", first, &[]), stream("b", other, &[])];
+        lerp_table(&data, offsets).remove(0)
+    }
+
+    #[test]
+    fn ident_ranges_skip_numbers_and_strings() {
+        let input = "radius * 1.5e3 + \"a b\" - x_2";
+        let idents: Vec<_> = (ident_ranges(input).into_iter())
+            .map(|r| &input[r])
+            .collect();
+        assert_eq!(idents, ["radius", "x_2"]);
+    }
+
+    #[test]
+    fn byte_at_inverts_pos_at() {
+        let input = "a +\nbc";
+        for i in [0, 2, 4, 5, input.len()] {
+            assert_eq!(byte_at(input, pos_at(input, i)), i);
         }
-    } else {
-        Val::Float(time as f64 / 1000.0)
+    }
+
+    #[test]
+    fn lerp_exact_and_between() {
+        let table = lerp(&[0, 10, 20, 30], &[0, 20], &[0, 0]);
+        use Lerp::*;
+        assert_eq!(table, [Exact(0), Between(0, 0.5), Exact(1), Exact(1)]);
+    }
+
+    #[test]
+    fn lerp_before_first_sample_uses_it() {
+        let table = lerp(&[0, 15], &[10, 20], &[0, 0]);
+        assert_eq!(table, [Lerp::Exact(0), Lerp::Between(0, 0.5)]);
+    }
+
+    #[test]
+    fn lerp_empty_stream_is_missing() {
+        let table = lerp(&[0, 10], &[], &[0, 0]);
+        assert_eq!(table, [Lerp::Missing, Lerp::Missing]);
+    }
+
+    #[test]
+    fn lerp_offsets() {
+        use Lerp::*;
+        // the other stream is shifted to 5 and 15
+        let expected = [Exact(0), Exact(0), Between(0, 0.5), Exact(1), Exact(1)];
+        assert_eq!(lerp(&[0, 5, 10, 15, 20], &[0, 10], &[0, 5]), expected);
+        // only the difference of the offsets matters
+        assert_eq!(lerp(&[0, 5, 10, 15, 20], &[0, 10], &[-5, 0]), expected);
+
+        // the other stream is shifted to -5 and 5
+        assert_eq!(
+            lerp(&[0, 5], &[0, 10], &[0, -5]),
+            [Between(0, 0.5), Exact(1)]
+        );
+    }
+
+    #[test]
+    fn lerp_restarts_at_backwards_timestamps() {
+        let table = lerp(&[0, 20, 10, 30], &[0, 10, 20, 30], &[0, 0]);
+        use Lerp::*;
+        assert_eq!(table, [Exact(0), Exact(2), Exact(1), Exact(3)]);
+    }
+
+    #[test]
+    fn eval_interpolates_streams_of_different_length() {
+        let data: Arc<[LogStream]> = Arc::from([
+            stream("a", &[0, 10, 20, 30], &[1.0, 2.0, 3.0, 4.0]),
+            stream("b", &[0, 20], &[0.0, 10.0]),
+        ]);
+        let Ok(values) = eval(&Expr::new("time", "a - b"), data, &Globals::default()) else {
+            panic!("evaluation failed");
+        };
+
+        let y: Vec<f64> = values.iter().map(|p| p.y).collect();
+        assert_eq!(y, [1.0, -3.0, -7.0, -6.0]);
+        let x: Vec<f64> = values.iter().map(|p| p.x).collect();
+        assert_eq!(x, [0.0, 0.01, 0.02, 0.03]);
     }
 }
@@ -0,0 +1,229 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+use egui::{ColorImage, Context, Event, ViewportCommand};
+
+use crate::analysis;
+use crate::app::{PlotApp, PlotValues};
+use crate::plot::PlotMode;
+use crate::render;
+
+pub const DEFAULT_EXPORT_DPI: f32 = 300.0;
+const METERS_PER_INCH: f32 = 0.0254;
+
+pub enum PendingExport {
+    /// Wait a frame so menus and dialogs are closed before capturing
+    Requested(ExportTarget),
+    Capturing(ExportTarget),
+}
+
+pub enum ExportTarget {
+    File(PathBuf),
+    Clipboard,
+}
+
+impl PlotApp {
+    pub fn export_png_dialog(&mut self, ctx: &Context) {
+        if !self.can_export() {
+            return;
+        }
+
+        let tab = self.config.selected_tab;
+        let file_name = format!("{}.png", self.config.tabs[tab].name);
+        let dialog = rfd::FileDialog::new()
+            .add_filter("PNG", &["png"])
+            .set_file_name(file_name);
+        if let Some(path) = dialog.save_file() {
+            self.pending_export = Some(PendingExport::Requested(ExportTarget::File(path)));
+            ctx.request_repaint();
+        }
+    }
+
+    /// Writes the visible part of the selected tab as vector graphics, see [`render::render_svg`]
+    pub fn export_svg_dialog(&mut self) {
+        if !self.can_export() {
+            return;
+        }
+        let Some(data) = &self.data else {
+            return;
+        };
+
+        let tab = self.config.selected_tab;
+        let tab_cfg = &self.config.tabs[tab];
+        let dialog = rfd::FileDialog::new()
+            .add_filter("SVG", &["svg"])
+            .set_file_name(format!("{}.svg", tab_cfg.name));
+        let Some(path) = dialog.save_file() else {
+            return;
+        };
+
+        let series: Vec<_> = (data.plots[tab].iter().zip(tab_cfg.plots.iter()))
+            .enumerate()
+            .filter_map(|(i, (v, p))| match v {
+                PlotValues::Result(Ok(d)) if !p.hidden && !d.is_empty() => Some((i, d)),
+                _ => None,
+            })
+            .collect();
+        let (series, axis_map) = render::prepare_series(tab_cfg, &series);
+
+        let time_format = self.config.time_format(data);
+        let svg = render::render_svg(&self.config, tab_cfg, &series, axis_map, time_format);
+        if let Err(e) = std::fs::write(&path, svg) {
+            self.error = Some(format!("Error exporting '{}': {e}", path.display()));
+        }
+    }
+
+    /// Copies the time at the cursor and the values of all visible time plots there as a tab
+    /// separated row, e.g. to paste it into a spreadsheet
+    pub fn copy_values(&mut self) {
+        let Some(data) = &self.data else {
+            self.error = Some("No data loaded".into());
+            return;
+        };
+        let cfg = &self.config;
+        let selected = &cfg.tabs[cfg.selected_tab];
+        // fall back to the row inspected in the values table or the last pinned cursor
+        let cursor = (cfg.cursor)
+            .or(selected.inspected)
+            .or(selected.pinned.last().copied());
+        let Some(x) = cursor else {
+            self.error = Some("No cursor to copy the values at".into());
+            return;
+        };
+
+        let mut row = vec![cfg.time_format(data).format(x)];
+        for tab in cfg.visible_tabs() {
+            let tab_cfg = &cfg.tabs[tab];
+            if tab_cfg.mode != PlotMode::Time || tab_cfg.compared_windows().is_some() {
+                continue;
+            }
+            for (v, p) in data.plots[tab].iter().zip(tab_cfg.plots.iter()) {
+                if p.hidden {
+                    continue;
+                }
+                let y = match v {
+                    PlotValues::Result(Ok(d)) => analysis::interpolate(&d.points, x),
+                    _ => None,
+                };
+                row.push(y.map_or(String::new(), |y| cfg.number_format.format(y)));
+            }
+        }
+
+        let text = row.join("\t");
+        match self.clipboard().and_then(|c| c.set_text(text)) {
+            Ok(()) => self.toast = Some(("Copied values".into(), Instant::now())),
+            Err(e) => self.error = Some(format!("Error copying values: {e}")),
+        }
+    }
+
+    pub fn copy_png(&mut self, ctx: &Context) {
+        if !self.can_export() {
+            return;
+        }
+
+        self.pending_export = Some(PendingExport::Requested(ExportTarget::Clipboard));
+        ctx.request_repaint();
+    }
+
+    /// Checks that the selected tab has something to export, otherwise sets an error
+    fn can_export(&mut self) -> bool {
+        let Some(data) = &self.data else {
+            self.error = Some("No data loaded".into());
+            return false;
+        };
+
+        let tab = self.config.selected_tab;
+        let has_values = data.plots[tab]
+            .iter()
+            .any(|p| matches!(p, PlotValues::Result(Ok(v)) if !v.is_empty()));
+        if !has_values {
+            let name = &self.config.tabs[tab].name;
+            self.error = Some(format!("Plot '{name}' has no data"));
+            return false;
+        }
+        true
+    }
+
+    pub fn update_export(&mut self, ctx: &Context) {
+        match self.pending_export.take() {
+            Some(PendingExport::Requested(target)) => {
+                ctx.send_viewport_cmd(ViewportCommand::Screenshot);
+                self.pending_export = Some(PendingExport::Capturing(target));
+            }
+            Some(PendingExport::Capturing(target)) => {
+                let image = ctx.input(|i| {
+                    i.raw.events.iter().find_map(|e| match e {
+                        Event::Screenshot { image, .. } => Some(Arc::clone(image)),
+                        _ => None,
+                    })
+                });
+                let Some(image) = image else {
+                    self.pending_export = Some(PendingExport::Capturing(target));
+                    ctx.request_repaint();
+                    return;
+                };
+
+                let Some(rect) = self.config.plot_rect else {
+                    self.error = Some("No plot is visible".into());
+                    return;
+                };
+                let image = image.region(&rect, Some(ctx.pixels_per_point()));
+                match target {
+                    ExportTarget::File(path) => {
+                        if let Err(e) = write_png(&path, &image, self.export_dpi) {
+                            self.error = Some(format!("Error exporting '{}': {e}", path.display()));
+                        }
+                    }
+                    ExportTarget::Clipboard => match self.copy_image(&image) {
+                        Ok(()) => self.toast = Some(("Copied plot".into(), Instant::now())),
+                        Err(e) => self.error = Some(format!("Error copying plot: {e}")),
+                    },
+                }
+            }
+            None => (),
+        }
+    }
+
+    fn copy_image(&mut self, image: &ColorImage) -> Result<(), arboard::Error> {
+        let [width, height] = image.size;
+        let bytes: Vec<u8> = image.pixels.iter().flat_map(|c| c.to_array()).collect();
+        self.clipboard()?.set_image(arboard::ImageData {
+            width,
+            height,
+            bytes: Cow::Owned(bytes),
+        })
+    }
+
+    /// Opened on first use and then kept
+    fn clipboard(&mut self) -> Result<&mut arboard::Clipboard, arboard::Error> {
+        if self.clipboard.is_none() {
+            self.clipboard = Some(arboard::Clipboard::new()?);
+        }
+        Ok(self.clipboard.as_mut().unwrap())
+    }
+}
+
+pub fn write_png(path: &Path, image: &ColorImage, dpi: f32) -> anyhow::Result<()> {
+    let file = File::create(path)?;
+    let [width, height] = image.size;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let pixels_per_meter = (dpi / METERS_PER_INCH).round() as u32;
+    encoder.set_pixel_dims(Some(png::PixelDimensions {
+        xppu: pixels_per_meter,
+        yppu: pixels_per_meter,
+        unit: png::Unit::Meter,
+    }));
+
+    let bytes: Vec<u8> = image.pixels.iter().flat_map(|c| c.to_array()).collect();
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&bytes)?;
+    writer.finish()?;
+
+    Ok(())
+}
@@ -1,17 +1,38 @@
+use std::collections::{HashMap, HashSet};
 use std::fmt::Write as _;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write as _};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
 
 use egui::{Align2, Color32, Context, Id, LayerId, Order, Pos2, Rect, TextStyle, Vec2};
+use flate2::read::GzDecoder;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use crate::app::{Job, PlotData, PlotValues};
-use crate::data::{self, LogStream, SanityError};
+use crate::app::{Comparison, PlotData, TimestampOrder};
+use crate::data::{self, LogStream, SanityError, Version};
+use crate::plot::{self, Marker};
+use crate::util;
 use crate::PlotApp;
 
-#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+const MAX_RECENT: usize = 10;
+
+/// Minimum time between two automatic reloads, files may be written to continuously
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+pub const DEFAULT_POLL_INTERVAL: f32 = 5.0;
+pub const MAX_POLL_INTERVAL: f32 = 300.0;
+/// How often samples piped into stdin are added to the plots
+const STDIN_BATCH_INTERVAL: Duration = Duration::from_millis(500);
+/// Stands in for the directory of the run, e.g. as key of the markers
+const STDIN_DIR: &str = "-";
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq)]
 pub struct Files {
     pub dir: PathBuf,
     pub items: Vec<PathBuf>,
@@ -19,6 +40,10 @@ pub struct Files {
 
 #[derive(Debug)]
 pub struct SelectableFiles {
+    /// The opened path, which may contain multiple runs when scanning subfolders
+    pub root: PathBuf,
+    pub runs: Vec<Files>,
+    pub selected_run: usize,
     pub dir: PathBuf,
     pub by_header: Vec<Vec<SelectableFile>>,
     pub with_error: Vec<ErrorFile>,
@@ -30,18 +55,560 @@ pub struct SelectableFile {
     pub file: PathBuf,
     pub stream: LogStream,
     pub sanity_check: Result<(), SanityError>,
+    /// Problems that didn't prevent reading the file
+    pub warnings: Vec<String>,
 }
 
 #[derive(Debug)]
 pub struct ErrorFile {
+    /// Absolute path, if it could be resolved
     pub file: PathBuf,
+    /// Version in the header, if it could be read
+    pub version: Option<Version>,
     pub error: data::Error,
 }
 
+/// CSV export of the runs below `root` on another thread, see [`batch_export_csv`]
+pub struct BatchExport {
+    handle: JoinHandle<Result<Vec<(PathBuf, Result<PathBuf, String>)>, data::Error>>,
+    /// Number of exported runs and of all runs
+    progress: Receiver<(usize, usize)>,
+    cancel: Arc<AtomicBool>,
+    root: PathBuf,
+    exported: usize,
+    total: usize,
+}
+
+impl BatchExport {
+    pub fn progress(&self) -> f32 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.exported as f32 / self.total as f32
+    }
+
+    pub fn text(&self) -> String {
+        format!("{} of {} runs", self.exported, self.total)
+    }
+}
+
+pub struct Loading {
+    handle: JoinHandle<SelectableFiles>,
+    /// Number of bytes read since the last update
+    progress: Receiver<u64>,
+    cancel: Arc<AtomicBool>,
+    after: AfterLoading,
+    bytes_read: u64,
+    bytes_total: u64,
+}
+
+enum AfterLoading {
+    /// Always show the selection dialog, along with the other runs that were found
+    Select {
+        root: PathBuf,
+        runs: Vec<Files>,
+        selected_run: usize,
+    },
+    /// Only show the selection dialog if opening a file or the sanity check failed
+    Show { always_show_dialog: bool },
+    /// Append the usable files to the loaded data
+    Append,
+    /// Draw the usable files on top of the loaded data
+    Compare,
+    /// Show the usable files like [`AfterLoading::Show`], then load the files to compare if any.
+    /// The warnings about the dropped paths are kept.
+    Dropped {
+        compared: Option<Files>,
+        warnings: Vec<String>,
+    },
+}
+
+impl Loading {
+    fn start(files: Files, after: AfterLoading) -> Self {
+        let bytes_total = (files.items.iter())
+            .filter_map(|f| std::fs::metadata(f).ok())
+            .map(|m| m.len())
+            .sum();
+        let (sender, progress) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let cancel = Arc::clone(&cancel);
+            std::thread::spawn(move || open_files(files, &sender, &cancel))
+        };
+
+        Self {
+            handle,
+            progress,
+            cancel,
+            after,
+            bytes_read: 0,
+            bytes_total,
+        }
+    }
+
+    fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn progress(&self) -> f32 {
+        if self.bytes_total == 0 {
+            return 1.0;
+        }
+        self.bytes_read as f32 / self.bytes_total as f32
+    }
+}
+
+struct ProgressReader<'a, R> {
+    inner: R,
+    progress: &'a Sender<u64>,
+    cancel: &'a AtomicBool,
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.cancel.load(Ordering::Relaxed) {
+            return Err(io::Error::other("loading was cancelled"));
+        }
+
+        let n = self.inner.read(buf)?;
+        self.progress.send(n as u64).ok();
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for ProgressReader<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+pub struct FileWatcher {
+    files: Files,
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<notify::Event>>,
+    changed: bool,
+    last_reload: Instant,
+}
+
+impl FileWatcher {
+    pub fn new(ctx: &Context, files: Files) -> notify::Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let ctx = ctx.clone();
+        let mut watcher = notify::recommended_watcher(move |e| {
+            if sender.send(e).is_ok() {
+                ctx.request_repaint();
+            }
+        })?;
+        for f in files.items.iter() {
+            watcher.watch(f, RecursiveMode::NonRecursive)?;
+        }
+
+        Ok(Self {
+            files,
+            _watcher: watcher,
+            events,
+            changed: false,
+            last_reload: Instant::now(),
+        })
+    }
+
+    /// Returns true if a watched file changed and the files should be reloaded now.
+    fn poll(&mut self, ctx: &Context) -> bool {
+        while let Ok(e) = self.events.try_recv() {
+            if let Ok(e) = e {
+                self.changed |= e.kind.is_modify();
+            }
+        }
+
+        if !self.changed {
+            return false;
+        }
+
+        let elapsed = self.last_reload.elapsed();
+        if elapsed < RELOAD_INTERVAL {
+            ctx.request_repaint_after(RELOAD_INTERVAL - elapsed);
+            return false;
+        }
+
+        self.changed = false;
+        self.last_reload = Instant::now();
+        true
+    }
+}
+
+/// Samples piped into stdin, which are read on another thread as they arrive and passed on in
+/// batches by a second one
+pub struct StdinStream {
+    batches: Receiver<Result<LogStream, data::Error>>,
+    /// Opened before, reopened if nothing is piped into stdin
+    previous: Option<Files>,
+    /// Disconnected when dropped, which stops the threads
+    _stop: Sender<()>,
+}
+
+/// Size and modification time of a file
+type FileStamp = Option<(u64, SystemTime)>;
+
+/// Alternative to the [`FileWatcher`] that checks the files in an interval on another thread, for
+/// file systems without change notifications like some network shares. New log files in the
+/// directory are picked up if all of its files are opened.
+pub struct FilePoller {
+    files: Files,
+    interval: Duration,
+    /// Samples read from each file, see [`PlotData::file_samples`]
+    samples: Vec<Option<usize>>,
+    changes: Receiver<PolledChange>,
+    /// Polling pauses while the window isn't focused
+    paused: Arc<AtomicBool>,
+    /// Dropped along with the poller, which stops the thread
+    _stop: Sender<()>,
+}
+
+enum PolledChange {
+    /// Samples written to the end of the files, with the index of the file
+    Append(Vec<(usize, LogStream)>),
+    /// Files were added or changed otherwise, so they have to be reloaded
+    Reload(Files),
+}
+
+impl FilePoller {
+    fn new(ctx: &Context, files: Files, samples: Vec<Option<usize>>, interval: Duration) -> Self {
+        let (sender, changes) = mpsc::channel();
+        let (stop, stopped) = mpsc::channel::<()>();
+        let paused = Arc::new(AtomicBool::new(false));
+        {
+            let (ctx, files, paused) = (ctx.clone(), files.clone(), Arc::clone(&paused));
+            let mut samples = samples.clone();
+            std::thread::spawn(move || {
+                let listing = list_log_files(&files.dir);
+                let mut stamps = file_stamps(&files.items);
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    if paused.load(Ordering::Relaxed) {
+                        continue;
+                    }
+                    let Some(change) = poll_files(&files, &listing, &mut stamps, &mut samples)
+                    else {
+                        continue;
+                    };
+                    let reload = matches!(change, PolledChange::Reload(_));
+                    if sender.send(change).is_err() {
+                        break;
+                    }
+                    ctx.request_repaint();
+                    if reload {
+                        break;
+                    }
+                }
+            });
+        }
+
+        Self {
+            files,
+            interval,
+            samples,
+            changes,
+            paused,
+            _stop: stop,
+        }
+    }
+
+    fn poll(&mut self, ctx: &Context) -> Option<PolledChange> {
+        let unfocused = ctx.input(|i| i.viewport().focused) == Some(false);
+        self.paused.store(unfocused, Ordering::Relaxed);
+
+        let change = self.changes.try_recv().ok()?;
+        if let PolledChange::Append(appended) = &change {
+            for (i, s) in appended.iter() {
+                if let Some(Some(n)) = self.samples.get_mut(*i) {
+                    *n += s.len();
+                }
+            }
+        }
+        Some(change)
+    }
+}
+
+/// Checks the files once, the new samples are read if the files only grew, see [`FilePoller`]
+fn poll_files(
+    files: &Files,
+    listing: &[PathBuf],
+    stamps: &mut Vec<FileStamp>,
+    samples: &mut [Option<usize>],
+) -> Option<PolledChange> {
+    let items = if files.items == listing {
+        list_log_files(&files.dir)
+    } else {
+        files.items.clone()
+    };
+    if items != files.items {
+        let dir = files.dir.clone();
+        return Some(PolledChange::Reload(Files { dir, items }));
+    }
+
+    let new_stamps = file_stamps(&files.items);
+    let mut appended = Vec::new();
+    for (i, (old, new)) in stamps.iter().zip(new_stamps.iter()).enumerate() {
+        if old == new {
+            continue;
+        }
+        let grown = matches!((old, new), (Some((a, _)), Some((b, _))) if b > a);
+        let read = match samples.get(i).copied().flatten() {
+            Some(n) if grown => read_new_samples(&files.items[i], n).ok().map(|s| (n, s)),
+            _ => None,
+        };
+        let Some((n, new_samples)) = read else {
+            return Some(PolledChange::Reload(files.clone()));
+        };
+        if !new_samples.is_empty() {
+            samples[i] = Some(n + new_samples.len());
+            appended.push((i, new_samples));
+        }
+    }
+    *stamps = new_stamps;
+    (!appended.is_empty()).then_some(PolledChange::Append(appended))
+}
+
+fn read_new_samples(path: &Path, skip: usize) -> Result<LogStream, data::Error> {
+    let mut reader = BufReader::new(File::open(path)?);
+    data::read_file_from(&mut reader, skip)
+}
+
+fn list_log_files(dir: &Path) -> Vec<PathBuf> {
+    match find_files(dir.to_path_buf(), false) {
+        Ok(runs) => runs.into_iter().next().map(|r| r.items).unwrap_or_default(),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn file_stamps(items: &[PathBuf]) -> Vec<FileStamp> {
+    (items.iter())
+        .map(|p| {
+            let meta = std::fs::metadata(p).ok()?;
+            Some((meta.len(), meta.modified().ok()?))
+        })
+        .collect()
+}
+
 impl PlotApp {
+    pub fn update_watcher(&mut self, ctx: &Context) {
+        let files = match &self.files {
+            Some(f) if self.auto_reload => f,
+            _ => {
+                self.watcher = None;
+                self.poller = None;
+                return;
+            }
+        };
+
+        // only one of both is active
+        if self.poll_reload {
+            self.watcher = None;
+            let interval = self.poll_interval.clamp(1.0, MAX_POLL_INTERVAL);
+            let interval = Duration::from_secs_f32(interval);
+            let samples = match &self.data {
+                Some(d) if d.dir == files.dir => d.file_samples.clone(),
+                _ => Vec::new(),
+            };
+            let up_to_date = matches!(&self.poller,
+                Some(p) if p.files == *files && p.interval == interval && p.samples == samples);
+            // the samples of the files are known once they are loaded
+            if !up_to_date && self.loading.is_none() {
+                self.poller = Some(FilePoller::new(ctx, files.clone(), samples, interval));
+            }
+            let Some(poller) = &mut self.poller else {
+                return;
+            };
+            match poller.poll(ctx) {
+                Some(PolledChange::Append(appended)) => self.append_new_samples(appended),
+                Some(PolledChange::Reload(files)) => {
+                    self.poller = None;
+                    self.try_open_files(files, false);
+                }
+                None => (),
+            }
+            return;
+        }
+        self.poller = None;
+
+        let up_to_date = matches!(&self.watcher, Some(w) if w.files == *files);
+        if !up_to_date {
+            match FileWatcher::new(ctx, files.clone()) {
+                Ok(w) => self.watcher = Some(w),
+                Err(e) => {
+                    self.error = Some(format!("Error watching files: {e}"));
+                    self.auto_reload = false;
+                    self.watcher = None;
+                    return;
+                }
+            }
+        }
+
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+        if watcher.poll(ctx) {
+            let files = watcher.files.clone();
+            self.try_open_files(files, false);
+        }
+    }
+
     pub fn open_dir_dialog(&mut self) {
         if let Some(path) = rfd::FileDialog::new().pick_folder() {
-            self.try_open_dir(path);
+            self.try_open(path);
+        }
+    }
+
+    /// Plots a stream piped into stdin, e.g. `cat 0.s3lg | s3plot -`, as the samples arrive
+    pub fn open_stdin(&mut self, ctx: &Context) {
+        let (sender, receiver) = mpsc::channel();
+        let (stop, stopped) = mpsc::channel::<()>();
+        let pending = Arc::new(Mutex::new(None));
+        let stop_reading = Arc::new(AtomicBool::new(false));
+        let ended = Arc::new(AtomicBool::new(false));
+
+        let reader_sender = sender.clone();
+        let (reader_pending, reader_stop, reader_ended) =
+            (pending.clone(), stop_reading.clone(), ended.clone());
+        std::thread::spawn(move || {
+            let mut reader = BufReader::new(io::stdin().lock());
+            let result = data::read_stream_live(&mut reader, &reader_pending, &reader_stop);
+            if let Err(e) = result {
+                let _ = reader_sender.send(Err(e));
+            }
+            reader_ended.store(true, Ordering::Relaxed);
+        });
+
+        // on a timer, so samples already read aren't held back while the pipe stalls
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            loop {
+                let done = ended.load(Ordering::Relaxed);
+                let samples = pending.lock().unwrap().take();
+                if let Some(samples) = samples {
+                    if sender.send(Ok(samples)).is_err() {
+                        break;
+                    }
+                    ctx.request_repaint();
+                }
+                let timeout = Err(RecvTimeoutError::Timeout);
+                if done || stopped.recv_timeout(STDIN_BATCH_INTERVAL) != timeout {
+                    break;
+                }
+            }
+            stop_reading.store(true, Ordering::Relaxed);
+            // the end of the stream is noticed once both threads are done
+            drop(sender);
+            ctx.request_repaint();
+        });
+
+        self.stdin = Some(StdinStream {
+            batches: receiver,
+            previous: self.files.take(),
+            _stop: stop,
+        });
+    }
+
+    pub fn update_stdin(&mut self) {
+        let Some(stdin) = &self.stdin else {
+            return;
+        };
+
+        let mut batches = Vec::new();
+        let mut ended = false;
+        loop {
+            match stdin.batches.try_recv() {
+                Ok(Ok(b)) => batches.push(b),
+                // nothing was piped into stdin
+                Ok(Err(data::Error::IO(e))) if e.kind() == io::ErrorKind::UnexpectedEof => (),
+                Ok(Err(e)) => self.error = Some(format!("Error reading stdin: {e}")),
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    ended = true;
+                    break;
+                }
+            }
+        }
+
+        let live = (self.data.as_ref()).is_some_and(|d| d.dir == Path::new(STDIN_DIR));
+        let mut batches = batches.into_iter().filter(|b| !b.is_empty());
+        if live {
+            let data = self.data.as_mut().unwrap();
+            let mut streams = data.streams.to_vec();
+            let mut changed = false;
+            for b in batches {
+                streams[0].extend(&b);
+                changed = true;
+            }
+            if changed {
+                data.streams = streams.into();
+                data.plots = plot::start_all_jobs(&self.config, &data.dir, &data.streams);
+            }
+        } else if let Some(mut stream) = batches.next() {
+            for b in batches {
+                stream.extend(&b);
+            }
+            for t in self.config.tabs.iter_mut() {
+                t.restore_bounds = true;
+            }
+            let streams: Arc<[LogStream]> = vec![stream].into();
+            let dir = PathBuf::from(STDIN_DIR);
+            let plots = plot::start_all_jobs(&self.config, &dir, &streams);
+            self.data = Some(PlotData {
+                dir,
+                streams,
+                plots,
+                seams: Vec::new(),
+                backwards: Vec::new(),
+                file_samples: Vec::new(),
+                comparison: None,
+                baseline: self.data.take().and_then(|d| d.baseline),
+            });
+        }
+
+        if ended {
+            let stdin = self.stdin.take().unwrap();
+            let live = (self.data.as_ref()).is_some_and(|d| d.dir == Path::new(STDIN_DIR));
+            if !live {
+                self.warnings
+                    .push("No samples were piped into stdin".into());
+                if let Some(files) = stdin.previous {
+                    self.try_open_files(files, false);
+                }
+            }
+        }
+    }
+
+    pub fn append_dir_dialog(&mut self) {
+        self.load_additional_dir(AfterLoading::Append);
+    }
+
+    pub fn compare_dir_dialog(&mut self) {
+        self.load_additional_dir(AfterLoading::Compare);
+    }
+
+    fn load_additional_dir(&mut self, after: AfterLoading) {
+        if self.data.is_none() {
+            self.error = Some("No data loaded".into());
+            return;
+        }
+        let Some(path) = rfd::FileDialog::new().pick_folder() else {
+            return;
+        };
+        match find_files(path.clone(), false) {
+            Ok(runs) => {
+                let files = runs.into_iter().next().unwrap();
+                self.start_loading(files, after);
+            }
+            Err(e) => self.error = Some(format!("Error opening '{}': {e}", path.display())),
+        }
+    }
+
+    pub fn open_file_dialog(&mut self) {
+        let dialog = rfd::FileDialog::new()
+            .add_filter("s3lg", &["s3lg", "gz"])
+            .add_filter("CSV", &["csv"]);
+        if let Some(path) = dialog.pick_file() {
+            self.try_open(path);
         }
     }
 
@@ -101,72 +668,310 @@ impl PlotApp {
         }
 
         // Collect dropped files
-        if !ctx.input(|i| i.raw.dropped_files.is_empty()) {
-            if let Some(p) = ctx.input(|i| i.raw.dropped_files.first().and_then(|f| f.path.clone()))
-            {
-                self.try_open_dir(p);
+        let dropped: Vec<PathBuf> = ctx.input(|i| {
+            i.raw
+                .dropped_files
+                .iter()
+                .filter_map(|f| f.path.clone())
+                .collect()
+        });
+        match dropped.len() {
+            0 => (),
+            1 => self.try_open(dropped.into_iter().next().unwrap()),
+            _ => self.try_open_all(dropped),
+        }
+    }
+
+    pub fn export_csv_dialog(&mut self) {
+        let Some(data) = &self.data else {
+            self.error = Some("No data loaded".into());
+            return;
+        };
+
+        let dialog = rfd::FileDialog::new().add_filter("CSV", &["csv"]);
+        if let Some(path) = dialog.save_file() {
+            let markers = match self.config.markers.get(&data.dir) {
+                Some(m) if self.export_markers => m.as_slice(),
+                _ => &[],
+            };
+            if let Err(e) = export_csv(&data.streams, markers, &path) {
+                self.error = Some(format!("Error exporting '{}': {e}", path.display()));
             }
         }
     }
 
-    pub fn try_open_dir(&mut self, dir: PathBuf) {
-        if let Ok(files) = find_files(dir) {
-            self.selectable_files = Some(open_files(files));
+    /// Exports every run below a chosen directory on another thread, see [`batch_export_csv`]
+    pub fn batch_export_csv_dialog(&mut self, ctx: &Context) {
+        let Some(root) = rfd::FileDialog::new().pick_folder() else {
+            return;
+        };
+        let dialog = rfd::FileDialog::new().set_title("Output directory");
+        let Some(out_dir) = dialog.pick_folder() else {
+            return;
+        };
+
+        let markers = if self.export_markers {
+            self.config.markers.clone()
+        } else {
+            HashMap::new()
+        };
+        let (sender, progress) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let (root, ctx, cancel) = (root.clone(), ctx.clone(), Arc::clone(&cancel));
+            std::thread::spawn(move || {
+                let result = batch_export_csv(&root, &out_dir, &markers, &sender, &cancel);
+                ctx.request_repaint();
+                result
+            })
+        };
+        self.batch_export = Some(BatchExport {
+            handle,
+            progress,
+            cancel,
+            root,
+            exported: 0,
+            total: 0,
+        });
+    }
+
+    pub fn cancel_batch_export(&mut self) {
+        if let Some(b) = &self.batch_export {
+            b.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn update_batch_export(&mut self, ctx: &Context) {
+        let Some(batch) = &mut self.batch_export else {
+            return;
+        };
+        while let Ok((exported, total)) = batch.progress.try_recv() {
+            (batch.exported, batch.total) = (exported, total);
         }
+        if !batch.handle.is_finished() {
+            ctx.request_repaint();
+            return;
+        }
+
+        let batch = self.batch_export.take().unwrap();
+        let cancelled = batch.cancel.load(Ordering::Relaxed);
+        let result = batch.handle.join().expect("failed to join export thread");
+        match result {
+            Ok(results) => {
+                let mut exported = 0;
+                for (dir, r) in results {
+                    match r {
+                        Ok(_) => exported += 1,
+                        Err(e) => {
+                            let msg = format!("Error exporting '{}': {e}", dir.display());
+                            self.warnings.push(msg);
+                        }
+                    }
+                }
+                let msg = if cancelled {
+                    format!("Cancelled after exporting {exported} runs")
+                } else {
+                    format!("Exported {exported} runs")
+                };
+                self.toast = Some((msg, Instant::now()));
+            }
+            Err(e) => {
+                let root = batch.root.display();
+                self.error = Some(format!("Error opening '{root}': {e}"));
+            }
+        }
+    }
+
+    /// Open either a directory or a single file
+    pub fn try_open(&mut self, path: PathBuf) {
+        match find_files(path.clone(), self.scan_subfolders) {
+            Ok(runs) => {
+                self.add_recent(path.clone());
+                let files = runs[0].clone();
+                let after = AfterLoading::Select {
+                    root: path,
+                    runs,
+                    selected_run: 0,
+                };
+                self.start_loading(files, after);
+            }
+            Err(e) => self.error = Some(format!("Error opening '{}': {e}", path.display())),
+        }
+    }
+
+    /// Open multiple paths as separate runs, the first is shown and the second is compared to it.
+    /// Paths that can't be opened are skipped with a warning.
+    pub fn try_open_all(&mut self, paths: Vec<PathBuf>) {
+        let mut runs = Vec::new();
+        let mut warnings = Vec::new();
+        for p in paths.into_iter() {
+            match find_files(p.clone(), self.scan_subfolders) {
+                Ok(r) => {
+                    self.add_recent(p);
+                    runs.push(r.into_iter().next().unwrap());
+                }
+                Err(e) => warnings.push(format!("Error opening '{}': {e}", p.display())),
+            }
+        }
+        let mut runs = runs.into_iter();
+        let Some(files) = runs.next() else {
+            self.warnings.extend(warnings);
+            self.error = Some("None of the paths could be opened".into());
+            return;
+        };
+        let compared = runs.next();
+        for skipped in runs {
+            let dir = skipped.dir.display();
+            warnings.push(format!(
+                "Only two runs can be shown at once, skipped '{dir}'"
+            ));
+        }
+        self.start_loading(files, AfterLoading::Dropped { compared, warnings });
+    }
+
+    /// Like [`Self::try_open`], but skip the selection dialog if all files can be opened
+    pub fn try_open_direct(&mut self, path: PathBuf) {
+        match find_files(path.clone(), self.scan_subfolders) {
+            Ok(runs) if runs.len() == 1 => {
+                self.add_recent(path);
+                let files = runs.into_iter().next().unwrap();
+                self.try_open_files(files, false);
+            }
+            Ok(_) => self.try_open(path),
+            Err(e) => self.error = Some(format!("Error opening '{}': {e}", path.display())),
+        }
+    }
+
+    fn add_recent(&mut self, path: PathBuf) {
+        self.recent.retain(|p| *p != path);
+        self.recent.insert(0, path);
+        self.recent.truncate(MAX_RECENT);
+    }
+
+    pub fn select_run(&mut self, run: usize) {
+        let Some(old) = self.selectable_files.take() else {
+            return;
+        };
+
+        let files = old.runs[run].clone();
+        let after = AfterLoading::Select {
+            root: old.root,
+            runs: old.runs,
+            selected_run: run,
+        };
+        self.start_loading(files, after);
     }
 
     pub fn try_open_files(&mut self, files: Files, always_show_dialog: bool) {
-        let selectable_files = open_files(files);
+        self.start_loading(files, AfterLoading::Show { always_show_dialog });
+    }
+
+    /// Open the files in a background thread, cancelling any previous loading
+    fn start_loading(&mut self, files: Files, after: AfterLoading) {
+        self.cancel_loading();
+        self.loading = Some(Loading::start(files, after));
+    }
+
+    pub fn cancel_loading(&mut self) {
+        if let Some(l) = self.loading.take() {
+            l.cancel();
+        }
+    }
+
+    pub fn update_loading(&mut self, ctx: &Context) {
+        let Some(loading) = &mut self.loading else {
+            return;
+        };
+
+        while let Ok(n) = loading.progress.try_recv() {
+            loading.bytes_read += n;
+        }
+        if !loading.handle.is_finished() {
+            ctx.request_repaint();
+            return;
+        }
 
-        let all_succeeded = selectable_files.with_error.is_empty();
+        let loading = self.loading.take().unwrap();
+        let mut selectable_files = loading
+            .handle
+            .join()
+            .expect("failed to join loading thread");
+        let (mut compared, mut dropped_warnings) = (None, Vec::new());
+        let always_show_dialog = match loading.after {
+            AfterLoading::Select {
+                root,
+                runs,
+                selected_run,
+            } => {
+                selectable_files.root = root;
+                selectable_files.runs = runs;
+                selectable_files.selected_run = selected_run;
+                true
+            }
+            AfterLoading::Show { always_show_dialog } => always_show_dialog,
+            AfterLoading::Append => {
+                self.append_and_show(selectable_files);
+                return;
+            }
+            AfterLoading::Compare => {
+                self.compare_and_show(selectable_files);
+                return;
+            }
+            AfterLoading::Dropped {
+                compared: c,
+                warnings,
+            } => {
+                (compared, dropped_warnings) = (c, warnings);
+                false
+            }
+        };
+
+        // skip files that couldn't be opened, as long as there are others left
+        let usable =
+            selectable_files.with_error.is_empty() || !selectable_files.by_header.is_empty();
         let sanity_check_passed = selectable_files
             .by_header
             .iter()
             .all(|g| g.iter().all(|f| f.sanity_check.is_ok()));
 
-        if all_succeeded && sanity_check_passed && !always_show_dialog {
+        if usable && sanity_check_passed && !always_show_dialog {
+            self.warnings = loading_warnings(&selectable_files);
+            self.warnings.extend(dropped_warnings);
             self.concat_and_show(selectable_files);
+            if let Some(files) = compared.filter(|_| self.data.is_some()) {
+                self.start_loading(files, AfterLoading::Compare);
+            }
         } else {
+            self.warnings.extend(dropped_warnings);
+            if let Some(files) = compared {
+                let dir = files.dir.display();
+                (self.warnings).push(format!("Select the files to show before comparing '{dir}'"));
+            }
             self.selectable_files = Some(selectable_files);
         }
     }
 
     pub fn concat_and_show(&mut self, selectable_files: SelectableFiles) {
-        let mut streams = Vec::with_capacity(selectable_files.by_header.len());
-        let mut files = Vec::new();
-        for group in selectable_files.by_header.into_iter() {
-            let additional = group.iter().skip(1).map(|s| s.stream.len()).sum();
-            let mut group_iter = group.into_iter().filter(|f| f.selected);
-
-            let mut first = match group_iter.next() {
-                Some(f) => f,
-                None => continue,
-            };
-            first.stream.reserve(additional);
-            files.push(first.file);
-
-            for s in group_iter {
-                first.stream.extend(&s.stream);
-                files.push(s.file);
-            }
-
-            streams.push(first.stream);
-        }
-
+        let file_samples = appendable_samples(&selectable_files.by_header);
+        let (mut streams, files) = concat_groups(selectable_files.by_header);
         let files = Files {
             dir: selectable_files.dir,
             items: files,
         };
 
         self.selectable_files = None;
-        if streams.is_empty() {
+        // the samples of stdin would replace the opened run
+        self.stdin = None;
+        check_timestamps(self.timestamp_order, &mut streams, &mut self.warnings);
+        if streams.iter().all(|s| s.time.is_empty()) {
             self.files = None;
             self.data = None;
+            let dir = files.dir.display();
+            self.error = Some(format!("The files in '{dir}' contain no samples"));
         } else {
             let mut lowest_delta = (0, 0);
             for (i, s) in streams.iter().enumerate() {
-                let delta = s.time.windows(2).take(20).map(|w| w[1] - w[0]).sum::<u32>()
-                    / std::cmp::min(20, s.time.len() as u32);
+                let delta = sample_interval(s);
                 if delta < lowest_delta.1 {
                     lowest_delta = (i, delta);
                 }
@@ -174,49 +979,369 @@ impl PlotApp {
 
             streams.swap(0, lowest_delta.0);
 
-            self.files = Some(files);
+            for t in self.config.tabs.iter_mut() {
+                t.restore_bounds = true;
+            }
+            let baseline = self.data.take().and_then(|d| d.baseline);
+            self.config.migrate_stream_settings(&files.dir, &streams);
+            let backwards = backwards_ranges(self.timestamp_order, &streams);
             self.data = Some({
+                let dir = files.dir.clone();
                 let streams = streams.into();
-                let plots = (self.config.tabs.iter())
-                    .map(|t| {
-                        t.plots
-                            .iter()
-                            .map(|p| {
-                                PlotValues::Job(Job::start(p.expr.clone(), Arc::clone(&streams)))
-                            })
-                            .collect()
-                    })
-                    .collect();
-                PlotData { streams, plots }
+                let plots = plot::start_all_jobs(&self.config, &dir, &streams);
+                PlotData {
+                    dir,
+                    streams,
+                    plots,
+                    seams: Vec::new(),
+                    backwards,
+                    file_samples,
+                    comparison: None,
+                    baseline,
+                }
             });
+            self.files = Some(files);
+        }
+    }
+
+    /// Appends the streams to the loaded ones with matching headers, shifted in time so they
+    /// start one sample interval after the loaded ones end. The opened files stay the same, so
+    /// reloading drops the appended streams.
+    fn append_and_show(&mut self, selectable_files: SelectableFiles) {
+        let Some(data) = &mut self.data else {
+            return;
+        };
+
+        self.warnings = loading_warnings(&selectable_files);
+        let (mut appended, _) = concat_groups(selectable_files.by_header);
+        check_timestamps(self.timestamp_order, &mut appended, &mut self.warnings);
+        if appended.is_empty() {
+            let dir = selectable_files.dir.display();
+            self.error = Some(format!("No files found in '{dir}'"));
+            return;
+        }
+
+        let mut streams = data.streams.to_vec();
+        let end = (streams.iter())
+            .filter_map(|s| s.time.last())
+            .max()
+            .map_or(0, |&t| t + sample_interval(&streams[0]));
+        let start = (appended.iter())
+            .filter_map(|s| s.time.first())
+            .min()
+            .copied()
+            .unwrap_or(0);
+        let mut matched = false;
+        for mut a in appended {
+            let Some(s) = streams.iter_mut().find(|s| s.header_matches(&a)) else {
+                let name = a.entries.first().map_or("", |e| e.name.as_str());
+                let msg = format!("Skipped stream '{name}', no loaded stream matches");
+                self.warnings.push(msg);
+                continue;
+            };
+            for t in a.time.iter_mut() {
+                *t = *t - start + end;
+            }
+            s.extend(&a);
+            matched = true;
+        }
+        if !matched {
+            let dir = selectable_files.dir.display();
+            self.error = Some(format!("No stream in '{dir}' matches the loaded ones"));
+            return;
+        }
+
+        data.seams.push(end as f64 / 1000.0);
+        data.backwards = backwards_ranges(self.timestamp_order, &streams);
+        data.streams = streams.into();
+        data.plots = plot::start_all_jobs(&self.config, &data.dir, &data.streams);
+    }
+
+    /// Appends the samples written to the opened files to the streams with matching headers
+    fn append_new_samples(&mut self, appended: Vec<(usize, LogStream)>) {
+        let Some(data) = &mut self.data else {
+            return;
+        };
+        // the samples would follow the appended runs
+        if !data.seams.is_empty() {
+            if let Some(files) = self.files.clone() {
+                self.try_open_files(files, false);
+            }
+            return;
+        }
+
+        let mut streams = data.streams.to_vec();
+        for (i, mut a) in appended {
+            let Some(s) = streams.iter_mut().find(|s| s.header_matches(&a)) else {
+                continue;
+            };
+            check_timestamps(
+                self.timestamp_order,
+                std::slice::from_mut(&mut a),
+                &mut self.warnings,
+            );
+            if let Some(Some(n)) = data.file_samples.get_mut(i) {
+                *n += a.len();
+            }
+            s.extend(&a);
+        }
+        data.backwards = backwards_ranges(self.timestamp_order, &streams);
+        data.streams = streams.into();
+        data.plots = plot::start_all_jobs(&self.config, &data.dir, &data.streams);
+    }
+
+    /// Loads the streams as a comparison, their plots are evaluated along the loaded ones
+    fn compare_and_show(&mut self, selectable_files: SelectableFiles) {
+        let Some(data) = &mut self.data else {
+            return;
+        };
+
+        // the warnings of the shown run still apply
+        self.warnings.extend(loading_warnings(&selectable_files));
+        let (mut streams, _) = concat_groups(selectable_files.by_header);
+        check_timestamps(self.timestamp_order, &mut streams, &mut self.warnings);
+        if streams.is_empty() {
+            let dir = selectable_files.dir.display();
+            self.error = Some(format!("No files found in '{dir}'"));
+            return;
+        }
+
+        data.comparison = Some(Comparison::new(&selectable_files.dir, streams.into()));
+    }
+}
+
+/// Sorts or warns about streams whose timestamps go backwards
+fn check_timestamps(order: TimestampOrder, streams: &mut [LogStream], warnings: &mut Vec<String>) {
+    for s in streams.iter_mut() {
+        let backwards = s.backwards();
+        if backwards == 0 {
+            continue;
+        }
+        match order {
+            TimestampOrder::Warn => {
+                let name = s.entries.first().map_or("", |e| e.name.as_str());
+                warnings.push(format!(
+                    "{backwards} timestamps of stream '{name}' go backwards, see the sidebar"
+                ));
+            }
+            TimestampOrder::Sort => s.sort_by_time(),
+            TimestampOrder::Keep => (),
+        }
+    }
+}
+
+/// Ranges where the timestamps of the streams go backwards, which are marked when warning about
+/// them
+fn backwards_ranges(order: TimestampOrder, streams: &[LogStream]) -> Vec<(usize, u32, u32)> {
+    if order != TimestampOrder::Warn {
+        return Vec::new();
+    }
+    (streams.iter().enumerate())
+        .flat_map(|(i, s)| {
+            s.backwards_ranges()
+                .into_iter()
+                .map(move |(a, b)| (i, a, b))
+        })
+        .collect()
+}
+
+pub fn loading_warnings(selectable_files: &SelectableFiles) -> Vec<String> {
+    let skipped = (selectable_files.with_error.iter())
+        .map(|e| format!("Skipped '{}': {}", e.file.display(), e.error));
+    let warnings = (selectable_files.by_header.iter().flatten()).flat_map(|f| {
+        f.warnings
+            .iter()
+            .map(|w| format!("'{}': {w}", f.file.display()))
+    });
+    skipped.chain(warnings).collect()
+}
+
+/// Concatenates the selected files of each group
+pub fn concat_groups(by_header: Vec<Vec<SelectableFile>>) -> (Vec<LogStream>, Vec<PathBuf>) {
+    let mut streams = Vec::with_capacity(by_header.len());
+    let mut files = Vec::new();
+    for group in by_header.into_iter() {
+        let additional = group.iter().skip(1).map(|s| s.stream.len()).sum();
+        let mut group_iter = group.into_iter().filter(|f| f.selected);
+
+        let mut first = match group_iter.next() {
+            Some(f) => f,
+            None => continue,
+        };
+        first.stream.reserve(additional);
+        files.push(first.file);
+
+        for s in group_iter {
+            first.stream.extend(&s.stream);
+            files.push(s.file);
+        }
+
+        streams.push(first.stream);
+    }
+    (streams, files)
+}
+
+/// Samples of the selected files in the order of [`concat_groups`]. New samples can only be
+/// appended to the last file of each group that isn't compressed or CSV.
+fn appendable_samples(by_header: &[Vec<SelectableFile>]) -> Vec<Option<usize>> {
+    let mut samples = Vec::new();
+    for group in by_header.iter() {
+        let selected: Vec<_> = group.iter().filter(|f| f.selected).collect();
+        for (i, f) in selected.iter().enumerate() {
+            let plain = f.file.extension().map_or(false, |e| e == "s3lg");
+            let last = i + 1 == selected.len();
+            samples.push((plain && last).then_some(f.stream.len()));
         }
     }
+    samples
 }
 
-fn find_files(dir: PathBuf) -> Result<Files, data::Error> {
+/// Average interval in ms between the first samples
+fn sample_interval(stream: &LogStream) -> u32 {
+    let n = std::cmp::min(20, stream.time.len() as u32);
+    if n == 0 {
+        return 0;
+    }
+    stream
+        .time
+        .windows(2)
+        .take(20)
+        .map(|w| w[1].saturating_sub(w[0]))
+        .sum::<u32>()
+        / n
+}
+
+/// Returns the files of each run, grouped by their parent directory. The first run is always
+/// the one inside `path` itself, even if it is empty.
+fn find_files(path: PathBuf, recursive: bool) -> Result<Vec<Files>, data::Error> {
+    if path.is_file() {
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        return Ok(vec![Files {
+            dir,
+            items: vec![path],
+        }]);
+    }
+
+    let mut runs = vec![Files {
+        dir: path.clone(),
+        items: Vec::new(),
+    }];
+    let mut visited = HashSet::new();
+    find_files_in_dir(path, recursive, &mut visited, &mut runs)?;
+
+    if runs.len() > 1 && runs[0].items.is_empty() {
+        runs.remove(0);
+    }
+    if runs[0].items.is_empty() {
+        return Err(data::Error::NoFiles(runs.remove(0).dir));
+    }
+
+    Ok(runs)
+}
+
+fn find_files_in_dir(
+    dir: PathBuf,
+    recursive: bool,
+    visited: &mut HashSet<PathBuf>,
+    runs: &mut Vec<Files>,
+) -> Result<(), data::Error> {
+    // guard against symlink loops
+    if !visited.insert(dir.canonicalize()?) {
+        return Ok(());
+    }
+
     let mut items = Vec::new();
+    let mut sub_dirs = Vec::new();
     for entry in std::fs::read_dir(&dir)? {
         let entry = entry?;
         let path = entry.path();
+        if path.is_dir() {
+            if recursive {
+                sub_dirs.push(path);
+            }
+            continue;
+        }
         if !path.is_file() {
             continue;
         }
 
-        if path.extension().map_or(false, |e| e == "s3lg") {
+        if is_log_file(&path) {
             items.push(path);
         }
     }
 
-    items.sort();
+    items.sort_by(|a, b| util::natural_path_cmp(a, b));
+    sub_dirs.sort_by(|a, b| util::natural_path_cmp(a, b));
+    check_duplicates(&items)?;
+
+    if runs[0].dir == dir {
+        runs[0].items = items;
+    } else if !items.is_empty() {
+        runs.push(Files { dir, items });
+    }
+
+    for d in sub_dirs {
+        find_files_in_dir(d, recursive, visited, runs)?;
+    }
+
+    Ok(())
+}
+
+/// Returns an error listing all files that share a stem with another one, otherwise they
+/// would silently be loaded twice
+fn check_duplicates(items: &[PathBuf]) -> Result<(), data::Error> {
+    let mut stems: HashMap<String, Vec<&PathBuf>> = HashMap::new();
+    for p in items {
+        stems.entry(log_file_stem(p)).or_default().push(p);
+    }
+    let mut duplicates: Vec<PathBuf> = (stems.into_values())
+        .filter(|paths| paths.len() > 1)
+        .flatten()
+        .cloned()
+        .collect();
+    if duplicates.is_empty() {
+        return Ok(());
+    }
+    duplicates.sort_by(|a, b| util::natural_path_cmp(a, b));
+    Err(data::Error::DuplicateFiles(duplicates))
+}
 
-    Ok(Files { dir, items })
+/// The file name without the log file extension
+fn log_file_stem(path: &Path) -> String {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    let stem = [".s3lg.gz", ".s3lg", ".csv"]
+        .iter()
+        .find_map(|ext| name.strip_suffix(ext))
+        .unwrap_or(&name);
+    stem.to_string()
 }
 
-fn open_files(files: Files) -> SelectableFiles {
+/// Either a plain `.s3lg` file, a gzip compressed `.s3lg.gz` file or a `.csv` file
+fn is_log_file(path: &Path) -> bool {
+    let name = path.file_name().map(|n| n.to_string_lossy());
+    name.map_or(false, |n| {
+        n.ends_with(".s3lg") || n.ends_with(".s3lg.gz") || n.ends_with(".csv")
+    })
+}
+
+fn is_compressed(path: &Path) -> bool {
+    path.extension().map_or(false, |e| e == "gz")
+}
+
+/// Reads the files in parallel, the groups keep the order of `files`
+pub fn open_files(files: Files, progress: &Sender<u64>, cancel: &AtomicBool) -> SelectableFiles {
+    let opened_files: Vec<_> = (files.items.par_iter())
+        .map_with(progress.clone(), |progress, f| {
+            (!cancel.load(Ordering::Relaxed)).then(|| open_file(f, progress, cancel))
+        })
+        .collect();
+
     let mut by_header: Vec<Vec<SelectableFile>> = Vec::new();
     let mut with_error = Vec::new();
-    'outer: for f in files.items.iter() {
-        let opened_file = open_file(f);
+    'outer: for opened_file in opened_files.into_iter().flatten() {
         match opened_file {
             Ok(selectable_file) => {
                 for group in by_header.iter_mut() {
@@ -232,30 +1357,380 @@ fn open_files(files: Files) -> SelectableFiles {
     }
 
     SelectableFiles {
+        root: files.dir.clone(),
+        runs: Vec::new(),
+        selected_run: 0,
         dir: files.dir,
         by_header,
         with_error,
     }
 }
 
-fn open_file(path: &Path) -> Result<SelectableFile, ErrorFile> {
+fn open_file(
+    path: &Path,
+    progress: &Sender<u64>,
+    cancel: &AtomicBool,
+) -> Result<SelectableFile, ErrorFile> {
     let result = File::open(path).map_err(From::from).and_then(|f| {
-        let mut reader = BufReader::new(f);
-        data::read_file(&mut reader)
+        let mut reader = BufReader::new(ProgressReader {
+            inner: f,
+            progress,
+            cancel,
+        });
+        if path.extension().map_or(false, |e| e == "csv") {
+            data::read_csv(reader)
+        } else if is_compressed(path) {
+            // the decompressed length isn't known up front
+            data::read_stream(&mut GzDecoder::new(reader), None)
+        } else {
+            data::read_file(&mut reader)
+        }
     });
 
     result
-        .map(|stream| {
+        .map(|(stream, warnings)| {
             let sanity_check = data::sanity_check(&stream.entries);
             SelectableFile {
                 selected: sanity_check.is_ok(),
                 file: path.to_path_buf(),
                 stream,
                 sanity_check,
+                warnings,
             }
         })
         .map_err(|error| ErrorFile {
-            file: path.to_path_buf(),
+            file: path.canonicalize().unwrap_or_else(|_| path.to_path_buf()),
+            version: file_version(path),
             error,
         })
 }
+
+fn file_version(path: &Path) -> Option<Version> {
+    if path.extension().map_or(false, |e| e == "csv") {
+        return None;
+    }
+    let mut reader = BufReader::new(File::open(path).ok()?);
+    let version = if is_compressed(path) {
+        data::read_version(&mut GzDecoder::new(reader))
+    } else {
+        data::read_version(&mut reader)
+    };
+    version.ok()
+}
+
+/// Loads every run in `root` and its subdirectories and writes it to a csv file in `out_dir`,
+/// named after its path relative to `root`. The number of exported runs and of all runs is sent
+/// to `progress` after each run. Returns the written file or the error of each run, without the
+/// runs left when it's cancelled.
+pub fn batch_export_csv(
+    root: &Path,
+    out_dir: &Path,
+    markers: &HashMap<PathBuf, Vec<Marker>>,
+    progress: &Sender<(usize, usize)>,
+    cancel: &AtomicBool,
+) -> Result<Vec<(PathBuf, Result<PathBuf, String>)>, data::Error> {
+    let runs = find_files(root.to_path_buf(), true)?;
+    std::fs::create_dir_all(out_dir)?;
+
+    // the bytes read aren't shown, only the number of runs
+    let (bytes_read, _) = mpsc::channel();
+    let total = runs.len();
+    let mut used_names = HashSet::new();
+    let mut results = Vec::with_capacity(total);
+    for files in runs.into_iter() {
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+
+        let dir = files.dir.clone();
+        let name = match dir.strip_prefix(root) {
+            Ok(p) if p.as_os_str().is_empty() => root.file_name().map(PathBuf::from),
+            Ok(p) => Some(p.to_path_buf()),
+            Err(_) => dir.file_name().map(PathBuf::from),
+        };
+        let name: String = (name
+            .unwrap_or_else(|| "run".into())
+            .to_string_lossy()
+            .chars())
+        .map(|c| if std::path::is_separator(c) { '_' } else { c })
+        .collect();
+        let name = unique_name(name, &mut used_names);
+        let path = out_dir.join(format!("{name}.csv"));
+
+        let selectable_files = open_files(files, &bytes_read, cancel);
+        let (streams, _) = concat_groups(selectable_files.by_header);
+        let result = if streams.is_empty() {
+            Err("no usable files".to_string())
+        } else {
+            let run_markers = markers.get(&dir).map_or(&[][..], |m| m.as_slice());
+            (export_csv(&streams, run_markers, &path).map(|_| path)).map_err(|e| e.to_string())
+        };
+        results.push((dir, result));
+        progress.send((results.len(), total)).ok();
+    }
+
+    Ok(results)
+}
+
+/// Appends a number to names that are already used, e.g. by the runs `a/b` and `a_b`. Names that
+/// only differ in case are the same file on some file systems.
+fn unique_name(name: String, used: &mut HashSet<String>) -> String {
+    let mut unique = name.clone();
+    let mut n = 2;
+    while !used.insert(unique.to_lowercase()) {
+        unique = format!("{name} ({n})");
+        n += 1;
+    }
+    unique
+}
+
+/// Writes one row per time sample of all streams. Streams are aligned on their timestamps, cells
+/// of streams without a sample at that time are left blank. If there are markers, their labels
+/// are written to an additional column in the row at or after their timestamp. Markers after the
+/// last sample get rows of their own.
+pub fn export_csv(
+    streams: &[LogStream],
+    markers: &[Marker],
+    path: &Path,
+) -> Result<(), data::Error> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    write!(writer, "time")?;
+    for e in streams.iter().flat_map(|s| s.entries.iter()) {
+        write!(writer, ",{}", e.name)?;
+    }
+    let has_markers = !markers.is_empty();
+    if has_markers {
+        write!(writer, ",marker")?;
+    }
+    writeln!(writer)?;
+
+    // markers can be moved out of order in the editor
+    let mut markers: Vec<&Marker> = markers.iter().collect();
+    markers.sort_by(|a, b| a.time.total_cmp(&b.time));
+    let mut markers = markers.into_iter().peekable();
+
+    let mut indices = vec![0; streams.len()];
+    loop {
+        let next_time = (streams.iter().zip(indices.iter()))
+            .filter_map(|(s, &i)| s.time.get(i))
+            .min();
+        let Some(&time) = next_time else {
+            break;
+        };
+
+        write!(writer, "{}", time as f64 / 1000.0)?;
+        for (s, i) in streams.iter().zip(indices.iter_mut()) {
+            if s.time.get(*i) == Some(&time) {
+                for e in s.entries.iter() {
+                    write!(writer, ",{}", e.kind.get_f64(*i))?;
+                }
+                *i += 1;
+            } else {
+                for _ in s.entries.iter() {
+                    write!(writer, ",")?;
+                }
+            }
+        }
+        if has_markers {
+            let labels: Vec<&str> = std::iter::from_fn(|| {
+                markers
+                    .next_if(|m| m.time <= time as f64 / 1000.0)
+                    .map(|m| m.label.as_str())
+            })
+            .collect();
+            write!(writer, ",{}", csv_field(&labels.join("; ")))?;
+        }
+        writeln!(writer)?;
+    }
+
+    let num_entries: usize = streams.iter().map(|s| s.entries.len()).sum();
+    while let Some(m) = markers.next() {
+        let time = m.time;
+        let mut labels = vec![m.label.as_str()];
+        labels.extend(std::iter::from_fn(|| {
+            markers
+                .next_if(|m| m.time == time)
+                .map(|m| m.label.as_str())
+        }));
+        write!(writer, "{time}")?;
+        for _ in 0..num_entries {
+            write!(writer, ",")?;
+        }
+        writeln!(writer, ",{}", csv_field(&labels.join("; ")))?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Quotes a field if it contains a separator, a quote or a line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{DataEntry, EntryKind};
+
+    use super::*;
+
+    /// An empty directory that is unique to the test and this process
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("s3plot-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// A v1 log with a `u32` and a `f32` entry
+    fn write_log(path: &Path, samples: u32) {
+        let mut buf = b"s3lg".to_vec();
+        buf.extend(1u16.to_be_bytes());
+        buf.extend(2u16.to_be_bytes());
+        for (code, name) in [(3, "a"), (9, "b")] {
+            buf.extend([code, name.len() as u8]);
+            buf.extend(name.as_bytes());
+        }
+        for i in 0..samples {
+            buf.extend((i * 20).to_be_bytes());
+            buf.extend(i.to_be_bytes());
+            buf.extend((i as f32).sin().to_be_bytes());
+        }
+        std::fs::write(path, buf).unwrap();
+    }
+
+    /// Prints the time of reading a large folder in parallel and one file after another, run
+    /// with `cargo test --release -- --ignored --nocapture open_files_speedup`
+    #[test]
+    #[ignore]
+    fn open_files_speedup() {
+        const FILES: usize = 64;
+        const SAMPLES: u32 = 500_000;
+
+        let dir = temp_dir("speedup");
+        let items: Vec<_> = (0..FILES).map(|i| dir.join(format!("{i}.s3lg"))).collect();
+        for path in items.iter() {
+            write_log(path, SAMPLES);
+        }
+        let files = Files {
+            dir: dir.clone(),
+            items,
+        };
+        let (progress, _) = mpsc::channel();
+        let cancel = AtomicBool::new(false);
+
+        let start = Instant::now();
+        for path in files.items.iter() {
+            open_file(path, &progress, &cancel).unwrap();
+        }
+        let sequential = start.elapsed();
+
+        let start = Instant::now();
+        let opened = open_files(files, &progress, &cancel);
+        let parallel = start.elapsed();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(opened.with_error.is_empty());
+        assert_eq!(opened.by_header.len(), 1);
+        let order: Vec<_> = (opened.by_header[0].iter())
+            .map(|f| f.file.file_name().unwrap().to_str().unwrap().to_owned())
+            .collect();
+        let expected: Vec<_> = (0..FILES).map(|i| format!("{i}.s3lg")).collect();
+        assert_eq!(order, expected);
+
+        let speedup = sequential.as_secs_f64() / parallel.as_secs_f64();
+        println!(
+            "{FILES} files of {SAMPLES} samples: {sequential:?} one after another, {parallel:?} in parallel, {speedup:.1}x faster on {} threads",
+            rayon::current_num_threads()
+        );
+    }
+
+    fn touch(dir: &Path, names: &[&str]) {
+        for name in names {
+            std::fs::write(dir.join(name), []).unwrap();
+        }
+    }
+
+    #[test]
+    fn find_files_reports_duplicates() {
+        let dir = temp_dir("duplicates");
+        touch(&dir, &["0.s3lg", "0.s3lg.gz", "1.s3lg", "2.s3lg", "2.csv"]);
+
+        let result = find_files(dir.clone(), false);
+        std::fs::remove_dir_all(&dir).unwrap();
+        match result {
+            Err(data::Error::DuplicateFiles(paths)) => {
+                let expected = ["0.s3lg", "0.s3lg.gz", "2.csv", "2.s3lg"].map(|n| dir.join(n));
+                assert_eq!(paths, expected);
+            }
+            Err(e) => panic!("unexpected error: {e}"),
+            Ok(_) => panic!("duplicates weren't detected"),
+        }
+    }
+
+    #[test]
+    fn find_files_accepts_distinct_files() {
+        let dir = temp_dir("distinct");
+        touch(
+            &dir,
+            &["10.s3lg", "2.csv", "1.s3lg.gz", "0.s3lg", "notes.txt"],
+        );
+        // the same name in another run isn't a duplicate
+        std::fs::create_dir(dir.join("run")).unwrap();
+        touch(&dir.join("run"), &["0.s3lg"]);
+
+        let result = find_files(dir.clone(), true);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let runs = match result {
+            Ok(runs) => runs,
+            Err(e) => panic!("unexpected error: {e}"),
+        };
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].dir, dir);
+        let expected = ["0.s3lg", "1.s3lg.gz", "2.csv", "10.s3lg"].map(|n| dir.join(n));
+        assert_eq!(runs[0].items, expected);
+        assert_eq!(runs[1].dir, dir.join("run"));
+        assert_eq!(runs[1].items, [dir.join("run").join("0.s3lg")]);
+    }
+
+    #[test]
+    fn export_csv_keeps_markers_after_the_last_sample() {
+        let dir = temp_dir("markers");
+        let stream = LogStream {
+            version: Version::V1,
+            start: None,
+            time: vec![0, 1000],
+            entries: vec![DataEntry {
+                name: "a".into(),
+                kind: EntryKind::U8(vec![1, 2]),
+            }],
+        };
+        let marker = |time, label: &str| Marker {
+            time,
+            label: label.into(),
+        };
+        let markers = [marker(5.0, "end"), marker(0.5, "a, b"), marker(5.0, "stop")];
+        let path = dir.join("run.csv");
+
+        let result = export_csv(&[stream], &markers, &path);
+        let csv = std::fs::read_to_string(&path);
+        std::fs::remove_dir_all(&dir).unwrap();
+        result.unwrap();
+        assert_eq!(
+            csv.unwrap(),
+            "time,a,marker\n0,1,\n1,2,\"a, b\"\n5,,end; stop\n"
+        );
+    }
+
+    #[test]
+    fn unique_name_numbers_collisions() {
+        let mut used = HashSet::new();
+        let names = ["a_b", "a_b", "A_B", "c"].map(|n| unique_name(n.into(), &mut used));
+        assert_eq!(names, ["a_b", "a_b (2)", "A_B (3)", "c"]);
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::plot::NamedPlot;
+
+const MAX_DEPTH: usize = 100;
+/// Changes following each other within this interval are undone at once, e.g. typing
+const COALESCE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone)]
+pub struct Snapshot {
+    pub tab_id: u64,
+    pub plots: Vec<NamedPlot>,
+}
+
+/// Undo and redo stacks of the plots of each tab
+#[derive(Default)]
+pub struct History {
+    undo: VecDeque<Snapshot>,
+    redo: Vec<Snapshot>,
+    current: Option<Snapshot>,
+    last_change: Option<Instant>,
+}
+
+impl History {
+    /// Records the previous state if the plots changed since the last call
+    pub fn track(&mut self, tab_id: u64, plots: &[NamedPlot]) {
+        let Some(current) = self.current.as_ref().filter(|c| c.tab_id == tab_id) else {
+            self.reset(tab_id, plots);
+            return;
+        };
+        if current.plots == plots {
+            return;
+        }
+
+        let now = Instant::now();
+        let coalesce = current.plots.len() == plots.len()
+            && self
+                .last_change
+                .is_some_and(|t| now - t < COALESCE_INTERVAL);
+        if !coalesce {
+            let previous = current.clone();
+            self.push_undo(previous);
+            self.redo.clear();
+        }
+        self.reset(tab_id, plots);
+        self.last_change = Some(now);
+    }
+
+    /// Sets the current state without recording a change
+    pub fn reset(&mut self, tab_id: u64, plots: &[NamedPlot]) {
+        self.current = Some(Snapshot {
+            tab_id,
+            plots: plots.to_vec(),
+        });
+        self.last_change = None;
+    }
+
+    pub fn pop_undo(&mut self) -> Option<Snapshot> {
+        self.undo.pop_back()
+    }
+
+    pub fn pop_redo(&mut self) -> Option<Snapshot> {
+        self.redo.pop()
+    }
+
+    pub fn push_undo(&mut self, snapshot: Snapshot) {
+        self.undo.push_back(snapshot);
+        if self.undo.len() > MAX_DEPTH {
+            self.undo.pop_front();
+        }
+    }
+
+    pub fn push_redo(&mut self, snapshot: Snapshot) {
+        self.redo.push(snapshot);
+    }
+}
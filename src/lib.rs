@@ -0,0 +1,4 @@
+//! Decoding of s3lg log files and their csv exports, so other tools can read the telemetry
+//! without the GUI. See [`data::read_file`] and [`data::LogStream::iter`].
+
+pub mod data;
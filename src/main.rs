@@ -1,26 +1,90 @@
 #![windows_subsystem = "windows"]
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc;
+
 use app::PlotApp;
 
 use eframe::NativeOptions;
+use egui::ViewportBuilder;
+use s3plot::data;
 
+mod analysis;
 mod app;
-mod data;
 mod eval;
+mod export;
 mod fs;
+mod history;
 mod plot;
+mod render;
 mod util;
+mod workspace;
 
 const APP_NAME: &str = "s3plot";
+const DEFAULT_WINDOW_SIZE: [f32; 2] = [1280.0, 800.0];
 
 fn main() {
+    let mut args = std::env::args_os().skip(1);
+    let path = args.next().map(PathBuf::from);
+    if path.as_deref() == Some(Path::new("--render")) {
+        let (Some(workspace), Some(out_dir)) = (args.next(), args.next()) else {
+            eprintln!("usage: {APP_NAME} --render <workspace> <out dir>");
+            std::process::exit(2);
+        };
+        match render::render_workspace(Path::new(&workspace), Path::new(&out_dir)) {
+            Ok(warnings) => warnings.iter().for_each(|w| eprintln!("{w}")),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if path.as_deref() == Some(Path::new("--export-csv")) {
+        let (Some(root), Some(out_dir)) = (args.next(), args.next()) else {
+            eprintln!("usage: {APP_NAME} --export-csv <dir> <out dir>");
+            std::process::exit(2);
+        };
+        let markers = Default::default();
+        // nobody is waiting for the progress
+        let (progress, _) = mpsc::channel();
+        let cancel = AtomicBool::new(false);
+        let (root, out_dir) = (Path::new(&root), Path::new(&out_dir));
+        match fs::batch_export_csv(root, out_dir, &markers, &progress, &cancel) {
+            Ok(results) => {
+                let mut failed = false;
+                for (dir, r) in results {
+                    match r {
+                        Ok(path) => println!("{}: {}", dir.display(), path.display()),
+                        Err(e) => {
+                            eprintln!("{}: {e}", dir.display());
+                            failed = true;
+                        }
+                    }
+                }
+                if failed {
+                    std::process::exit(1);
+                }
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     let options = NativeOptions {
         follow_system_theme: true,
+        // the window is persisted by default, eframe restores it clamped to the monitors over this
+        // viewport, so the size only applies on the first start
+        viewport: ViewportBuilder::default().with_inner_size(DEFAULT_WINDOW_SIZE),
         ..Default::default()
     };
     let res = eframe::run_native(
         APP_NAME,
         options,
-        Box::new(|c| Ok(Box::new(PlotApp::new(c)))),
+        Box::new(|c| Ok(Box::new(PlotApp::new(c, path)))),
     );
     if let Err(err) = res {
         println!("{err}");
@@ -1,21 +1,33 @@
-use std::fmt::Write;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
 use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+use chrono::Local;
 use cods::{BuiltinConst, BuiltinFun, DataType, Pos, SignatureKind, UserFacing};
+use egui::ecolor::Hsva;
 use egui::emath::TSTransform;
-use egui::text::{LayoutJob, LayoutSection};
+use egui::text::{CCursor, CCursorRange, LayoutJob, LayoutSection};
 use egui::{
-    Align, Button, CentralPanel, CollapsingHeader, Color32, CursorIcon, Frame, Id, Key, Label,
-    LayerId, Layout, Margin, Modifiers, Order, Pos2, RichText, Rounding, ScrollArea, Sense,
-    SidePanel, TextEdit, TextFormat, TextStyle, Ui, Vec2, WidgetText,
+    Align, Align2, Area, Button, CentralPanel, Checkbox, CollapsingHeader, Color32, ComboBox,
+    CursorIcon, DragValue, FontId, Frame, Grid, Id, Key, Label, LayerId, Layout, Margin, Modifiers,
+    Order, Pos2, Rect, Response, RichText, Rounding, ScrollArea, Sense, SidePanel, Slider,
+    TextEdit, TextFormat, TextStyle, TopBottomPanel, Ui, Vec2, WidgetText,
+};
+use egui_extras::{Column, TableBuilder};
+use egui_plot::{
+    AxisHints, Bar, BarChart, Corner, GridInput, GridMark, HPlacement, Legend, Line, Plot,
+    PlotBounds, PlotMemory, PlotPoint, PlotPoints, PlotUi, Points, Text, VLine,
 };
-use egui_plot::{Legend, Line, Plot, PlotPoint, PlotPoints};
 use serde::{Deserialize, Serialize};
 
-use crate::app::{Job, PlotData, PlotValues};
-use crate::eval::Expr;
-use crate::util::{self, format_time};
+use crate::analysis::{self, Processing, SpectrumConfig, Unit, WindowFn, DEFAULT_SAMPLE_RATE};
+use crate::app::{Comparison, Job, JobInputs, PlotData, PlotValues};
+use crate::data::LogStream;
+use crate::eval::{self, Constant, Expr, Globals, SeriesFun};
+use crate::history::{History, Snapshot};
+use crate::util::{self, format_time, NumberFormat, TimeFormat};
 
 const TAB_CROSS_WIDTH: f32 = 20.0;
 const TAB_BUTTON_WIDTH: f32 = 80.0;
@@ -27,6 +39,47 @@ const TEXT_EDIT_MARGIN_X: f32 = 4.0;
 const TEXT_EDIT_MARGIN_Y: f32 = 2.0;
 
 const DEFAULT_ASPECT_RATIO: f32 = 0.1;
+const MIN_SAMPLE_RATE: f64 = 0.001;
+const DEFAULT_SMOOTHING_WINDOW: usize = 25;
+const DEFAULT_HISTOGRAM_BINS: usize = 50;
+/// Excludes sentinel values that sensors report on faults, like -273 °C or `i16::MAX`
+const DEFAULT_VALID_RANGE: (f64, f64) = (-250.0, 10_000.0);
+const SCATTER_RADIUS: f32 = 1.5;
+const DEFAULT_MARKER_SIZE: f32 = 2.5;
+const MAX_MARKER_SIZE: f32 = 10.0;
+/// Visible samples of a plot up to which every one of them gets a marker, more are decimated
+const MAX_SAMPLE_MARKERS: usize = 20_000;
+/// Number of shades when coloring scatter points by time
+const TIME_COLOR_STEPS: usize = 8;
+const MAX_COMPLETIONS: usize = 10;
+const MAX_LISTED_CROSSINGS: usize = 50;
+const MAX_TIME_OFFSET: i32 = 5000;
+const DEFAULT_DECIMATION_THRESHOLD: usize = 10_000;
+pub const DEFAULT_MAX_POINTS: usize = 2000;
+const LINKED_X_GROUP: &str = "linked_x";
+const OVERVIEW_HEIGHT: f32 = 50.0;
+const VALUES_TABLE_HEIGHT: f32 = 200.0;
+const VALUES_ROW_HEIGHT: f32 = 18.0;
+const VALUES_COLUMN_WIDTH: f32 = 120.0;
+pub const DEFAULT_GAP_THRESHOLD: f64 = 1.0;
+const DEFAULT_LINE_WIDTH: f32 = 1.5;
+pub const MAX_LINE_WIDTH: f32 = 10.0;
+const DEFAULT_TICKS: usize = 10;
+const MAX_TICKS: usize = 100;
+/// Minor grid lines per major step
+const MINOR_STEPS: f64 = 5.0;
+const DEFAULT_LEGEND_TEXT_SIZE: f32 = 12.5;
+/// Name of the text style of legends with a custom text size
+const LEGEND_TEXT_STYLE: &str = "legend";
+const MAX_LEGEND_TEXT_SIZE: f32 = 32.0;
+const DASH_LENGTH: f32 = 10.0;
+const DOT_SPACING: f32 = 5.0;
+/// Placeholder values of a vehicle, which the user is expected to adjust
+const VEHICLE_CONSTANTS: [(&str, f64); 3] = [
+    ("wheel_radius", 0.25),
+    ("track_width", 1.2),
+    ("mass", 250.0),
+];
 const ERROR_RED: Color32 = Color32::from_rgb(0xf0, 0x56, 0x56);
 
 #[derive(Serialize, Deserialize)]
@@ -36,10 +89,107 @@ pub struct Config {
     pub search_help: String,
     pub selected_tab: usize,
     pub tabs: Vec<TabConfig>,
+    #[serde(default)]
+    pub constants: Vec<Constant>,
+    /// Time offset in ms of streams, keyed by the directory of their run and their index. The
+    /// directory is absolute, so the offsets stay behind when a run is moved.
+    #[serde(default)]
+    pub run_time_offsets: HashMap<PathBuf, HashMap<usize, i32>>,
+    /// Offsets saved by the name of the first entry of a stream, moved to `run_time_offsets` once
+    /// a run with such a stream is loaded
+    #[serde(
+        default,
+        rename = "time_offsets",
+        skip_serializing_if = "HashMap::is_empty"
+    )]
+    pub legacy_time_offsets: HashMap<String, i32>,
+    /// Number of visible points above which plots are decimated
+    #[serde(default = "default_decimation_threshold")]
+    pub decimation_threshold: usize,
+    /// Points drawn per plot at most, below the number of pixels, for a higher frame rate
+    #[serde(default)]
+    pub max_points: Option<usize>,
+    /// Smooth the edges of lines, costly with many points
+    #[serde(default = "default_antialiasing")]
+    pub antialiasing: bool,
+    /// Show the frame rate in a corner, to tune the performance settings
+    #[serde(default)]
+    pub show_fps: bool,
+    /// Share the x-range between the tabs in time mode
+    #[serde(default)]
+    pub link_x: bool,
+    /// Line width of series that don't override it
+    #[serde(default = "default_line_width")]
+    pub line_width: f32,
+    /// Line style of series that don't override it
+    #[serde(default)]
+    pub line_style: LineStyle,
+    /// Markers of each run, keyed by its directory
+    #[serde(default)]
+    pub markers: HashMap<PathBuf, Vec<Marker>>,
+    /// Free text per run, keyed by its directory like the markers
+    #[serde(default)]
+    pub notes: HashMap<PathBuf, String>,
+    /// Mark where appended runs start
+    #[serde(default)]
+    pub show_seams: bool,
+    /// Break time plots where there are no samples for longer than this many seconds
+    #[serde(default)]
+    pub gap_threshold: Option<f64>,
+    #[serde(default)]
+    pub shade_gaps: bool,
+    /// Shift a compared run so it starts at the same time as the loaded one
+    #[serde(default)]
+    pub align_comparison: bool,
+    /// Show the whole timeline below continuous plots
+    #[serde(default = "default_show_overview")]
+    pub show_overview: bool,
+    /// Show the position of the pointer in the corner of the plot
+    #[serde(default = "default_show_coordinates")]
+    pub show_coordinates: bool,
+    /// Format of numbers in readouts, statistics and axis labels
+    #[serde(default)]
+    pub number_format: NumberFormat,
+    /// Grid of tabs that don't override it
+    #[serde(default)]
+    pub grid: GridConfig,
+    #[serde(default)]
+    pub palette: Palette,
+    /// Tabs shown at once, the page containing the selected tab is shown
+    #[serde(default)]
+    pub layout: PlotLayout,
+    /// Show the time of day instead of the time since the start of the run
+    #[serde(default)]
+    pub wall_clock: bool,
+    /// Start of each run as unix timestamp in seconds, overrides the one in the files
+    #[serde(default)]
+    pub start_times: HashMap<PathBuf, i64>,
+    /// Nominal sample rate in Hz of streams, overrides the detected one. Keyed by the directory
+    /// of their run and their index, like the time offsets.
+    #[serde(default)]
+    pub run_sample_rates: HashMap<PathBuf, HashMap<usize, f64>>,
+    /// Rates saved by the name of the first entry of a stream, moved like the time offsets
+    #[serde(
+        default,
+        rename = "sample_rates",
+        skip_serializing_if = "HashMap::is_empty"
+    )]
+    pub legacy_sample_rates: HashMap<String, f64>,
+    /// Hide the markers, e.g. while exporting without them
+    #[serde(skip)]
+    pub hide_markers: bool,
     #[serde(skip)]
     pub dragged_tab: Option<(usize, Pos2)>,
     #[serde(skip)]
     pub dragged_plot: Option<(usize, Pos2)>,
+    /// Screen area of the plot in the selected tab
+    #[serde(skip)]
+    pub plot_rect: Option<Rect>,
+    /// Time under the pointer in one of the visible time plots
+    #[serde(skip)]
+    pub cursor: Option<f64>,
+    #[serde(skip)]
+    pub history: History,
 }
 
 impl Default for Config {
@@ -52,34 +202,205 @@ impl Default for Config {
                 "Tab 1".into(),
                 DEFAULT_ASPECT_RATIO,
                 vec![
-                    NamedPlot {
-                        name: "1.".into(),
-                        expr: Expr {
-                            x: "time".into(),
-                            y: "sin(time / PI) * 10.0".into(),
-                        },
-                    },
-                    NamedPlot {
-                        name: "2.".into(),
-                        expr: Expr {
-                            x: "time".into(),
-                            y: "cos(time / PI - PI) * 10.0".into(),
-                        },
-                    },
+                    NamedPlot::new("1.".into(), Expr::new("time", "sin(time / PI) * 10.0")),
+                    NamedPlot::new("2.".into(), Expr::new("time", "cos(time / PI - PI) * 10.0")),
                 ],
             )],
+            constants: Vec::new(),
+            run_time_offsets: HashMap::new(),
+            legacy_time_offsets: HashMap::new(),
+            decimation_threshold: DEFAULT_DECIMATION_THRESHOLD,
+            max_points: None,
+            antialiasing: true,
+            show_fps: false,
+            link_x: false,
+            line_width: DEFAULT_LINE_WIDTH,
+            line_style: LineStyle::default(),
+            markers: HashMap::new(),
+            notes: HashMap::new(),
+            show_seams: false,
+            show_overview: true,
+            show_coordinates: true,
+            number_format: NumberFormat::default(),
+            grid: GridConfig::default(),
+            palette: Palette::default(),
+            layout: PlotLayout::default(),
+            wall_clock: false,
+            start_times: HashMap::new(),
+            run_sample_rates: HashMap::new(),
+            legacy_sample_rates: HashMap::new(),
+            align_comparison: false,
+            gap_threshold: None,
+            shade_gaps: false,
+            hide_markers: false,
             dragged_tab: None,
             dragged_plot: None,
+            plot_rect: None,
+            cursor: None,
+            history: History::default(),
+        }
+    }
+}
+
+fn default_decimation_threshold() -> usize {
+    DEFAULT_DECIMATION_THRESHOLD
+}
+
+fn default_antialiasing() -> bool {
+    true
+}
+
+fn default_line_width() -> f32 {
+    DEFAULT_LINE_WIDTH
+}
+
+fn default_show_overview() -> bool {
+    true
+}
+
+fn default_show_coordinates() -> bool {
+    true
+}
+
+impl Config {
+    /// Settings of the expressions evaluated on the streams of the run in `dir`
+    pub fn globals(&self, dir: &Path, streams: &[LogStream]) -> Globals {
+        Globals {
+            constants: self.constants.clone(),
+            time_offsets: (0..streams.len())
+                .map(|i| self.time_offset(dir, i))
+                .collect(),
+        }
+    }
+
+    /// The page of tabs shown in the layout, the one containing the selected tab
+    pub fn visible_tabs(&self) -> Range<usize> {
+        let cells = self.layout.cells();
+        let first = self.selected_tab / cells * cells;
+        first..(first + cells).min(self.tabs.len())
+    }
+
+    /// Wall clock time requires a known start of the run
+    pub fn time_format(&self, data: &PlotData) -> TimeFormat {
+        self.run_time_format(&data.dir, &data.streams)
+    }
+
+    /// Time format of the run in `dir`, see [`Config::time_format`]
+    pub fn run_time_format(&self, dir: &Path, streams: &[LogStream]) -> TimeFormat {
+        let start = (self.start_times.get(dir).copied()).or_else(|| file_start(streams));
+        match start {
+            Some(start) if self.wall_clock => TimeFormat::WallClock(start),
+            _ => TimeFormat::Relative,
+        }
+    }
+
+    /// Sample rate of the first stream of the run in `dir`, which all others are aligned to
+    pub fn sample_rate(&self, dir: &Path, streams: &[LogStream]) -> f64 {
+        let Some(first) = streams.first() else {
+            return DEFAULT_SAMPLE_RATE;
+        };
+        (self.run_sample_rates.get(dir))
+            .and_then(|r| r.get(&0))
+            .copied()
+            .or_else(|| first.sample_rate())
+            .unwrap_or(DEFAULT_SAMPLE_RATE)
+    }
+
+    /// Moves the settings saved by the name of the first entry of a stream to the run in `dir`
+    pub fn migrate_stream_settings(&mut self, dir: &Path, streams: &[LogStream]) {
+        for (i, s) in streams.iter().enumerate() {
+            let Some(name) = stream_name(s) else {
+                continue;
+            };
+            if let Some(offset) = self.legacy_time_offsets.remove(name) {
+                let run_offsets = self.run_time_offsets.entry(dir.to_path_buf()).or_default();
+                run_offsets.entry(i).or_insert(offset);
+            }
         }
+        // only the rate of the first stream is used
+        let name = streams.first().and_then(stream_name);
+        if let Some(rate) = name.and_then(|n| self.legacy_sample_rates.remove(n)) {
+            let run_rates = self.run_sample_rates.entry(dir.to_path_buf()).or_default();
+            run_rates.entry(0).or_insert(rate);
+        }
+    }
+
+    /// Offset of the stream at `index` of the run in `dir`
+    pub fn time_offset(&self, dir: &Path, index: usize) -> i32 {
+        (self.run_time_offsets.get(dir))
+            .and_then(|o| o.get(&index))
+            .copied()
+            .unwrap_or(0)
     }
 }
 
+/// Labeled vertical line at a timestamp in time mode
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Marker {
+    /// Time in seconds
+    pub time: f64,
+    pub label: String,
+}
+
+/// Name of the first entry, which tells the streams of a run apart
+fn stream_name(stream: &LogStream) -> Option<&str> {
+    stream.entries.first().map(|e| e.name.as_str())
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct TabConfig {
     pub name: String,
     pub id: u64,
     pub aspect_ratio: f32,
     pub plots: Vec<NamedPlot>,
+    #[serde(default)]
+    pub mode: PlotMode,
+    #[serde(default)]
+    pub spectrum: SpectrumConfig,
+    /// Logarithmic y-axis, the x-axis always stays linear
+    #[serde(default)]
+    pub log_y: bool,
+    /// Shade scatter points from dark to bright as time progresses
+    #[serde(default)]
+    pub color_by_time: bool,
+    #[serde(default)]
+    pub histogram: HistogramConfig,
+    #[serde(default)]
+    pub labels: PlotLabels,
+    #[serde(default)]
+    pub legend: LegendConfig,
+    /// Overrides the grid of [`Config::grid`]
+    #[serde(default)]
+    pub grid: Option<GridConfig>,
+    /// Time range selected by dragging with shift held
+    #[serde(skip)]
+    pub selection: Option<(f64, f64)>,
+    /// X range of the last frame, used for the statistics
+    #[serde(skip)]
+    pub visible_x: Option<(f64, f64)>,
+    /// X range the plot is moved to in the next frame
+    #[serde(skip)]
+    pub pan_to: Option<(f64, f64)>,
+    /// Times of readouts pinned by alt+clicking into a time plot
+    #[serde(skip)]
+    pub pinned: Vec<f64>,
+    /// Time of the row clicked in the values table, shown as cursor while the plot isn't hovered
+    #[serde(skip)]
+    pub inspected: Option<f64>,
+    #[serde(default)]
+    pub x_range: XRange,
+    #[serde(default)]
+    pub windows: Windows,
+    #[serde(default)]
+    pub crossings: Crossings,
+    /// Bounds as `[min_x, min_y, max_x, max_y]`, unless they automatically fit the values
+    #[serde(default)]
+    pub bounds: Option<[f64; 4]>,
+    /// Restore the bounds once the values of the loaded data are evaluated
+    #[serde(skip)]
+    pub restore_bounds: bool,
+    #[serde(skip)]
+    pub reset_view: bool,
     #[serde(skip)]
     #[serde(default)]
     pub editing: bool,
@@ -92,6 +413,25 @@ impl TabConfig {
             id: rand::random(),
             aspect_ratio,
             plots,
+            mode: PlotMode::default(),
+            spectrum: SpectrumConfig::default(),
+            log_y: false,
+            color_by_time: true,
+            histogram: HistogramConfig::default(),
+            labels: PlotLabels::default(),
+            legend: LegendConfig::default(),
+            grid: None,
+            selection: None,
+            visible_x: None,
+            pan_to: None,
+            pinned: Vec::new(),
+            inspected: None,
+            x_range: XRange::default(),
+            windows: Windows::default(),
+            crossings: Crossings::default(),
+            bounds: None,
+            restore_bounds: false,
+            reset_view: false,
             editing: false,
         }
     }
@@ -99,171 +439,904 @@ impl TabConfig {
     pub fn named(name: String) -> Self {
         Self::new(name, DEFAULT_ASPECT_RATIO, Vec::new())
     }
+
+    /// The windows `[a, b]` as `(start, end)` if they are compared in time mode
+    pub fn compared_windows(&self) -> Option<[(f64, f64); 2]> {
+        match self.windows {
+            Windows {
+                a: Some(a),
+                b: Some(b),
+                compare: true,
+            } if self.mode == PlotMode::Time => Some([a, b]),
+            _ => None,
+        }
+    }
+
+    pub fn x_label(&self) -> String {
+        if !self.labels.x.is_empty() {
+            return self.labels.x.clone();
+        }
+        match self.mode {
+            PlotMode::Time => "time".into(),
+            PlotMode::Spectrum => "frequency [Hz]".into(),
+            PlotMode::Scatter => self.common_expr(|e| &e.x).unwrap_or_default(),
+            PlotMode::Histogram => with_unit(
+                self.common_expr(|e| &e.y).unwrap_or_default(),
+                self.common_unit(),
+            ),
+        }
+    }
+
+    /// The names of the plots on the right axis
+    pub fn right_label(&self) -> String {
+        let names: Vec<_> = (self.plots.iter())
+            .filter(|p| p.right_axis)
+            .map(|p| p.name.as_str())
+            .collect();
+        names.join(", ")
+    }
+
+    pub fn y_label(&self) -> String {
+        let label = if !self.labels.y.is_empty() {
+            self.labels.y.clone()
+        } else {
+            match self.mode {
+                PlotMode::Histogram if self.histogram.percent => "%".into(),
+                PlotMode::Histogram => "count".into(),
+                _ => with_unit(
+                    self.common_expr(|e| &e.y).unwrap_or_default(),
+                    self.common_unit(),
+                ),
+            }
+        };
+        match (self.log_y, label.is_empty()) {
+            (false, _) => label,
+            (true, true) => "log₁₀ y".into(),
+            (true, false) => format!("log₁₀ {label}"),
+        }
+    }
+
+    /// The display unit shared by all plots
+    fn common_unit(&self) -> Option<&'static str> {
+        let first = self.plots.first()?.unit;
+        (self.plots.iter())
+            .all(|p| p.unit == first)
+            .then(|| first.symbol())
+            .flatten()
+    }
+
+    /// The expression shared by all plots
+    fn common_expr(&self, f: impl Fn(&Expr) -> &String) -> Option<String> {
+        let first = f(&self.plots.first()?.expr);
+        (self.plots.iter())
+            .all(|p| f(&p.expr) == first)
+            .then(|| first.trim().to_string())
+    }
+
+    /// `sample_rate` is the one of the run, see [`Config::sample_rate`]
+    pub fn processing(&self, plot: &NamedPlot, sample_rate: f64) -> Processing {
+        let mut spectrum = self.spectrum;
+        if spectrum.run_rate {
+            spectrum.sample_rate = sample_rate;
+        }
+        Processing {
+            unit: plot.unit,
+            derivative: plot.derivative,
+            spectrum: (self.mode == PlotMode::Spectrum).then_some(spectrum),
+            histogram_bins: (self.mode == PlotMode::Histogram).then_some(self.histogram.bins),
+            smoothing: plot.smoothing,
+            valid_range: plot.valid_range,
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct NamedPlot {
-    pub name: String,
-    pub expr: Expr,
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct HistogramConfig {
+    pub bins: usize,
+    /// Show the share of samples per bin instead of the count
+    pub percent: bool,
 }
 
-impl NamedPlot {
-    fn new(name: String, expr: Expr) -> Self {
-        Self { name, expr }
+impl Default for HistogramConfig {
+    fn default() -> Self {
+        Self {
+            bins: DEFAULT_HISTOGRAM_BINS,
+            percent: false,
+        }
     }
 }
 
-pub fn add_tab(data: &mut PlotData, cfg: &mut Config) {
-    cfg.tabs
-        .push(TabConfig::named(format!("Tab {}", cfg.tabs.len() + 1)));
-    data.plots.push(Vec::new());
-    cfg.selected_tab = cfg.tabs.len() - 1;
+fn with_unit(label: String, unit: Option<&str>) -> String {
+    match unit {
+        Some(u) if label.is_empty() => format!("[{u}]"),
+        Some(u) => format!("{label} [{u}]"),
+        None => label,
+    }
 }
 
-pub fn remove_tab(data: &mut PlotData, cfg: &mut Config, tab: usize) -> bool {
-    if cfg.tabs.len() == 1 {
-        return false;
-    }
-    cfg.tabs.remove(tab);
-    data.plots.remove(tab);
+/// X range entered by the user
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct XRange {
+    pub start: f64,
+    pub end: f64,
+    /// Keep the plot at this range, zooming and dragging only change the y-axis
+    pub locked: bool,
+}
 
-    if cfg.selected_tab > tab || cfg.selected_tab == cfg.tabs.len() {
-        cfg.selected_tab -= 1;
+impl Default for XRange {
+    fn default() -> Self {
+        Self {
+            start: 0.0,
+            end: 60.0,
+            locked: false,
+        }
     }
+}
 
-    true
+/// Two time ranges of a run whose plots can be drawn on top of each other, e.g. two laps
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Windows {
+    pub a: Option<(f64, f64)>,
+    pub b: Option<(f64, f64)>,
+    /// Draw both windows shifted to start at zero instead of the whole run
+    pub compare: bool,
 }
 
-pub fn move_tab(data: &mut PlotData, cfg: &mut Config, from: usize, to: usize) {
-    let selected_tab = cfg.selected_tab;
-    if from < to {
-        cfg.tabs[from..=to].rotate_left(1);
-        data.plots[from..=to].rotate_left(1);
+/// Threshold whose crossings by one of the plots are listed
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Crossings {
+    /// Name of the plot
+    pub plot: String,
+    pub threshold: f64,
+    /// Crossings of the last listed series, see [`CrossingsCache`]
+    #[serde(skip)]
+    cache: Option<CrossingsCache>,
+}
 
-        if selected_tab > from && selected_tab <= to {
-            cfg.selected_tab -= 1;
-        }
-    } else {
-        cfg.tabs[to..=from].rotate_right(1);
-        data.plots[to..=from].rotate_right(1);
+/// Crossings of a plot, kept until the plot, its points or the threshold change
+#[derive(Clone)]
+struct CrossingsCache {
+    /// Index of the plot, address and number of its points, and the threshold
+    key: (usize, usize, usize, f64),
+    crossings: Vec<analysis::Crossing>,
+}
 
-        if selected_tab < from && selected_tab >= to {
-            cfg.selected_tab += 1;
+/// Arrangement of the plots of multiple tabs in a grid, to see them at once
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlotLayout {
+    pub rows: usize,
+    pub columns: usize,
+}
+
+impl Default for PlotLayout {
+    fn default() -> Self {
+        Self {
+            rows: 1,
+            columns: 1,
         }
     }
+}
 
-    if selected_tab == from {
-        cfg.selected_tab = to;
+impl fmt::Display for PlotLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} × {}", self.rows, self.columns)
     }
 }
 
-pub fn select_next_tab(cfg: &mut Config) {
-    cfg.selected_tab = (cfg.selected_tab + 1) % cfg.tabs.len()
-}
+impl PlotLayout {
+    pub const ALL: [Self; 5] = [
+        Self::new(1, 1),
+        Self::new(1, 2),
+        Self::new(2, 1),
+        Self::new(2, 2),
+        Self::new(2, 3),
+    ];
+
+    const fn new(rows: usize, columns: usize) -> Self {
+        Self { rows, columns }
+    }
 
-pub fn select_prev_tab(cfg: &mut Config) {
-    cfg.selected_tab = (cfg.tabs.len() + cfg.selected_tab - 1) % cfg.tabs.len()
+    pub fn cells(&self) -> usize {
+        self.rows * self.columns
+    }
 }
 
-pub fn add_plot(data: &mut PlotData, cfg: &mut Config, plot: NamedPlot, eval: bool) {
-    let tab = cfg.selected_tab;
-    let plots = &mut cfg.tabs[tab].plots;
+/// Grid lines and how many ticks are shown along each axis
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GridConfig {
+    pub show_major: bool,
+    pub show_minor: bool,
+    /// Approximate number of major ticks, chosen depending on the zoom if `None`
+    pub x_ticks: Option<usize>,
+    pub y_ticks: Option<usize>,
+}
 
-    if eval {
-        let job = Job::start(plot.expr.clone(), Arc::clone(&data.streams));
-        data.plots[tab].push(PlotValues::Job(job));
-    } else {
-        data.plots[tab].push(PlotValues::Result(Ok(Vec::new())));
+impl Default for GridConfig {
+    fn default() -> Self {
+        Self {
+            show_major: true,
+            show_minor: true,
+            x_ticks: None,
+            y_ticks: None,
+        }
     }
-    plots.push(plot);
 }
 
-pub fn move_plot(data: &mut PlotData, cfg: &mut Config, from: usize, to: usize) {
-    let tab = cfg.selected_tab;
-    if from < to {
-        for i in from..to {
-            cfg.tabs[tab].plots.swap(i, i + 1);
-            data.plots[tab].swap(i, i + 1);
-        }
-    } else {
-        for i in (to..from).rev() {
-            cfg.tabs[tab].plots.swap(i + 1, i);
-            data.plots[tab].swap(i + 1, i);
+/// Custom title and axis labels, empty ones fall back to a default
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct PlotLabels {
+    pub title: String,
+    pub x: String,
+    pub y: String,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LegendPosition {
+    Hidden,
+    TopLeft,
+    #[default]
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl fmt::Display for LegendPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Hidden => write!(f, "Hidden"),
+            Self::TopLeft => write!(f, "Top left"),
+            Self::TopRight => write!(f, "Top right"),
+            Self::BottomLeft => write!(f, "Bottom left"),
+            Self::BottomRight => write!(f, "Bottom right"),
         }
     }
 }
 
-pub fn keybindings(ui: &mut Ui, data: &mut PlotData, cfg: &mut Config) {
-    ui.input_mut(|input| {
-        if input.consume_key(Modifiers::CTRL, Key::T) {
-            add_tab(data, cfg);
-        }
-        if input.consume_key(Modifiers::CTRL, Key::W) {
-            let tab = cfg.selected_tab;
-            remove_tab(data, cfg, tab);
+impl LegendPosition {
+    pub const ALL: [Self; 5] = [
+        Self::Hidden,
+        Self::TopLeft,
+        Self::TopRight,
+        Self::BottomLeft,
+        Self::BottomRight,
+    ];
+
+    fn corner(self) -> Option<Corner> {
+        match self {
+            Self::Hidden => None,
+            Self::TopLeft => Some(Corner::LeftTop),
+            Self::TopRight => Some(Corner::RightTop),
+            Self::BottomLeft => Some(Corner::LeftBottom),
+            Self::BottomRight => Some(Corner::RightBottom),
         }
+    }
+}
 
-        if input.consume_key(Modifiers::CTRL | Modifiers::SHIFT, Key::Tab)
-            || input.consume_key(Modifiers::ALT, Key::ArrowLeft)
-        {
-            select_prev_tab(cfg);
-        }
-        if input.consume_key(Modifiers::CTRL, Key::Tab)
-            || input.consume_key(Modifiers::ALT, Key::ArrowRight)
-        {
-            select_next_tab(cfg);
-        }
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LegendConfig {
+    pub position: LegendPosition,
+    /// Overrides the size of the body text
+    pub text_size: Option<f32>,
+}
 
-        if input.consume_key(Modifiers::CTRL, Key::H) {
-            cfg.show_help = !cfg.show_help;
-        }
-        // Open help sidebar so the search bar can be focused
-        if !cfg.show_help
-            && input.modifiers.matches_exact(Modifiers::CTRL)
-            && input.key_pressed(Key::F)
-        {
-            cfg.show_help = true;
-        }
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlotMode {
+    #[default]
+    Time,
+    Spectrum,
+    /// Y against x as unconnected points
+    Scatter,
+    /// Distribution of the y values
+    Histogram,
+}
 
-        if input.consume_key(Modifiers::CTRL, Key::N) {
-            let name = format!("{}.", cfg.tabs[cfg.selected_tab].plots.len() + 1);
-            add_plot(
-                data,
-                cfg,
-                NamedPlot::new(name, Expr::new("time", "")),
-                false,
-            );
+impl fmt::Display for PlotMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Time => write!(f, "Time"),
+            Self::Spectrum => write!(f, "Spectrum"),
+            Self::Scatter => write!(f, "Scatter"),
+            Self::Histogram => write!(f, "Histogram"),
         }
-    });
+    }
 }
 
-#[inline]
-fn tab_height() -> f32 {
-    TAB_BUTTON_HEIGHT + 2.0 * TEXT_EDIT_MARGIN_Y
+impl PlotMode {
+    pub const ALL: [Self; 4] = [Self::Time, Self::Spectrum, Self::Scatter, Self::Histogram];
+
+    /// Whether plots are continuous along the x axis, so ranges of it can be selected
+    pub fn is_continuous(&self) -> bool {
+        matches!(self, Self::Time | Self::Spectrum)
+    }
 }
 
-#[inline]
-fn tab_button_width() -> f32 {
-    TAB_BUTTON_WIDTH + 2.0 * TEXT_EDIT_MARGIN_X
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LineStyle {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
 }
 
-#[inline]
-fn tab_width(ui: &Ui) -> f32 {
-    tab_button_width() + ui.spacing().item_spacing.x + TAB_CROSS_WIDTH
+impl fmt::Display for LineStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Solid => write!(f, "Solid"),
+            Self::Dashed => write!(f, "Dashed"),
+            Self::Dotted => write!(f, "Dotted"),
+        }
+    }
 }
 
-pub fn tab_bar(ui: &mut Ui, data: &mut PlotData, cfg: &mut Config) {
-    ui.horizontal(|ui| {
-        let tab_width = tab_width(ui);
-        let tab_spacing = ui.spacing().item_spacing.x;
-        let tab_distance = tab_width + tab_spacing;
+impl LineStyle {
+    pub const ALL: [Self; 3] = [Self::Solid, Self::Dashed, Self::Dotted];
 
-        let pointer_pos = ui.ctx().pointer_interact_pos();
-        let drag = match (pointer_pos, cfg.dragged_tab) {
-            (Some(pointer_pos), Some((from, grab_pos))) => {
-                let distance = pointer_pos.x - grab_pos.x;
-                let moved = (distance / tab_distance).round() as isize;
-                let to = (from as isize + moved).clamp(0, cfg.tabs.len() as isize - 1) as usize;
+    /// Lengths of the dashes and gaps, for drawing without egui_plot
+    pub fn dash_pattern(self) -> Option<[f32; 2]> {
+        match self {
+            Self::Solid => None,
+            Self::Dashed => Some([DASH_LENGTH, DASH_LENGTH]),
+            Self::Dotted => Some([1.0, DOT_SPACING]),
+        }
+    }
+
+    fn to_plot_style(self) -> egui_plot::LineStyle {
+        match self {
+            Self::Solid => egui_plot::LineStyle::Solid,
+            Self::Dashed => egui_plot::LineStyle::Dashed {
+                length: DASH_LENGTH,
+            },
+            Self::Dotted => egui_plot::LineStyle::Dotted {
+                spacing: DOT_SPACING,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MarkerShape {
+    #[default]
+    Circle,
+    Square,
+    Cross,
+}
+
+impl fmt::Display for MarkerShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Circle => write!(f, "Circle"),
+            Self::Square => write!(f, "Square"),
+            Self::Cross => write!(f, "Cross"),
+        }
+    }
+}
+
+impl MarkerShape {
+    pub const ALL: [Self; 3] = [Self::Circle, Self::Square, Self::Cross];
+
+    fn to_plot_shape(self) -> egui_plot::MarkerShape {
+        match self {
+            Self::Circle => egui_plot::MarkerShape::Circle,
+            Self::Square => egui_plot::MarkerShape::Square,
+            Self::Cross => egui_plot::MarkerShape::Cross,
+        }
+    }
+}
+
+/// How the line of a time plot continues across gaps longer than the gap threshold
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GapFill {
+    #[default]
+    Break,
+    Interpolate,
+    /// Keep the last value until the next sample, e.g. for counters
+    Hold,
+}
+
+impl fmt::Display for GapFill {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Break => write!(f, "Break"),
+            Self::Interpolate => write!(f, "Interpolate"),
+            Self::Hold => write!(f, "Hold"),
+        }
+    }
+}
+
+impl GapFill {
+    pub const ALL: [Self; 3] = [Self::Break, Self::Interpolate, Self::Hold];
+}
+
+/// Drawn at every sample of a time plot, e.g. for sparse channels
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Markers {
+    pub shape: MarkerShape,
+    /// Radius in points
+    pub size: f32,
+    /// Also connect the samples with a line
+    pub line: bool,
+}
+
+impl Default for Markers {
+    fn default() -> Self {
+        Self {
+            shape: MarkerShape::default(),
+            size: DEFAULT_MARKER_SIZE,
+            line: true,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct NamedPlot {
+    pub name: String,
+    pub expr: Expr,
+    /// Overrides the automatically assigned color
+    #[serde(default)]
+    pub color: Option<Color32>,
+    /// Window size in samples of the moving average overlay
+    #[serde(default)]
+    pub smoothing: Option<usize>,
+    /// Plot the derivative of y with respect to x instead
+    #[serde(default)]
+    pub derivative: bool,
+    /// Overrides the line width of the settings
+    #[serde(default)]
+    pub line_width: Option<f32>,
+    /// Overrides the line style of the settings
+    #[serde(default)]
+    pub line_style: Option<LineStyle>,
+    /// Excluded from drawing, the legend and the statistics
+    #[serde(default)]
+    pub hidden: bool,
+    /// Display unit of the y values
+    #[serde(default)]
+    pub unit: Unit,
+    /// Shade the area between the line and zero, only within the selection if there is one. The
+    /// integral of the shaded area is shown.
+    #[serde(default)]
+    pub fill: bool,
+    /// Scaled to the right y axis, to mix values of different magnitude in time and spectrum mode
+    #[serde(default)]
+    pub right_axis: bool,
+    /// Samples outside of `(min, max)` are omitted, and marked at the bounds in time mode
+    #[serde(default)]
+    pub valid_range: Option<(f64, f64)>,
+    /// Draws the samples in time mode
+    #[serde(default)]
+    pub markers: Option<Markers>,
+    #[serde(default)]
+    pub gap_fill: GapFill,
+}
+
+impl NamedPlot {
+    fn new(name: String, expr: Expr) -> Self {
+        Self {
+            name,
+            expr,
+            color: None,
+            smoothing: None,
+            derivative: false,
+            line_width: None,
+            line_style: None,
+            hidden: false,
+            unit: Unit::Unchanged,
+            fill: false,
+            right_axis: false,
+            valid_range: None,
+            markers: None,
+            gap_fill: GapFill::Break,
+        }
+    }
+
+    /// Line with the width and style of the plot, or else the default ones
+    fn line(&self, width: f32, style: LineStyle, points: Vec<PlotPoint>) -> Line {
+        let width = self.line_width.unwrap_or(width);
+        let style = self.line_style.unwrap_or(style);
+        Line::new(PlotPoints::Owned(points))
+            .width(width)
+            .style(style.to_plot_style())
+    }
+
+    pub fn color(&self, idx: usize, palette: Palette) -> Color32 {
+        self.color.unwrap_or_else(|| palette.color(idx))
+    }
+}
+
+/// Colors assigned to plots in order, unless they override it
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Palette {
+    /// Same colors egui_plot assigns automatically
+    #[default]
+    Auto,
+    /// Colorblind friendly palette by Okabe and Ito, with gray instead of black
+    OkabeIto,
+    /// Colorblind friendly palette by Paul Tol
+    TolBright,
+    Tableau,
+}
+
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => write!(f, "Auto"),
+            Self::OkabeIto => write!(f, "Okabe-Ito"),
+            Self::TolBright => write!(f, "Tol bright"),
+            Self::Tableau => write!(f, "Tableau"),
+        }
+    }
+}
+
+impl Palette {
+    pub const ALL: [Self; 4] = [Self::Auto, Self::OkabeIto, Self::TolBright, Self::Tableau];
+
+    pub fn color(&self, idx: usize) -> Color32 {
+        let colors: &[u32] = match self {
+            Self::Auto => {
+                let golden_ratio = (5.0_f32.sqrt() - 1.0) / 2.0;
+                let h = idx as f32 * golden_ratio;
+                return Hsva::new(h, 0.85, 0.5, 1.0).into();
+            }
+            Self::OkabeIto => &[
+                0xe69f00, 0x56b4e9, 0x009e73, 0xf0e442, 0x0072b2, 0xd55e00, 0xcc79a7, 0x999999,
+            ],
+            Self::TolBright => &[
+                0x4477aa, 0x66ccee, 0x228833, 0xccbb44, 0xee6677, 0xaa3377, 0xbbbbbb,
+            ],
+            Self::Tableau => &[
+                0x4e79a7, 0xf28e2b, 0xe15759, 0x76b7b2, 0x59a14f, 0xedc948, 0xb07aa1, 0xff9da7,
+                0x9c755f, 0xbab0ac,
+            ],
+        };
+        let [_, r, g, b] = colors[idx % colors.len()].to_be_bytes();
+        Color32::from_rgb(r, g, b)
+    }
+}
+
+/// Evaluates the plot on the streams of the run in `dir`
+pub fn start_job(
+    cfg: &Config,
+    tab: &TabConfig,
+    plot: &NamedPlot,
+    dir: &Path,
+    streams: &Arc<[LogStream]>,
+) -> PlotValues {
+    let job = Job::start(
+        plot.expr.clone(),
+        Arc::clone(streams),
+        cfg.globals(dir, streams),
+        tab.processing(plot, cfg.sample_rate(dir, streams)),
+    );
+    PlotValues::Job(job)
+}
+
+/// Starts jobs for the plots of the selected tab whose inputs changed, and collects finished ones
+fn update_comparison(ctx: &egui::Context, comparison: &mut Comparison, cfg: &Config) {
+    let tab_cfg = &cfg.tabs[cfg.selected_tab];
+    for (i, p) in tab_cfg.plots.iter().enumerate() {
+        let inputs = JobInputs {
+            expr: p.expr.clone(),
+            processing: tab_cfg
+                .processing(p, cfg.sample_rate(&comparison.dir, &comparison.streams)),
+            globals: cfg.globals(&comparison.dir, &comparison.streams),
+        };
+        let key = (tab_cfg.id, i);
+        match comparison.plots.get_mut(&key) {
+            Some((current, values)) if *current == inputs => {
+                if let PlotValues::Job(j) = values {
+                    if j.is_done() {
+                        let job = std::mem::replace(values, PlotValues::empty());
+                        *values = PlotValues::Result(job.into_job().unwrap().join());
+                    } else {
+                        ctx.request_repaint();
+                    }
+                }
+            }
+            _ => {
+                let values = start_job(cfg, tab_cfg, p, &comparison.dir, &comparison.streams);
+                comparison.plots.insert(key, (inputs, values));
+                ctx.request_repaint();
+            }
+        }
+    }
+}
+
+/// Time in seconds the second streams have to be shifted by to start with the first ones
+fn start_offset(a: &[LogStream], b: &[LogStream]) -> f64 {
+    let start = |s: &[LogStream]| s.first().and_then(|s| s.time.first()).copied();
+    match (start(a), start(b)) {
+        (Some(a), Some(b)) => (a as f64 - b as f64) / 1000.0,
+        _ => 0.0,
+    }
+}
+
+/// Faint color of the plot of the baseline run
+fn baseline_color(color: Color32) -> Color32 {
+    color.gamma_multiply(0.35)
+}
+
+/// Color of the plot of a compared run, with the opposite hue
+fn comparison_color(color: Color32) -> Color32 {
+    let mut hsva = Hsva::from(color);
+    hsva.h = (hsva.h + 0.5).fract();
+    hsva.into()
+}
+
+/// Plots on the right axis are drawn in the coordinates of the left axis. Their values are
+/// mapped linearly from the range of the visible right axis plots onto the range of the left
+/// ones, so both fill the plot, and the labels of the right axis map the marks back.
+#[derive(Clone, Copy)]
+pub struct AxisMap {
+    /// Minimum and maximum of the plots on the left axis
+    left: (f64, f64),
+    /// Minimum and maximum of the plots on the right axis
+    right: (f64, f64),
+}
+
+impl AxisMap {
+    /// `None` unless there are plots on both axes
+    fn new(values: &[PlotValues], tab: &TabConfig) -> Option<Self> {
+        let series = (values.iter().enumerate()).filter_map(|(i, v)| match v {
+            PlotValues::Result(Ok(d)) => Some((i, d)),
+            _ => None,
+        });
+        Self::of(series, tab)
+    }
+
+    /// See [`AxisMap::new`], for the series of the plots with matching indices
+    pub fn of<'a>(
+        series: impl Iterator<Item = (usize, &'a analysis::Series)> + Clone,
+        tab: &TabConfig,
+    ) -> Option<Self> {
+        if !matches!(tab.mode, PlotMode::Time | PlotMode::Spectrum) {
+            return None;
+        }
+        let range = |right: bool| {
+            let points = (series.clone())
+                .filter(|(i, _)| tab.plots[*i].right_axis == right && !tab.plots[*i].hidden)
+                .flat_map(|(_, d)| d.points.iter());
+            let ys = points
+                .map(|p| if tab.log_y { p.y.log10() } else { p.y })
+                .filter(|y| y.is_finite());
+            let (min, max) = ys.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), y| {
+                (min.min(y), max.max(y))
+            });
+            (min < max).then_some((min, max))
+        };
+        Some(Self {
+            left: range(false)?,
+            right: range(true)?,
+        })
+    }
+
+    /// Maps a value of the right axis onto the left one
+    fn left_y(&self, y: f64) -> f64 {
+        let scale = (self.left.1 - self.left.0) / (self.right.1 - self.right.0);
+        self.left.0 + (y - self.right.0) * scale
+    }
+
+    /// Maps a value of the left axis back onto the right one
+    pub fn right_y(&self, y: f64) -> f64 {
+        let scale = (self.right.1 - self.right.0) / (self.left.1 - self.left.0);
+        self.right.0 + (y - self.left.0) * scale
+    }
+
+    pub fn apply(&self, values: &mut [PlotPoint]) {
+        for v in values.iter_mut() {
+            v.y = self.left_y(v.y);
+        }
+    }
+}
+
+pub fn start_all_jobs(
+    cfg: &Config,
+    dir: &Path,
+    streams: &Arc<[LogStream]>,
+) -> Vec<Vec<PlotValues>> {
+    (cfg.tabs.iter())
+        .map(|t| {
+            (t.plots.iter())
+                .map(|p| start_job(cfg, t, p, dir, streams))
+                .collect()
+        })
+        .collect()
+}
+
+pub fn restart_jobs(data: &mut PlotData, cfg: &Config, tab: usize) {
+    let tab_cfg = &cfg.tabs[tab];
+    for (values, p) in data.plots[tab].iter_mut().zip(tab_cfg.plots.iter()) {
+        *values = start_job(cfg, tab_cfg, p, &data.dir, &data.streams);
+    }
+}
+
+pub fn add_tab(data: &mut PlotData, cfg: &mut Config) {
+    cfg.tabs
+        .push(TabConfig::named(format!("Tab {}", cfg.tabs.len() + 1)));
+    data.plots.push(Vec::new());
+    cfg.selected_tab = cfg.tabs.len() - 1;
+}
+
+pub fn remove_tab(data: &mut PlotData, cfg: &mut Config, tab: usize) -> bool {
+    if cfg.tabs.len() == 1 {
+        return false;
+    }
+    cfg.tabs.remove(tab);
+    data.plots.remove(tab);
+
+    if cfg.selected_tab > tab || cfg.selected_tab == cfg.tabs.len() {
+        cfg.selected_tab -= 1;
+    }
+
+    true
+}
+
+pub fn move_tab(data: &mut PlotData, cfg: &mut Config, from: usize, to: usize) {
+    let selected_tab = cfg.selected_tab;
+    if from < to {
+        cfg.tabs[from..=to].rotate_left(1);
+        data.plots[from..=to].rotate_left(1);
+
+        if selected_tab > from && selected_tab <= to {
+            cfg.selected_tab -= 1;
+        }
+    } else {
+        cfg.tabs[to..=from].rotate_right(1);
+        data.plots[to..=from].rotate_right(1);
+
+        if selected_tab < from && selected_tab >= to {
+            cfg.selected_tab += 1;
+        }
+    }
+
+    if selected_tab == from {
+        cfg.selected_tab = to;
+    }
+}
+
+pub fn select_next_tab(cfg: &mut Config) {
+    cfg.selected_tab = (cfg.selected_tab + 1) % cfg.tabs.len()
+}
+
+pub fn select_prev_tab(cfg: &mut Config) {
+    cfg.selected_tab = (cfg.tabs.len() + cfg.selected_tab - 1) % cfg.tabs.len()
+}
+
+pub fn add_plot(data: &mut PlotData, cfg: &mut Config, plot: NamedPlot, eval: bool) {
+    let tab = cfg.selected_tab;
+
+    if eval {
+        let values = start_job(cfg, &cfg.tabs[tab], &plot, &data.dir, &data.streams);
+        data.plots[tab].push(values);
+    } else {
+        data.plots[tab].push(PlotValues::empty());
+    }
+    cfg.tabs[tab].plots.push(plot);
+}
+
+pub fn undo(data: &mut PlotData, cfg: &mut Config) {
+    if let Some(snapshot) = cfg.history.pop_undo() {
+        if let Some(present) = restore(data, cfg, snapshot) {
+            cfg.history.push_redo(present);
+        }
+    }
+}
+
+pub fn redo(data: &mut PlotData, cfg: &mut Config) {
+    if let Some(snapshot) = cfg.history.pop_redo() {
+        if let Some(present) = restore(data, cfg, snapshot) {
+            cfg.history.push_undo(present);
+        }
+    }
+}
+
+/// Returns the replaced state, or `None` if the tab was removed
+fn restore(data: &mut PlotData, cfg: &mut Config, snapshot: Snapshot) -> Option<Snapshot> {
+    let tab = cfg.tabs.iter().position(|t| t.id == snapshot.tab_id)?;
+    let plots = std::mem::replace(&mut cfg.tabs[tab].plots, snapshot.plots);
+    cfg.selected_tab = tab;
+    cfg.history.reset(snapshot.tab_id, &cfg.tabs[tab].plots);
+
+    let tab_cfg = &cfg.tabs[tab];
+    data.plots[tab] = (tab_cfg.plots.iter())
+        .map(|p| start_job(cfg, tab_cfg, p, &data.dir, &data.streams))
+        .collect();
+
+    Some(Snapshot {
+        tab_id: snapshot.tab_id,
+        plots,
+    })
+}
+
+pub fn move_plot(data: &mut PlotData, cfg: &mut Config, from: usize, to: usize) {
+    let tab = cfg.selected_tab;
+    if from < to {
+        for i in from..to {
+            cfg.tabs[tab].plots.swap(i, i + 1);
+            data.plots[tab].swap(i, i + 1);
+        }
+    } else {
+        for i in (to..from).rev() {
+            cfg.tabs[tab].plots.swap(i + 1, i);
+            data.plots[tab].swap(i + 1, i);
+        }
+    }
+}
+
+pub fn keybindings(ui: &mut Ui, data: &mut PlotData, cfg: &mut Config) {
+    ui.input_mut(|input| {
+        if input.consume_key(Modifiers::CTRL, Key::T) {
+            add_tab(data, cfg);
+        }
+        if input.consume_key(Modifiers::CTRL, Key::W) {
+            let tab = cfg.selected_tab;
+            remove_tab(data, cfg, tab);
+        }
+
+        if input.consume_key(Modifiers::CTRL | Modifiers::SHIFT, Key::Tab)
+            || input.consume_key(Modifiers::ALT, Key::ArrowLeft)
+        {
+            select_prev_tab(cfg);
+        }
+        if input.consume_key(Modifiers::CTRL, Key::Tab)
+            || input.consume_key(Modifiers::ALT, Key::ArrowRight)
+        {
+            select_next_tab(cfg);
+        }
+
+        if input.consume_key(Modifiers::CTRL, Key::H) {
+            cfg.show_help = !cfg.show_help;
+        }
+        // Open help sidebar so the search bar can be focused
+        if !cfg.show_help
+            && input.modifiers.matches_exact(Modifiers::CTRL)
+            && input.key_pressed(Key::F)
+        {
+            cfg.show_help = true;
+        }
+
+        if input.consume_key(Modifiers::CTRL, Key::N) {
+            let name = format!("{}.", cfg.tabs[cfg.selected_tab].plots.len() + 1);
+            add_plot(
+                data,
+                cfg,
+                NamedPlot::new(name, Expr::new("time", "")),
+                false,
+            );
+        }
+    });
+}
+
+#[inline]
+fn tab_height() -> f32 {
+    TAB_BUTTON_HEIGHT + 2.0 * TEXT_EDIT_MARGIN_Y
+}
+
+#[inline]
+fn tab_button_width() -> f32 {
+    TAB_BUTTON_WIDTH + 2.0 * TEXT_EDIT_MARGIN_X
+}
+
+#[inline]
+fn tab_width(ui: &Ui) -> f32 {
+    tab_button_width() + ui.spacing().item_spacing.x + TAB_CROSS_WIDTH
+}
+
+pub fn tab_bar(ui: &mut Ui, data: &mut PlotData, cfg: &mut Config) {
+    ui.horizontal(|ui| {
+        let tab_width = tab_width(ui);
+        let tab_spacing = ui.spacing().item_spacing.x;
+        let tab_distance = tab_width + tab_spacing;
+
+        let pointer_pos = ui.ctx().pointer_interact_pos();
+        let drag = match (pointer_pos, cfg.dragged_tab) {
+            (Some(pointer_pos), Some((from, grab_pos))) => {
+                let distance = pointer_pos.x - grab_pos.x;
+                let moved = (distance / tab_distance).round() as isize;
+                let to = (from as isize + moved).clamp(0, cfg.tabs.len() as isize - 1) as usize;
 
                 // move the tab if it was dropped
                 if ui.input(|i| i.pointer.any_released()) {
@@ -324,29 +1397,294 @@ pub fn tab_bar(ui: &mut Ui, data: &mut PlotData, cfg: &mut Config) {
                 None => (),
             }
 
-            if !(removed && remove_tab(data, cfg, i)) {
-                i += 1;
+            if !(removed && remove_tab(data, cfg, i)) {
+                i += 1;
+            }
+        }
+
+        let button =
+            Button::new(RichText::new(" + ").strong().size(16.0)).fill(ui.visuals().faint_bg_color);
+        let resp = ui.add_sized(Vec2::splat(TAB_BUTTON_HEIGHT), button);
+        if resp.clicked() {
+            add_tab(data, cfg);
+        }
+
+        util::ratio_slider(
+            ui,
+            &mut cfg.tabs[cfg.selected_tab].aspect_ratio,
+            DEFAULT_ASPECT_RATIO,
+            1000.0,
+        );
+
+        let tab = cfg.selected_tab;
+        if mode_selector(ui, &mut cfg.tabs[tab]) {
+            restart_jobs(data, cfg, tab);
+        }
+        ui.checkbox(&mut cfg.tabs[tab].log_y, "log y");
+        if ui.button("reset view").clicked() {
+            cfg.tabs[tab].reset_view = true;
+        }
+        ui.menu_button("x range", |ui| {
+            x_range_editor(ui, &mut cfg.tabs[tab]);
+        });
+        if cfg.tabs[tab].mode == PlotMode::Time {
+            ui.menu_button("windows", |ui| {
+                windows_editor(ui, &mut cfg.tabs[tab]);
+            });
+        }
+        ui.menu_button("labels", |ui| {
+            labels_editor(ui, &mut cfg.tabs[tab].labels);
+        });
+        ui.menu_button("legend", |ui| {
+            legend_editor(ui, &mut cfg.tabs[tab].legend);
+        });
+        ui.menu_button("grid", |ui| {
+            let grid = &mut cfg.tabs[tab].grid;
+            let mut custom = grid.is_some();
+            if ui.checkbox(&mut custom, "override global").changed() {
+                *grid = custom.then_some(cfg.grid);
+            }
+            if let Some(grid) = grid {
+                ui.separator();
+                grid_editor(ui, grid);
+            }
+        });
+
+        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+            ui.toggle_value(&mut cfg.show_help, "?");
+        });
+    });
+}
+
+fn windows_editor(ui: &mut Ui, tab: &mut TabConfig) {
+    let selection = tab.selection.map(|(a, b)| (a.min(b), a.max(b)));
+    let windows = &mut tab.windows;
+    Grid::new("windows_editor").show(ui, |ui| {
+        for (name, window) in [("A", &mut windows.a), ("B", &mut windows.b)] {
+            ui.label(name);
+            match window {
+                Some((start, end)) => {
+                    for value in [start, end] {
+                        let drag = DragValue::new(value)
+                            .speed(0.1)
+                            .custom_formatter(|v, _| format_time(v))
+                            .custom_parser(util::parse_time);
+                        ui.add(drag);
+                    }
+                }
+                None => {
+                    ui.weak("-");
+                    ui.weak("-");
+                }
+            }
+            let from_selection = Button::new("from selection");
+            if ui
+                .add_enabled(selection.is_some(), from_selection)
+                .clicked()
+            {
+                *window = selection;
+            }
+            if ui.add_enabled(window.is_some(), Button::new("🗙")).clicked() {
+                *window = None;
+            }
+            ui.end_row();
+        }
+    });
+
+    let both = windows.a.is_some() && windows.b.is_some();
+    let compare = Checkbox::new(&mut windows.compare, "overlay aligned at their start");
+    if ui.add_enabled(both, compare).changed() {
+        tab.reset_view = true;
+    }
+    if selection.is_none() {
+        ui.weak("Drag with shift held to select a window");
+    }
+}
+
+fn x_range_editor(ui: &mut Ui, tab: &mut TabConfig) {
+    let time_mode = tab.mode == PlotMode::Time;
+    let range = &mut tab.x_range;
+    Grid::new("x_range_editor").show(ui, |ui| {
+        for (name, value) in [("start", &mut range.start), ("end", &mut range.end)] {
+            ui.label(name);
+            let mut drag = DragValue::new(value).speed(0.1);
+            if time_mode {
+                drag = drag
+                    .custom_formatter(|v, _| format_time(v))
+                    .custom_parser(util::parse_time);
+            }
+            ui.add(drag);
+            ui.end_row();
+        }
+    });
+
+    ui.horizontal(|ui| {
+        if ui.button("apply").clicked() {
+            tab.pan_to = Some((range.start, range.end));
+        }
+        if ui.button("from view").clicked() {
+            if let Some((start, end)) = tab.visible_x {
+                range.start = start;
+                range.end = end;
+            }
+        }
+        ui.checkbox(&mut range.locked, "lock");
+    });
+}
+
+fn labels_editor(ui: &mut Ui, labels: &mut PlotLabels) {
+    Grid::new("labels_editor").show(ui, |ui| {
+        for (name, label) in [
+            ("title", &mut labels.title),
+            ("x-axis", &mut labels.x),
+            ("y-axis", &mut labels.y),
+        ] {
+            ui.label(name);
+            ui.add(TextEdit::singleline(label).hint_text("default"));
+            ui.end_row();
+        }
+    });
+}
+
+fn legend_editor(ui: &mut Ui, legend: &mut LegendConfig) {
+    ComboBox::from_label("position")
+        .selected_text(legend.position.to_string())
+        .show_ui(ui, |ui| {
+            for p in LegendPosition::ALL {
+                ui.selectable_value(&mut legend.position, p, p.to_string());
+            }
+        });
+    ui.horizontal(|ui| {
+        let mut custom = legend.text_size.is_some();
+        if ui.checkbox(&mut custom, "text size").changed() {
+            legend.text_size = custom.then_some(DEFAULT_LEGEND_TEXT_SIZE);
+        }
+        if let Some(size) = &mut legend.text_size {
+            ui.add(
+                DragValue::new(size)
+                    .speed(0.1)
+                    .clamp_range(4.0..=MAX_LEGEND_TEXT_SIZE),
+            );
+        }
+    });
+}
+
+pub fn grid_editor(ui: &mut Ui, grid: &mut GridConfig) {
+    ui.checkbox(&mut grid.show_major, "major lines");
+    let minor = Checkbox::new(&mut grid.show_minor, "minor lines");
+    ui.add_enabled(grid.show_major, minor);
+    for (name, ticks) in [
+        ("x ticks", &mut grid.x_ticks),
+        ("y ticks", &mut grid.y_ticks),
+    ] {
+        ui.horizontal(|ui| {
+            let mut fixed = ticks.is_some();
+            if ui.checkbox(&mut fixed, name).changed() {
+                *ticks = fixed.then_some(DEFAULT_TICKS);
+            }
+            if let Some(t) = ticks {
+                ui.add(DragValue::new(t).clamp_range(1..=MAX_TICKS));
+            }
+        });
+    }
+}
+
+/// Places about `ticks` major grid lines at round steps, or uses the default spacing
+fn grid_spacer(ticks: Option<usize>, minor: bool) -> impl Fn(GridInput) -> Vec<GridMark> {
+    let default_spacer = egui_plot::log_grid_spacer(10);
+    move |input| {
+        let Some(ticks) = ticks else {
+            let marks = default_spacer(input);
+            if minor {
+                return marks;
             }
-        }
+            let major = marks.iter().map(|m| m.step_size).fold(0.0, f64::max);
+            return marks.into_iter().filter(|m| m.step_size == major).collect();
+        };
 
-        let button =
-            Button::new(RichText::new(" + ").strong().size(16.0)).fill(ui.visuals().faint_bg_color);
-        let resp = ui.add_sized(Vec2::splat(TAB_BUTTON_HEIGHT), button);
-        if resp.clicked() {
-            add_tab(data, cfg);
+        let (min, max) = input.bounds;
+        let step = round_step((max - min) / ticks as f64);
+        let mut marks = Vec::new();
+        if !(step.is_finite() && step > 0.0) {
+            return marks;
         }
+        let minor_step = step / MINOR_STEPS;
+        let steps = if minor { minor_step } else { step };
+        let mut i = (min / steps).ceil() as i64;
+        while (i as f64) * steps <= max {
+            let value = i as f64 * steps;
+            let is_major = !minor || i % MINOR_STEPS as i64 == 0;
+            let step_size = if is_major { step } else { minor_step };
+            marks.push(GridMark { value, step_size });
+            i += 1;
+        }
+        marks
+    }
+}
 
-        util::ratio_slider(
-            ui,
-            &mut cfg.tabs[cfg.selected_tab].aspect_ratio,
-            DEFAULT_ASPECT_RATIO,
-            1000.0,
-        );
+/// The next step of 1, 2 or 5 times a power of ten
+fn round_step(step: f64) -> f64 {
+    let magnitude = 10f64.powf(step.log10().floor());
+    let normalized = step / magnitude;
+    let factor = match normalized {
+        n if n <= 1.0 => 1.0,
+        n if n <= 2.0 => 2.0,
+        n if n <= 5.0 => 5.0,
+        _ => 10.0,
+    };
+    factor * magnitude
+}
 
-        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
-            ui.toggle_value(&mut cfg.show_help, "?");
+/// Returns true if the mode or its config changed
+fn mode_selector(ui: &mut Ui, tab: &mut TabConfig) -> bool {
+    let mut changed = false;
+
+    ComboBox::from_id_source("plot_mode")
+        .selected_text(tab.mode.to_string())
+        .show_ui(ui, |ui| {
+            for m in PlotMode::ALL {
+                changed |= ui
+                    .selectable_value(&mut tab.mode, m, m.to_string())
+                    .changed();
+            }
         });
-    });
+
+    if tab.mode == PlotMode::Spectrum {
+        let spectrum = &mut tab.spectrum;
+        ComboBox::from_id_source("spectrum_window")
+            .selected_text(spectrum.window.to_string())
+            .show_ui(ui, |ui| {
+                for w in WindowFn::ALL {
+                    changed |= ui
+                        .selectable_value(&mut spectrum.window, w, w.to_string())
+                        .changed();
+                }
+            });
+
+        changed |= ui
+            .checkbox(&mut spectrum.run_rate, "run rate")
+            .on_hover_text("Use the sample rate of the run, see the sidebar")
+            .changed();
+        if !spectrum.run_rate {
+            let resp = ui.add(DragValue::new(&mut spectrum.sample_rate).suffix(" Hz"));
+            spectrum.sample_rate = spectrum.sample_rate.max(MIN_SAMPLE_RATE);
+            changed |= resp.changed();
+        }
+    }
+
+    if tab.mode == PlotMode::Scatter {
+        ui.checkbox(&mut tab.color_by_time, "color by time");
+    }
+
+    if tab.mode == PlotMode::Histogram {
+        let histogram = &mut tab.histogram;
+        let resp = ui.add(DragValue::new(&mut histogram.bins).suffix(" bins"));
+        histogram.bins = histogram.bins.max(1);
+        changed |= resp.changed();
+        ui.checkbox(&mut histogram.percent, "percent");
+    }
+
+    changed
 }
 
 enum TabAction {
@@ -414,6 +1752,12 @@ fn draw_tab(ui: &mut Ui, name: &mut String, selected: bool, editing: bool) -> Op
 }
 
 pub fn tab_plot(ui: &mut Ui, data: &mut PlotData, cfg: &mut Config) {
+    if let Some(comparison) = &mut data.comparison {
+        update_comparison(ui.ctx(), comparison, cfg);
+    }
+    if let Some(baseline) = &mut data.baseline {
+        update_comparison(ui.ctx(), baseline, cfg);
+    }
     let panel_fill = if ui.style().visuals.dark_mode {
         Color32::from_gray(0x20)
     } else {
@@ -435,6 +1779,9 @@ pub fn tab_plot(ui: &mut Ui, data: &mut PlotData, cfg: &mut Config) {
                     input_sidebar(ui, data, cfg);
                 });
         });
+    if let Some(tab) = cfg.tabs.get(cfg.selected_tab) {
+        cfg.history.track(tab.id, &tab.plots);
+    }
 
     if cfg.show_help {
         SidePanel::right("help")
@@ -451,60 +1798,1053 @@ pub fn tab_plot(ui: &mut Ui, data: &mut PlotData, cfg: &mut Config) {
             });
     }
 
-    CentralPanel::default()
-        .frame(Frame::none())
-        .show_inside(ui, |ui| {
-            let tab_cfg = &mut cfg.tabs[cfg.selected_tab];
-
-            let num_pixels = ui.ctx().pixels_per_point() * ui.available_width();
-            Plot::new(tab_cfg.id)
-                .data_aspect(tab_cfg.aspect_ratio)
-                .label_formatter(|_, v| {
-                    let x = format_time(v.x);
-                    let y = (v.y * 1000.0).round() / 1000.0;
-                    format!("t = {x}\ny = {y}")
-                })
-                .legend(Legend::default())
-                .show(ui, |ui| {
-                    let auto_bounds = ui.auto_bounds().any();
-                    let x_min = *ui.plot_bounds().range_x().start();
-                    let x_max = *ui.plot_bounds().range_x().end();
-
-                    // HACK: logs are in 50Hz (20ms steps), but that frequency could change at any
-                    // time, or even be dynamic
-                    let steps = 50.0 * (x_max - x_min);
-                    let chunk_size = ((steps / num_pixels as f64) as usize).max(1);
-
-                    for (values, p) in data.plots[cfg.selected_tab]
-                        .iter_mut()
-                        .zip(tab_cfg.plots.iter())
-                    {
-                        if let PlotValues::Job(j) = values {
-                            if j.is_done() {
-                                let job = std::mem::replace(values, PlotValues::empty());
-                                *values = PlotValues::Result(job.into_job().unwrap().join());
-                            } else {
-                                ui.ctx().request_repaint();
-                            }
-                        }
+    cfg.cursor = None;
+    CentralPanel::default()
+        .frame(Frame::none())
+        .show_inside(ui, |ui| {
+            let layout = cfg.layout;
+            if layout.cells() == 1 {
+                tab_view(ui, data, cfg, cfg.selected_tab);
+                return;
+            }
+
+            let rect = ui.available_rect_before_wrap();
+            let size = Vec2::new(
+                rect.width() / layout.columns as f32,
+                rect.height() / layout.rows as f32,
+            );
+            for (k, tab) in cfg.visible_tabs().enumerate() {
+                let (row, column) = (k / layout.columns, k % layout.columns);
+                let min = rect.min + Vec2::new(column as f32 * size.x, row as f32 * size.y);
+                let cell = Rect::from_min_size(min, size).shrink(PLOT_FRAME_PADDING);
+                ui.allocate_ui_at_rect(cell, |ui| {
+                    ui.set_clip_rect(cell);
+                    ui.push_id(cfg.tabs[tab].id, |ui| tab_view(ui, data, cfg, tab));
+                });
+                // the sidebar edits the plots of the selected tab
+                let pressed = ui.input(|i| i.pointer.any_pressed());
+                if pressed && ui.rect_contains_pointer(cell) {
+                    cfg.selected_tab = tab;
+                }
+                if tab == cfg.selected_tab {
+                    let stroke = ui.visuals().selection.stroke;
+                    ui.painter().rect_stroke(cell.expand(1.0), 0.0, stroke);
+                }
+            }
+        });
+}
+
+fn tab_view(ui: &mut Ui, data: &mut PlotData, cfg: &mut Config, tab: usize) {
+    let time_format = cfg.time_format(data);
+    let tab_cfg = &mut cfg.tabs[tab];
+
+    // the values of a histogram are counts
+    if tab_cfg.mode != PlotMode::Histogram {
+        TopBottomPanel::bottom(Id::new(("statistics", tab_cfg.id)))
+            .frame(Frame::none())
+            .show_inside(ui, |ui| {
+                let values = &data.plots[tab];
+                statistics_panel(ui, values, tab_cfg, cfg.number_format, cfg.palette);
+            });
+    }
+    if tab_cfg.mode == PlotMode::Time && tab_cfg.compared_windows().is_none() {
+        TopBottomPanel::bottom(Id::new(("values", tab_cfg.id)))
+            .frame(Frame::none())
+            .show_inside(ui, |ui| {
+                let values = &data.plots[tab];
+                let (number_format, palette) = (cfg.number_format, cfg.palette);
+                let format = (time_format, number_format);
+                if let Some(t) = values_table(ui, values, tab_cfg, format, palette) {
+                    tab_cfg.inspected = Some(t);
+                }
+            });
+    }
+    if cfg.show_overview && tab_cfg.mode.is_continuous() {
+        TopBottomPanel::bottom(Id::new(("overview", tab_cfg.id)))
+            .frame(Frame::none())
+            .show_inside(ui, |ui| {
+                overview(ui, &data.plots[tab], tab_cfg, cfg.palette);
+            });
+    }
+
+    let mode = tab_cfg.mode;
+    // drawn instead of the whole run, so the time axis doesn't match it
+    let windows = tab_cfg.compared_windows();
+    let whole_run = windows.is_none();
+    let log_y = tab_cfg.log_y;
+    let percent = tab_cfg.histogram.percent;
+    let num_pixels = ui.ctx().pixels_per_point() * ui.available_width();
+    let mut buckets = (num_pixels as usize).max(1);
+    let mut threshold = cfg.decimation_threshold;
+    if let Some(max) = cfg.max_points {
+        // decimation keeps two points per bucket
+        buckets = buckets.min(max / 2).max(1);
+        threshold = threshold.min(max);
+    }
+    let (line_width, line_style) = (cfg.line_width, cfg.line_style);
+    let show_seams = cfg.show_seams && mode == PlotMode::Time && whole_run;
+    let gap_threshold = cfg.gap_threshold.filter(|_| mode == PlotMode::Time);
+    let mut gaps = Vec::new();
+    // offsets in seconds added to the x values of the compared and baseline runs
+    let align = cfg.align_comparison && mode == PlotMode::Time;
+    let offset = |c: &Option<Comparison>| match c {
+        Some(c) if align => start_offset(&data.streams, &c.streams),
+        _ => 0.0,
+    };
+    let comparison_offset = offset(&data.comparison);
+    let baseline_offset = offset(&data.baseline);
+    let number_format = cfg.number_format;
+    let show_coordinates = cfg.show_coordinates;
+    let palette = cfg.palette;
+    let y_value = move |y: f64| if log_y { 10f64.powf(y) } else { y };
+    let axis_map = AxisMap::new(&data.plots[tab], tab_cfg);
+    let right_names: Vec<String> = (tab_cfg.plots.iter())
+        .filter(|p| p.right_axis)
+        .map(|p| p.name.clone())
+        .collect();
+    // also matches overlays like "name (baseline)"
+    let is_right = move |name: &str| {
+        right_names.iter().any(|n| {
+            name.strip_prefix(n.as_str())
+                .map_or(false, |rest| rest.is_empty() || rest.starts_with(" ("))
+        })
+    };
+    // labeled x and y values of a position in the plot
+    let coordinates = move |x: f64, y: f64| {
+        let y = number_format.format(y_value(y));
+        let x_text = number_format.format(x);
+        match mode {
+            PlotMode::Time => [format!("t = {}", time_format.format(x)), format!("y = {y}")],
+            PlotMode::Spectrum => [format!("f = {x_text} Hz"), format!("y = {y}")],
+            PlotMode::Scatter => [format!("x = {x_text}"), format!("y = {y}")],
+            PlotMode::Histogram if percent => [format!("x = {x_text}"), format!("{y} %")],
+            PlotMode::Histogram => [format!("x = {x_text}"), format!("count = {y}")],
+        }
+    };
+    let mut plot = Plot::new(tab_cfg.id).data_aspect(tab_cfg.aspect_ratio);
+    if let Some(map) = axis_map {
+        let right = AxisHints::new_y()
+            .placement(HPlacement::Right)
+            .label(tab_cfg.right_label())
+            .formatter(move |mark, _| number_format.format(y_value(map.right_y(mark.value))));
+        plot = plot.custom_y_axes(vec![AxisHints::new_y(), right]);
+    }
+    let mut plot = plot
+        .label_formatter(move |name, v| {
+            let y = match axis_map {
+                Some(map) if is_right(name) => map.right_y(v.y),
+                _ => v.y,
+            };
+            coordinates(v.x, y).join("\n")
+        })
+        .y_axis_formatter(move |mark, _| number_format.format(y_value(mark.value)));
+    if let Some(corner) = tab_cfg.legend.position.corner() {
+        let mut legend = Legend::default().position(corner);
+        if tab_cfg.legend.text_size.is_some() {
+            legend = legend.text_style(TextStyle::Name(LEGEND_TEXT_STYLE.into()));
+        }
+        plot = plot.legend(legend);
+    }
+    match (mode, time_format) {
+        (PlotMode::Time, TimeFormat::Relative) => (),
+        (PlotMode::Time, TimeFormat::WallClock(_)) => {
+            plot = plot.x_axis_formatter(move |mark, _| time_format.format(mark.value));
+        }
+        _ => {
+            plot = plot.x_axis_formatter(move |mark, _| number_format.format(mark.value));
+        }
+    }
+    plot = plot
+        .x_axis_label(tab_cfg.x_label())
+        .y_axis_label(tab_cfg.y_label());
+    let grid = tab_cfg.grid.unwrap_or(cfg.grid);
+    let minor = grid.show_minor;
+    plot = plot
+        .show_grid(grid.show_major)
+        .x_grid_spacer(grid_spacer(grid.x_ticks, minor))
+        .y_grid_spacer(grid_spacer(grid.y_ticks, minor));
+    if cfg.link_x && mode == PlotMode::Time && whole_run {
+        plot = plot
+            .link_axis(LINKED_X_GROUP, true, false)
+            .link_cursor(LINKED_X_GROUP, true, false);
+    }
+    let color_by_time = tab_cfg.color_by_time;
+    let selecting = mode.is_continuous() && whole_run && ui.input(|i| i.modifiers.shift);
+    let x_range = tab_cfg.x_range;
+    let locked = x_range.locked && x_range.start < x_range.end;
+    plot = plot
+        .allow_drag([!selecting && !locked, !selecting])
+        .allow_zoom([!locked, true])
+        .allow_scroll([!locked, true]);
+    let selection = (tab_cfg.selection)
+        .filter(|_| mode.is_continuous() && whole_run)
+        .map(|(a, b)| (a.min(b), a.max(b)));
+    let markers = match cfg.markers.get(&data.dir) {
+        Some(m) if mode == PlotMode::Time && whole_run && !cfg.hide_markers => m.as_slice(),
+        _ => &[],
+    };
+    let mut integrals = Vec::new();
+    let mut readouts = Vec::new();
+    let pinned = match mode {
+        PlotMode::Time if whole_run => tab_cfg.pinned.clone(),
+        _ => Vec::new(),
+    };
+    let mut pinned_readouts = vec![Vec::new(); pinned.len()];
+    let pan_to = if locked {
+        Some((x_range.start, x_range.end))
+    } else {
+        tab_cfg.pan_to.take()
+    };
+    let reset_view = std::mem::take(&mut tab_cfg.reset_view);
+    let inspected = tab_cfg.inspected;
+    let restore_to = restored_bounds(&data.plots[tab], tab_cfg);
+    let mut auto_bounds = false;
+    // clicking a legend entry toggles the visibility of the plot
+    let plot_id = Id::new(("plot", tab_cfg.id));
+    let synced = PlotMemory::load(ui.ctx(), plot_id).map(|mut m| {
+        let is_plot = |name: &String| tab_cfg.plots.iter().any(|p| p.name == *name);
+        m.hidden_items.retain(|name| !is_plot(name));
+        let hidden = tab_cfg.plots.iter().filter(|p| p.hidden);
+        m.hidden_items.extend(hidden.map(|p| p.name.clone()));
+        m.store(ui.ctx(), plot_id);
+    });
+    let synced = synced.is_some();
+    let plot = plot.id(plot_id);
+    let legend_text_size = tab_cfg.legend.text_size;
+    let show_plot = |ui: &mut PlotUi| {
+        if reset_view {
+            ui.set_auto_bounds(true.into());
+        } else if let Some(b) = restore_to {
+            ui.set_plot_bounds(PlotBounds::from_min_max([b[0], b[1]], [b[2], b[3]]));
+        }
+        if let Some((start, end)) = pan_to {
+            let y = ui.plot_bounds().range_y();
+            let bounds = PlotBounds::from_min_max([start, *y.start()], [end, *y.end()]);
+            ui.set_plot_bounds(bounds);
+        }
+        let cursor = (ui.pointer_coordinate().map(|p| p.x))
+            .or(inspected)
+            .filter(|_| mode == PlotMode::Time && whole_run);
+        auto_bounds = ui.auto_bounds().any();
+        let x_min = *ui.plot_bounds().range_x().start();
+        let x_max = *ui.plot_bounds().range_x().end();
+        // scatter points aren't sorted, and histograms only have few bins
+        let decimated = mode.is_continuous();
+
+        let draw_overlay = |ui: &mut PlotUi,
+                            c: &Comparison,
+                            offset: f64,
+                            label: &str,
+                            color_fn: &dyn Fn(Color32) -> Color32| {
+            if mode == PlotMode::Histogram {
+                return;
+            }
+            for (i, p) in tab_cfg.plots.iter().enumerate() {
+                let Some((_, PlotValues::Result(Ok(d)))) = c.plots.get(&(tab_cfg.id, i)) else {
+                    continue;
+                };
+                if p.hidden || d.is_empty() {
+                    continue;
+                }
+
+                let decimate_to = (decimated && d.points.len() > threshold).then_some(buckets);
+                let segments =
+                    split_at_gaps(&d.points, gap_threshold, decimate_to, &mut Vec::new());
+                let segments = segments.into_iter().map(|mut values| {
+                    for v in values.iter_mut() {
+                        v.x += offset;
+                    }
+                    if log_y {
+                        values = log_scale(values);
+                    }
+                    if let Some(map) = axis_map.filter(|_| p.right_axis) {
+                        map.apply(&mut values);
+                    }
+                    values
+                });
+
+                let name = format!("{} ({label})", p.name);
+                let color = color_fn(p.color(i, palette));
+                if mode == PlotMode::Scatter {
+                    scatter(
+                        ui,
+                        &name,
+                        color,
+                        segments.flatten().collect(),
+                        color_by_time,
+                    );
+                } else {
+                    for s in fill_gaps(segments.collect(), p.gap_fill) {
+                        let line = p.line(line_width, line_style, s);
+                        ui.line(line.name(&name).color(color));
+                    }
+                }
+            }
+        };
+        // drawn first, so it's behind the other plots
+        if let Some(b) = data.baseline.as_ref().filter(|_| whole_run) {
+            draw_overlay(ui, b, baseline_offset, "baseline", &baseline_color);
+        }
+
+        for (i, (values, p)) in data.plots[tab]
+            .iter_mut()
+            .zip(tab_cfg.plots.iter())
+            .enumerate()
+        {
+            if let PlotValues::Job(j) = values {
+                if j.is_done() {
+                    let job = std::mem::replace(values, PlotValues::empty());
+                    *values = PlotValues::Result(job.into_job().unwrap().join());
+                } else {
+                    ui.ctx().request_repaint();
+                }
+            }
+            if p.hidden {
+                // keep the legend entry, so it can be shown again
+                let line = Line::new([0.0, f64::NAN]).name(&p.name);
+                ui.line(line.color(p.color(i, palette)));
+                continue;
+            }
+
+            match values {
+                PlotValues::Result(Ok(d)) if windows.is_some() && !d.is_empty() => {
+                    let color = p.color(i, palette);
+                    for (w, (start, end)) in windows.iter().flatten().enumerate() {
+                        let range = find_plot_range(&d.points, *start, *end);
+                        let points: Vec<PlotPoint> = (d.points[range].iter())
+                            .filter(|v| v.x >= *start && v.x <= *end)
+                            .map(|v| PlotPoint::new(v.x - start, v.y))
+                            .collect();
+                        let points = if points.len() > threshold {
+                            decimate(&points, buckets)
+                        } else {
+                            points
+                        };
+                        let mut points = if log_y { log_scale(points) } else { points };
+                        if let Some(map) = axis_map.filter(|_| p.right_axis) {
+                            map.apply(&mut points);
+                        }
+                        let (name, color) = match w {
+                            0 => (format!("{} (A)", p.name), color),
+                            _ => (format!("{} (B)", p.name), comparison_color(color)),
+                        };
+                        let line = p.line(line_width, line_style, points);
+                        ui.line(line.name(name).color(color));
+                    }
+                }
+                PlotValues::Result(Ok(d)) if !d.is_empty() => {
+                    // when auto bounds are set, use full range to avoid slowly zooming out
+                    let range = if auto_bounds || mode == PlotMode::Scatter {
+                        0..d.points.len()
+                    } else {
+                        find_plot_range(&d.points, x_min, x_max)
+                    };
+
+                    let transform = |values: Vec<PlotPoint>| {
+                        let mut values = if log_y { log_scale(values) } else { values };
+                        if let Some(map) = axis_map.filter(|_| p.right_axis) {
+                            map.apply(&mut values);
+                        }
+                        values
+                    };
+                    let display = |points: &[PlotPoint]| {
+                        let visible = &points[range.clone()];
+                        let values = if decimated && visible.len() > threshold {
+                            decimate(visible, buckets)
+                        } else {
+                            visible.to_vec()
+                        };
+                        transform(values)
+                    };
+                    // the gaps are found before decimating, which would hide them
+                    let display_segments = |points: &[PlotPoint], gaps: &mut Vec<(f64, f64)>| {
+                        let visible = &points[range.clone()];
+                        let decimate_to =
+                            (decimated && visible.len() > threshold).then_some(buckets);
+                        (split_at_gaps(visible, gap_threshold, decimate_to, gaps).into_iter())
+                            .map(&transform)
+                            .collect::<Vec<_>>()
+                    };
+
+                    let color = p.color(i, palette);
+                    match mode {
+                        PlotMode::Scatter => {
+                            let values = display(&d.points);
+                            scatter(ui, &p.name, color, values, color_by_time);
+                        }
+                        PlotMode::Histogram => {
+                            // the share of each bin has to be known before the log scale
+                            let bins = if percent {
+                                histogram_percent(&d.points)
+                            } else {
+                                d.points.clone()
+                            };
+                            let bars = histogram_bars(display(&bins));
+                            ui.bar_chart(BarChart::new(bars).name(&p.name).color(color));
+                        }
+                        PlotMode::Time | PlotMode::Spectrum => {
+                            let segments = display_segments(&d.points, &mut gaps);
+                            if let Some((start, end)) = selection.filter(|_| p.fill) {
+                                let selected = (segments.iter().flatten())
+                                    .filter(|v| v.x >= start && v.x <= end)
+                                    .copied()
+                                    .collect();
+                                let area = Line::new(PlotPoints::Owned(selected));
+                                ui.line(area.width(0.0).fill(0.0).color(color));
+                            }
+                            let fill_all = p.fill && selection.is_none();
+                            let markers = p.markers.filter(|_| mode == PlotMode::Time);
+                            if let Some(m) = markers {
+                                // at the samples, not at the points of the decimated or held line
+                                let visible = &d.points[range.clone()];
+                                let samples = if visible.len() > MAX_SAMPLE_MARKERS {
+                                    decimate(visible, MAX_SAMPLE_MARKERS / 2)
+                                } else {
+                                    visible.to_vec()
+                                };
+                                let points = Points::new(PlotPoints::Owned(transform(samples)))
+                                    .shape(m.shape.to_plot_shape())
+                                    .radius(m.size)
+                                    .filled(true);
+                                ui.points(points.name(&p.name).color(color));
+                            }
+                            for s in fill_gaps(segments, p.gap_fill) {
+                                let mut line = p.line(line_width, line_style, s);
+                                // still drawn for the fill and the hover readout
+                                if markers.is_some_and(|m| !m.line) {
+                                    line = line.width(0.0);
+                                }
+                                if fill_all {
+                                    line = line.fill(0.0);
+                                }
+                                ui.line(line.name(&p.name).color(color));
+                            }
+                            if let Some(range) = p
+                                .valid_range
+                                .filter(|_| mode == PlotMode::Time && !p.derivative)
+                            {
+                                invalid_points(ui, p, &d.invalid, range, log_y, axis_map);
+                            }
+                        }
+                    }
+
+                    // without a selection, filled plots show the integral of the visible range
+                    let integrated = match selection {
+                        Some(s) => Some(s),
+                        None if p.fill && mode.is_continuous() => Some((x_min, x_max)),
+                        None => None,
+                    };
+                    if let Some((start, end)) = integrated {
+                        let integral = analysis::integral(&d.points, start, end);
+                        integrals.push((p.name.clone(), color, integral));
+                    }
+                    if let Some(x) = cursor {
+                        let y = analysis::interpolate(&d.points, x);
+                        readouts.push((p.name.clone(), color, y));
+                    }
+                    for (&x, r) in pinned.iter().zip(pinned_readouts.iter_mut()) {
+                        let y = analysis::interpolate(&d.points, x);
+                        r.push((p.name.clone(), color, y));
+                    }
+
+                    if let Some(smoothed) = &d.smoothed {
+                        let name = format!("{} (average)", p.name);
+                        let color = color.gamma_multiply(0.5);
+                        if mode == PlotMode::Scatter {
+                            scatter(ui, &name, color, display(smoothed), false);
+                        } else {
+                            let segments = display_segments(smoothed, &mut Vec::new());
+                            for s in fill_gaps(segments, p.gap_fill) {
+                                let line = p.line(line_width, line_style, s);
+                                ui.line(line.name(&name).color(color));
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    let line = Line::new([0.0, f64::NAN]);
+                    ui.line(line.name(&p.name).color(p.color(i, palette)));
+                }
+            }
+        }
+
+        if let Some(c) = data.comparison.as_ref().filter(|_| whole_run) {
+            draw_overlay(ui, c, comparison_offset, &c.name, &comparison_color);
+        }
+
+        if let Some((start, end)) = selection {
+            let color = ui.ctx().style().visuals.selection.bg_fill;
+            ui.vline(VLine::new(start).color(color));
+            ui.vline(VLine::new(end).color(color));
+        }
+        if let Some(x) = cursor {
+            let color = ui.ctx().style().visuals.weak_text_color();
+            ui.vline(VLine::new(x).color(color).width(1.0));
+        }
+        for &x in pinned.iter() {
+            let color = ui.ctx().style().visuals.strong_text_color();
+            ui.vline(VLine::new(x).color(color).width(1.0));
+        }
+        if show_seams {
+            for &x in data.seams.iter() {
+                let color = ui.ctx().style().visuals.weak_text_color();
+                let style = egui_plot::LineStyle::Dashed {
+                    length: DASH_LENGTH,
+                };
+                ui.vline(VLine::new(x).color(color).style(style));
+            }
+        }
+        let top = *ui.plot_bounds().range_y().end();
+        for m in markers {
+            let color = ui.ctx().style().visuals.warn_fg_color;
+            ui.vline(VLine::new(m.time).color(color));
+            let text = Text::new(PlotPoint::new(m.time, top), &m.label)
+                .anchor(Align2::LEFT_TOP)
+                .color(color);
+            ui.text(text);
+        }
+    };
+    // the legend only takes a text style, which is only added to the style of this plot
+    let resp = ui
+        .scope(|ui| {
+            if let Some(size) = legend_text_size {
+                let style = TextStyle::Name(LEGEND_TEXT_STYLE.into());
+                (ui.style_mut().text_styles).insert(style, FontId::proportional(size));
+            }
+            plot.show(ui, show_plot)
+        })
+        .inner;
+    if tab == cfg.selected_tab {
+        cfg.plot_rect = Some(resp.response.rect);
+    }
+    if let Some(pos) = resp.response.hover_pos() {
+        if mode == PlotMode::Time && whole_run {
+            cfg.cursor = Some(resp.transform.value_from_position(pos).x);
+        }
+    }
+
+    if cfg.shade_gaps {
+        let rect = resp.response.rect;
+        let fill = ui.visuals().warn_fg_color.gamma_multiply(0.15);
+        for (start, end) in gaps {
+            let left = resp.transform.position_from_point_x(start).max(rect.left());
+            let right = resp.transform.position_from_point_x(end).min(rect.right());
+            if left < right {
+                let gap = Rect::from_x_y_ranges(left..=right, rect.y_range());
+                ui.painter().rect_filled(gap, 0.0, fill);
+            }
+        }
+    }
+
+    if mode == PlotMode::Time && whole_run {
+        let rect = resp.response.rect;
+        let fill = ui.visuals().error_fg_color.gamma_multiply(0.15);
+        for &(i, start, end) in data.backwards.iter() {
+            let offset = cfg.time_offset(&data.dir, i);
+            let x = |t: u32| (t as i64 + offset as i64) as f64 / 1000.0;
+            let left = resp
+                .transform
+                .position_from_point_x(x(start))
+                .max(rect.left());
+            let right = resp
+                .transform
+                .position_from_point_x(x(end))
+                .min(rect.right());
+            if left < right {
+                let region = Rect::from_x_y_ranges(left..=right, rect.y_range());
+                ui.painter().rect_filled(region, 0.0, fill);
+            }
+        }
+    }
+
+    // the memory doesn't exist before the plot was first shown
+    if synced {
+        if let Some(m) = PlotMemory::load(ui.ctx(), plot_id) {
+            for p in tab_cfg.plots.iter_mut() {
+                p.hidden = m.hidden_items.contains(&p.name);
+            }
+        }
+    }
+
+    let hovered = resp.response.hover_pos().filter(|_| show_coordinates);
+    if let Some(pos) = hovered {
+        let frame = resp.transform.frame();
+        let value = resp.transform.value_from_position(pos);
+        let text = coordinates(value.x, value.y).join("   ");
+        let pos = frame.left_bottom() + Vec2::new(6.0, -4.0);
+        let font = TextStyle::Monospace.resolve(ui.style());
+        let color = ui.visuals().text_color();
+        ui.painter()
+            .text(pos, Align2::LEFT_BOTTOM, text, font, color);
+    }
+
+    let title = &tab_cfg.labels.title;
+    if !title.is_empty() {
+        let pos = resp.response.rect.center_top() + Vec2::new(0.0, 4.0);
+        let font = TextStyle::Heading.resolve(ui.style());
+        let color = ui.visuals().strong_text_color();
+        ui.painter()
+            .text(pos, Align2::CENTER_TOP, title, font, color);
+    }
+
+    if let Some(x) = resp.response.hover_pos().filter(|_| !readouts.is_empty()) {
+        let x = resp.transform.value_from_position(x).x;
+        resp.response.clone().on_hover_ui_at_pointer(|ui| {
+            cursor_readout(ui, &time_format.format(x), &readouts, number_format);
+        });
+    }
+
+    let rect = resp.response.rect;
+    let mut dismissed = None;
+    for (i, (&x, readouts)) in pinned.iter().zip(pinned_readouts.iter()).enumerate() {
+        let pos_x = resp.transform.position_from_point_x(x);
+        if !rect.x_range().contains(pos_x) {
+            continue;
+        }
+        Area::new(Id::new((tab_cfg.id, "pinned_readout", i)))
+            .fixed_pos(Pos2::new(pos_x + 4.0, rect.top() + 4.0))
+            .order(Order::Foreground)
+            .show(ui.ctx(), |ui| {
+                Frame::popup(ui.style()).show(ui, |ui| {
+                    if ui.add(Button::new("🗙").frame(false)).clicked() {
+                        dismissed = Some(i);
+                    }
+                    cursor_readout(ui, &time_format.format(x), readouts, number_format);
+                });
+            });
+    }
+    if let Some(i) = dismissed {
+        tab_cfg.pinned.remove(i);
+    }
+
+    let bounds = resp.transform.bounds();
+    if !tab_cfg.restore_bounds {
+        let [min_x, min_y] = bounds.min();
+        let [max_x, max_y] = bounds.max();
+        tab_cfg.bounds = (!auto_bounds).then_some([min_x, min_y, max_x, max_y]);
+    }
+    let visible_x = Some((bounds.min()[0], bounds.max()[0]));
+    if tab_cfg.visible_x != visible_x {
+        tab_cfg.visible_x = visible_x;
+        ui.ctx().request_repaint();
+    }
+
+    let add_marker = mode == PlotMode::Time && whole_run && ui.input(|i| i.modifiers.command);
+    if add_marker && resp.response.clicked() {
+        if let Some(pos) = resp.response.interact_pointer_pos() {
+            let markers = cfg.markers.entry(data.dir.clone()).or_default();
+            markers.push(Marker {
+                time: resp.transform.value_from_position(pos).x,
+                label: format!("Marker {}", markers.len() + 1),
+            });
+            markers.sort_by(|a, b| a.time.total_cmp(&b.time));
+        }
+    }
+
+    let pin = mode == PlotMode::Time && whole_run && ui.input(|i| i.modifiers.alt);
+    if pin && resp.response.clicked() {
+        if let Some(pos) = resp.response.interact_pointer_pos() {
+            tab_cfg
+                .pinned
+                .push(resp.transform.value_from_position(pos).x);
+        }
+    }
+
+    if selecting {
+        let pointer = resp.response.interact_pointer_pos();
+        let x = pointer.map(|p| resp.transform.value_from_position(p).x);
+        if resp.response.clicked() {
+            tab_cfg.selection = None;
+        } else if let Some(x) = x {
+            if resp.response.drag_started() {
+                tab_cfg.selection = Some((x, x));
+            } else if let Some((_, end)) = &mut tab_cfg.selection {
+                if resp.response.dragged() {
+                    *end = x;
+                }
+            }
+        }
+    }
+
+    let integrated = selection.or(visible_x.filter(|_| !integrals.is_empty()));
+    if let Some((start, end)) = integrated {
+        let [start_text, end_text] = match mode {
+            PlotMode::Time => [start, end].map(|v| time_format.format(v)),
+            _ => [start, end].map(|v| number_format.format(v)),
+        };
+        let range = match mode {
+            PlotMode::Spectrum => format!("{start_text} - {end_text} Hz"),
+            _ => format!("{start_text} - {end_text}"),
+        };
+        let rect = resp.response.rect;
+        integral_readout(ui, rect, &range, &integrals, number_format);
+    }
+}
+
+fn scatter(ui: &mut PlotUi, name: &str, color: Color32, values: Vec<PlotPoint>, by_time: bool) {
+    if !by_time {
+        let points = Points::new(PlotPoints::Owned(values)).radius(SCATTER_RADIUS);
+        ui.points(points.name(name).color(color));
+        return;
+    }
+
+    // points are in chronological order
+    let chunk_size = values.len().div_ceil(TIME_COLOR_STEPS).max(1);
+    for (k, chunk) in values.chunks(chunk_size).enumerate() {
+        let mut hsva = Hsva::from(color);
+        hsva.v *= 0.3 + 0.7 * k as f32 / (TIME_COLOR_STEPS - 1) as f32;
+        let points = Points::new(PlotPoints::Owned(chunk.to_vec())).radius(SCATTER_RADIUS);
+        ui.points(points.name(name).color(Color32::from(hsva)));
+    }
+}
+
+/// Marks samples outside of the valid range at its bounds, so they don't stretch the plot
+fn invalid_points(
+    ui: &mut PlotUi,
+    plot: &NamedPlot,
+    invalid: &[PlotPoint],
+    (min, max): (f64, f64),
+    log_y: bool,
+    axis_map: Option<AxisMap>,
+) {
+    if invalid.is_empty() {
+        return;
+    }
+    let clamped = (invalid.iter())
+        .map(|v| PlotPoint::new(v.x, v.y.clamp(min, max)))
+        .collect();
+    let mut values = if log_y { log_scale(clamped) } else { clamped };
+    if let Some(map) = axis_map.filter(|_| plot.right_axis) {
+        map.apply(&mut values);
+    }
+    let name = format!("{} ({} out of range)", plot.name, invalid.len());
+    let color = ui.ctx().style().visuals.warn_fg_color;
+    let points = Points::new(PlotPoints::Owned(values)).radius(SCATTER_RADIUS * 2.0);
+    ui.points(points.name(name).color(color));
+}
+
+/// Bars for the bins of a histogram, which are evenly spaced
+fn histogram_bars(bins: Vec<PlotPoint>) -> Vec<Bar> {
+    let width = match bins.as_slice() {
+        [a, b, ..] => b.x - a.x,
+        _ => 1.0,
+    };
+    (bins.iter())
+        .map(|b| Bar::new(b.x, b.y).width(width))
+        .collect()
+}
+
+/// The counts of all bins as a percentage of their sum
+pub fn histogram_percent(bins: &[PlotPoint]) -> Vec<PlotPoint> {
+    let total: f64 = bins.iter().map(|b| b.y).sum();
+    if total <= 0.0 {
+        return bins.to_vec();
+    }
+    (bins.iter())
+        .map(|b| PlotPoint::new(b.x, 100.0 * b.y / total))
+        .collect()
+}
+
+/// The saved bounds once all values are evaluated, if they still overlap the range of the values
+fn restored_bounds(values: &[PlotValues], tab_cfg: &mut TabConfig) -> Option<[f64; 4]> {
+    if !tab_cfg.restore_bounds || values.iter().any(|v| matches!(v, PlotValues::Job(_))) {
+        return None;
+    }
+    tab_cfg.restore_bounds = false;
+
+    let bounds = tab_cfg.bounds?;
+    let (min_x, max_x) = (values.iter())
+        .filter_map(|v| match v {
+            PlotValues::Result(Ok(d)) => Some(d.points.iter()),
+            _ => None,
+        })
+        .flatten()
+        .filter(|p| p.x.is_finite())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), p| {
+            (min.min(p.x), max.max(p.x))
+        });
+    (bounds[0] < max_x && bounds[2] > min_x).then_some(bounds)
+}
+
+/// Splits the samples where the x distance between two of them exceeds the threshold, and
+/// decimates the segments to about `buckets` points in total. The ranges of the gaps are added to
+/// `gaps` for shading.
+fn split_at_gaps(
+    values: &[PlotPoint],
+    threshold: Option<f64>,
+    buckets: Option<usize>,
+    gaps: &mut Vec<(f64, f64)>,
+) -> Vec<Vec<PlotPoint>> {
+    let mut bounds = vec![0];
+    if let Some(threshold) = threshold {
+        for i in analysis::gaps(values, threshold) {
+            gaps.push((values[i].x, values[i + 1].x));
+            bounds.push(i + 1);
+        }
+    }
+    bounds.push(values.len());
+
+    (bounds.windows(2))
+        .map(|w| {
+            let s = &values[w[0]..w[1]];
+            match buckets {
+                // each segment gets its share of the buckets
+                Some(b) => decimate(s, (b * s.len() / values.len()).max(1)),
+                None => s.to_vec(),
+            }
+        })
+        .collect()
+}
+
+/// Breaks or fills the line between the segments of [`split_at_gaps`], as configured by `fill`.
+/// The points inserted to hold a value aren't samples, so markers are drawn from the samples.
+fn fill_gaps(segments: Vec<Vec<PlotPoint>>, fill: GapFill) -> Vec<Vec<PlotPoint>> {
+    let segments = segments.into_iter().filter(|s| !s.is_empty());
+    match fill {
+        GapFill::Break => segments.collect(),
+        GapFill::Interpolate => vec![segments.flatten().collect()],
+        GapFill::Hold => {
+            let mut held: Vec<PlotPoint> = Vec::new();
+            for s in segments {
+                if let Some(last) = held.last() {
+                    held.push(PlotPoint::new(s[0].x, last.y));
+                }
+                held.extend(s);
+            }
+            vec![held]
+        }
+    }
+}
+
+/// Whole timeline with the visible range highlighted, which can be moved by dragging
+fn overview(ui: &mut Ui, values: &[PlotValues], tab_cfg: &mut TabConfig, palette: Palette) {
+    let buckets = (ui.ctx().pixels_per_point() * ui.available_width()) as usize;
+    let plot = Plot::new((tab_cfg.id, "overview"))
+        .height(OVERVIEW_HEIGHT)
+        .show_axes(false)
+        .show_grid(false)
+        .show_x(false)
+        .show_y(false)
+        .allow_drag(false)
+        .allow_zoom(false)
+        .allow_scroll(false)
+        .allow_boxed_zoom(false)
+        .allow_double_click_reset(false);
+    let resp = plot.show(ui, |ui| {
+        for (i, (v, p)) in values.iter().zip(tab_cfg.plots.iter()).enumerate() {
+            let PlotValues::Result(Ok(d)) = v else {
+                continue;
+            };
+            if p.hidden {
+                continue;
+            }
+            let points = decimate(&d.points, buckets.max(1));
+            let points = if tab_cfg.log_y {
+                log_scale(points)
+            } else {
+                points
+            };
+            ui.line(Line::new(PlotPoints::Owned(points)).color(p.color(i, palette)));
+        }
+    });
+
+    let Some((start, end)) = tab_cfg.visible_x else {
+        return;
+    };
+    let rect = resp.response.rect;
+    let left = resp.transform.position_from_point_x(start).max(rect.left());
+    let right = resp.transform.position_from_point_x(end).min(rect.right());
+    if left < right {
+        let window = Rect::from_x_y_ranges(left..=right, rect.y_range());
+        let visuals = &ui.visuals().selection;
+        let fill = visuals.bg_fill.gamma_multiply(0.3);
+        ui.painter().rect(window, 0.0, fill, visuals.stroke);
+    }
+
+    let pointer = resp.response.interact_pointer_pos();
+    if let Some(pos) = pointer.filter(|_| resp.response.dragged() || resp.response.clicked()) {
+        let center = resp.transform.value_from_position(pos).x;
+        let half_width = 0.5 * (end - start);
+        tab_cfg.pan_to = Some((center - half_width, center + half_width));
+        ui.ctx().request_repaint();
+    }
+}
 
-                        match values {
-                            PlotValues::Result(Ok(d)) if !d.is_empty() => {
-                                // when auto bounds are set, use full range to avoid slowly zooming out
-                                let range = if auto_bounds {
-                                    0..d.len()
-                                } else {
-                                    find_plot_range(d, x_min, x_max)
-                                };
+fn statistics_panel(
+    ui: &mut Ui,
+    values: &[PlotValues],
+    tab_cfg: &TabConfig,
+    number_format: NumberFormat,
+    palette: Palette,
+) {
+    CollapsingHeader::new("Statistics")
+        .id_source(tab_cfg.id)
+        .show(ui, |ui| {
+            let ranges = match (tab_cfg.compared_windows(), tab_cfg.visible_x) {
+                (Some([a, b]), _) => vec![(" (A)", a), (" (B)", b)],
+                (None, Some(visible)) => vec![("", visible)],
+                (None, None) => return,
+            };
+
+            Grid::new("statistics_grid").striped(true).show(ui, |ui| {
+                for label in ["", "min", "max", "mean", "std dev"] {
+                    ui.strong(label);
+                }
+                ui.end_row();
+
+                for (i, (v, p)) in values.iter().zip(tab_cfg.plots.iter()).enumerate() {
+                    let PlotValues::Result(Ok(d)) = v else {
+                        continue;
+                    };
+                    if p.hidden {
+                        continue;
+                    }
 
-                                let values = subsample_plot(&d[range], chunk_size);
-                                ui.line(Line::new(PlotPoints::Owned(values)).name(&p.name));
+                    for (suffix, (x_min, x_max)) in ranges.iter().copied() {
+                        ui.colored_label(p.color(i, palette), format!("{}{suffix}", p.name));
+                        let range = match tab_cfg.mode {
+                            PlotMode::Time | PlotMode::Spectrum => {
+                                find_plot_range(&d.points, x_min, x_max)
+                            }
+                            PlotMode::Scatter | PlotMode::Histogram => 0..d.points.len(),
+                        };
+                        let visible: Vec<_> = (d.points[range].iter())
+                            .filter(|p| p.x >= x_min && p.x <= x_max)
+                            .copied()
+                            .collect();
+                        match analysis::stats(&visible) {
+                            Some(s) => {
+                                for v in [s.min, s.max, s.mean, s.std_dev] {
+                                    ui.monospace(number_format.format(v));
+                                }
+                            }
+                            None => {
+                                ui.label("no values");
                             }
-                            _ => ui.line(Line::new([0.0, f64::NAN]).name(&p.name)),
                         }
+                        ui.end_row();
+                    }
+                }
+            });
+        });
+}
+
+/// Lists the values of the plots in the visible time range, returns the time of a clicked row
+fn values_table(
+    ui: &mut Ui,
+    values: &[PlotValues],
+    tab_cfg: &TabConfig,
+    (time_format, number_format): (TimeFormat, NumberFormat),
+    palette: Palette,
+) -> Option<f64> {
+    let mut clicked = None;
+    CollapsingHeader::new("Values")
+        .id_source(("values", tab_cfg.id))
+        .show(ui, |ui| {
+            let Some((x_min, x_max)) = tab_cfg.visible_x else {
+                return;
+            };
+            let plots: Vec<_> = (values.iter().zip(tab_cfg.plots.iter()).enumerate())
+                .filter(|(_, (_, p))| !p.hidden)
+                .filter_map(|(i, (v, p))| match v {
+                    PlotValues::Result(Ok(d)) if !d.is_empty() => Some((i, d, p)),
+                    _ => None,
+                })
+                .collect();
+            // the times of the first plot, the others are interpolated
+            let Some((_, first, _)) = plots.first() else {
+                ui.weak("no values");
+                return;
+            };
+            let range = find_plot_range(&first.points, x_min, x_max);
+            let times: Vec<f64> = (first.points[range].iter())
+                .map(|p| p.x)
+                .filter(|&x| x >= x_min && x <= x_max)
+                .collect();
+
+            let mut table = TableBuilder::new(ui)
+                .max_scroll_height(VALUES_TABLE_HEIGHT)
+                .striped(true)
+                .column(Column::exact(VALUES_COLUMN_WIDTH));
+            for _ in plots.iter() {
+                table = table.column(Column::exact(VALUES_COLUMN_WIDTH));
+            }
+            table
+                .header(VALUES_ROW_HEIGHT, |mut header| {
+                    header.col(|ui| {
+                        ui.strong("time");
+                    });
+                    for (i, _, p) in plots.iter() {
+                        header.col(|ui| {
+                            ui.colored_label(p.color(*i, palette), &p.name);
+                        });
                     }
+                })
+                .body(|body| {
+                    body.rows(VALUES_ROW_HEIGHT, times.len(), |mut row| {
+                        let t = times[row.index()];
+                        row.col(|ui| {
+                            let selected = tab_cfg.inspected == Some(t);
+                            let label = ui.selectable_label(selected, time_format.format(t));
+                            if label.clicked() {
+                                clicked = Some(t);
+                            }
+                        });
+                        for (_, d, _) in plots.iter() {
+                            row.col(|ui| match analysis::interpolate(&d.points, t) {
+                                Some(y) => {
+                                    ui.monospace(number_format.format(y));
+                                }
+                                None => {
+                                    ui.weak("-");
+                                }
+                            });
+                        }
+                    });
                 });
         });
+    clicked
+}
+
+fn cursor_readout(
+    ui: &mut Ui,
+    time: &str,
+    readouts: &[(String, Color32, Option<f64>)],
+    number_format: NumberFormat,
+) {
+    ui.strong(time);
+    Grid::new("cursor_readout_grid").show(ui, |ui| {
+        for (name, color, y) in readouts {
+            ui.colored_label(*color, name);
+            match y {
+                Some(y) => ui.monospace(number_format.format(*y)),
+                None => ui.weak("-"),
+            };
+            ui.end_row();
+        }
+    });
+}
+
+fn integral_readout(
+    ui: &mut Ui,
+    plot_rect: Rect,
+    range: &str,
+    integrals: &[(String, Color32, f64)],
+    number_format: NumberFormat,
+) {
+    let rect = plot_rect.shrink(40.0);
+    ui.allocate_ui_at_rect(rect, |ui| {
+        Frame::popup(ui.style()).show(ui, |ui| {
+            ui.label(RichText::new(range).strong());
+            Grid::new("integrals").show(ui, |ui| {
+                for (name, color, integral) in integrals {
+                    ui.colored_label(*color, format!("∫ {name}"));
+                    ui.monospace(number_format.format(*integral));
+                    ui.end_row();
+                }
+            });
+        });
+    });
 }
 
 fn input_sidebar(ui: &mut Ui, data: &mut PlotData, cfg: &mut Config) {
@@ -537,7 +2877,12 @@ fn input_sidebar(ui: &mut Ui, data: &mut PlotData, cfg: &mut Config) {
         _ => None,
     };
 
+    let mut names = eval::variable_names(&data.streams, &cfg.constants);
+    names.sort();
+    names.dedup();
+
     let mut i = 0;
+    let palette = cfg.palette;
     while i < cfg.tabs[cfg.selected_tab].plots.len() {
         let plot = &mut cfg.tabs[cfg.selected_tab].plots[i];
         let values = &data.plots[cfg.selected_tab][i];
@@ -548,7 +2893,7 @@ fn input_sidebar(ui: &mut Ui, data: &mut PlotData, cfg: &mut Config) {
                 let id = Id::new("plot").with(i);
                 let layer_id = LayerId::new(Order::Tooltip, id);
                 ui.with_layer_id(layer_id, |ui| {
-                    expr_inputs(ui, plot, values, i, &mut cfg.dragged_plot);
+                    expr_inputs(ui, plot, values, i, &mut cfg.dragged_plot, &names, palette);
                 });
                 let transform = TSTransform::new(Vec2::new(0.0, dist), 1.0);
                 ui.ctx().transform_layer_shapes(layer_id, transform);
@@ -559,28 +2904,36 @@ fn input_sidebar(ui: &mut Ui, data: &mut PlotData, cfg: &mut Config) {
                 let id = Id::new("plot").with(i);
                 let layer_id = LayerId::new(Order::Foreground, id);
                 ui.with_layer_id(layer_id, |ui| {
-                    expr_inputs(ui, plot, values, i, &mut cfg.dragged_plot);
+                    expr_inputs(ui, plot, values, i, &mut cfg.dragged_plot, &names, palette);
                 });
                 let offset = -dist.signum() * plot_distance;
                 let transform = TSTransform::new(Vec2::new(0.0, offset), 1.0);
                 ui.ctx().transform_layer_shapes(layer_id, transform);
             }
             _ => {
-                input = Some(expr_inputs(ui, plot, values, i, &mut cfg.dragged_plot));
+                input = Some(expr_inputs(
+                    ui,
+                    plot,
+                    values,
+                    i,
+                    &mut cfg.dragged_plot,
+                    &names,
+                    palette,
+                ));
             }
         };
 
-        let tab_cfg = &mut cfg.tabs[cfg.selected_tab];
-        let plot = &mut tab_cfg.plots[i];
+        let tab = cfg.selected_tab;
         match input {
             Some(input) if input.removed => {
-                tab_cfg.plots.remove(i);
-                let _ = data.plots[cfg.selected_tab].remove(i);
+                cfg.tabs[tab].plots.remove(i);
+                let _ = data.plots[tab].remove(i);
             }
             Some(input) => {
-                if input.x_changed || input.y_changed {
-                    data.plots[cfg.selected_tab][i] =
-                        PlotValues::Job(Job::start(plot.expr.clone(), Arc::clone(&data.streams)));
+                if input.x_changed || input.y_changed || input.options_changed {
+                    let tab_cfg = &cfg.tabs[tab];
+                    let plot = &tab_cfg.plots[i];
+                    data.plots[tab][i] = start_job(cfg, tab_cfg, plot, &data.dir, &data.streams);
                 }
                 i += 1;
             }
@@ -616,13 +2969,412 @@ fn input_sidebar(ui: &mut Ui, data: &mut PlotData, cfg: &mut Config) {
                 });
             });
         });
+
+        if ui.button("Reset colors of this tab").clicked() {
+            for p in cfg.tabs[cfg.selected_tab].plots.iter_mut() {
+                p.color = None;
+            }
+        }
+    });
+
+    ui.add_space(10.0);
+    let mut changed = constants_editor(ui, &mut cfg.constants);
+    changed |= time_offsets_editor(ui, &mut cfg.run_time_offsets, &data.dir, &data.streams);
+    changed |= sample_rates_editor(ui, &mut cfg.run_sample_rates, &data.dir, &data.streams);
+    if changed {
+        for tab in 0..cfg.tabs.len() {
+            restart_jobs(data, cfg, tab);
+        }
+    }
+    notes_editor(ui, &mut cfg.notes, &data.dir);
+    markers_editor(ui, &mut cfg.markers, &data.dir);
+    crossings_panel(ui, data, cfg);
+    start_time_editor(ui, &mut cfg.start_times, data);
+    timestamps_panel(ui, &data.streams);
+}
+
+/// Decoded timestamps of the streams, to diagnose parsing issues
+fn timestamps_panel(ui: &mut Ui, streams: &[LogStream]) {
+    CollapsingHeader::new("Timestamps").show(ui, |ui| {
+        Grid::new("timestamps").striped(true).show(ui, |ui| {
+            for label in [
+                "",
+                "version",
+                "samples",
+                "first",
+                "last",
+                "avg delta",
+                "backwards",
+            ] {
+                ui.strong(label);
+            }
+            ui.end_row();
+
+            for s in streams.iter() {
+                ui.label(RichText::new(stream_name(s).unwrap_or("-")).monospace());
+                ui.label(s.version.to_string());
+                ui.label(s.len().to_string());
+                match (s.time.first(), s.time.last()) {
+                    (Some(&first), Some(&last)) => {
+                        ui.monospace(format_time(first as f64 / 1000.0));
+                        ui.monospace(format_time(last as f64 / 1000.0));
+                    }
+                    _ => {
+                        ui.weak("-");
+                        ui.weak("-");
+                    }
+                }
+                match s.sample_rate() {
+                    Some(rate) => ui.monospace(format!("{:.2} ms", 1000.0 / rate)),
+                    None => ui.weak("-"),
+                };
+                let backwards = s.backwards();
+                if backwards > 0 {
+                    ui.colored_label(ERROR_RED, backwards.to_string())
+                        .on_hover_text(
+                            "Timestamps smaller than the previous one, e.g. a wraparound",
+                        );
+                } else {
+                    ui.label("0");
+                }
+                ui.end_row();
+            }
+        });
+    });
+}
+
+fn start_time_editor(ui: &mut Ui, start_times: &mut HashMap<PathBuf, i64>, data: &PlotData) {
+    CollapsingHeader::new("Start time").show(ui, |ui| {
+        let file_start = file_start(&data.streams);
+        let mut custom = start_times.contains_key(&data.dir);
+        let checkbox = ui
+            .checkbox(&mut custom, "override")
+            .on_hover_text("Enter the start of the run, e.g. to match external logs");
+        if checkbox.changed() {
+            if custom {
+                let start = file_start.unwrap_or_else(|| Local::now().timestamp());
+                start_times.insert(data.dir.clone(), start);
+            } else {
+                start_times.remove(&data.dir);
+            }
+        }
+
+        match start_times.get_mut(&data.dir) {
+            Some(start) => {
+                let drag = DragValue::new(start)
+                    .custom_formatter(|v, _| util::format_date_time(v as i64))
+                    .custom_parser(util::parse_date_time);
+                ui.add(drag);
+            }
+            None => match file_start {
+                Some(start) => {
+                    ui.label(util::format_date_time(start));
+                }
+                None => {
+                    ui.weak("The files contain no start time");
+                }
+            },
+        }
+    });
+}
+
+/// Start time stored in the first file of the run as unix timestamp in seconds, the time at zero
+fn file_start(streams: &[LogStream]) -> Option<i64> {
+    let start = streams.first()?.start?;
+    Some(start.and_utc().timestamp())
+}
+
+/// Lists the times where a plot of the selected tab crosses a threshold
+fn crossings_panel(ui: &mut Ui, data: &PlotData, cfg: &mut Config) {
+    CollapsingHeader::new("Threshold crossings").show(ui, |ui| {
+        let time_format = cfg.time_format(data);
+        let tab = cfg.selected_tab;
+        let tab_cfg = &mut cfg.tabs[tab];
+        if tab_cfg.mode != PlotMode::Time {
+            ui.weak("Only available in time mode");
+            return;
+        }
+
+        let crossings_cfg = &mut tab_cfg.crossings;
+        ComboBox::from_label("plot")
+            .selected_text(crossings_cfg.plot.as_str())
+            .show_ui(ui, |ui| {
+                for p in tab_cfg.plots.iter() {
+                    ui.selectable_value(&mut crossings_cfg.plot, p.name.clone(), &p.name);
+                }
+            });
+        ui.horizontal(|ui| {
+            ui.label("threshold");
+            ui.add(DragValue::new(&mut crossings_cfg.threshold).speed(0.1));
+        });
+
+        let name = crossings_cfg.plot.clone();
+        let threshold = crossings_cfg.threshold;
+        let index = tab_cfg.plots.iter().position(|p| p.name == name);
+        let Some((i, PlotValues::Result(Ok(d)))) =
+            index.and_then(|i| Some((i, data.plots[tab].get(i)?)))
+        else {
+            ui.weak("Select a plot with values");
+            return;
+        };
+        // the points are replaced when the plot is evaluated again
+        let key = (i, d.points.as_ptr() as usize, d.points.len(), threshold);
+        let cache = match crossings_cfg.cache.take() {
+            Some(c) if c.key == key => c,
+            _ => CrossingsCache {
+                key,
+                crossings: analysis::crossings(&d.points, threshold),
+            },
+        };
+        let crossings = cache.crossings.as_slice();
+        let (rising, falling): (Vec<_>, Vec<_>) = crossings.iter().partition(|c| c.rising);
+
+        let mut clicked = None;
+        Grid::new("crossings").striped(true).show(ui, |ui| {
+            ui.strong(format!("rising ({})", rising.len()));
+            ui.strong(format!("falling ({})", falling.len()));
+            ui.end_row();
+            let rows = rising.len().max(falling.len());
+            for k in 0..rows.min(MAX_LISTED_CROSSINGS) {
+                for c in [rising.get(k), falling.get(k)] {
+                    match c {
+                        Some(c) => {
+                            if ui.link(time_format.format(c.time)).clicked() {
+                                clicked = Some(c.time);
+                            }
+                        }
+                        None => {
+                            ui.label("");
+                        }
+                    }
+                }
+                ui.end_row();
+            }
+        });
+        let hidden = rising
+            .len()
+            .max(falling.len())
+            .saturating_sub(MAX_LISTED_CROSSINGS);
+        if hidden > 0 {
+            ui.weak(format!("{hidden} more rows"));
+        }
+        // center the plot on the clicked crossing
+        if let (Some(t), Some((min, max))) = (clicked, tab_cfg.visible_x) {
+            let half = 0.5 * (max - min);
+            tab_cfg.pan_to = Some((t - half, t + half));
+        }
+
+        let add = ui.add_enabled(!crossings.is_empty(), Button::new("Add as markers"));
+        if add.clicked() {
+            let threshold = cfg.number_format.format(threshold);
+            let markers = cfg.markers.entry(data.dir.clone()).or_default();
+            for c in crossings.iter() {
+                let op = if c.rising { ">" } else { "<" };
+                let label = format!("{name} {op} {threshold}");
+                // adding them again doesn't duplicate them
+                if !markers.iter().any(|m| m.time == c.time && m.label == label) {
+                    markers.push(Marker {
+                        time: c.time,
+                        label,
+                    });
+                }
+            }
+            markers.sort_by(|a, b| a.time.total_cmp(&b.time));
+        }
+        cfg.tabs[tab].crossings.cache = Some(cache);
+    });
+}
+
+fn notes_editor(ui: &mut Ui, notes: &mut HashMap<PathBuf, String>, dir: &Path) {
+    CollapsingHeader::new("Notes")
+        .default_open(notes.contains_key(dir))
+        .show(ui, |ui| {
+            let mut text = notes.get(dir).cloned().unwrap_or_default();
+            let edit = TextEdit::multiline(&mut text)
+                .hint_text("e.g. the weather or the setup of the run")
+                .desired_width(f32::INFINITY)
+                .desired_rows(3);
+            if ui.add(edit).changed() {
+                if text.is_empty() {
+                    notes.remove(dir);
+                } else {
+                    notes.insert(dir.to_path_buf(), text);
+                }
+            }
+        });
+}
+
+fn markers_editor(ui: &mut Ui, markers: &mut HashMap<PathBuf, Vec<Marker>>, dir: &Path) {
+    CollapsingHeader::new("Markers").show(ui, |ui| {
+        let Some(run_markers) = markers.get_mut(dir) else {
+            ui.weak("Ctrl+click into a time plot to add a marker");
+            return;
+        };
+
+        run_markers.retain_mut(|m| {
+            ui.horizontal(|ui| {
+                let removed = ui.button(" − ").clicked();
+                ui.add(DragValue::new(&mut m.time).speed(0.01).suffix(" s"));
+                ui.add(TextEdit::singleline(&mut m.label).desired_width(160.0));
+                !removed
+            })
+            .inner
+        });
+        if run_markers.is_empty() {
+            markers.remove(dir);
+        }
+    });
+}
+
+/// Returns true if an offset changed
+fn time_offsets_editor(
+    ui: &mut Ui,
+    offsets: &mut HashMap<PathBuf, HashMap<usize, i32>>,
+    dir: &Path,
+    streams: &[LogStream],
+) -> bool {
+    let mut changed = false;
+
+    CollapsingHeader::new("Time offsets").show(ui, |ui| {
+        Grid::new("time_offsets").show(ui, |ui| {
+            for (i, s) in streams.iter().enumerate() {
+                let Some(name) = stream_name(s) else {
+                    continue;
+                };
+
+                ui.label(RichText::new(name).monospace());
+                let run_offsets = offsets.get(dir);
+                let mut offset = run_offsets.and_then(|o| o.get(&i)).copied().unwrap_or(0);
+                let slider = Slider::new(&mut offset, -MAX_TIME_OFFSET..=MAX_TIME_OFFSET)
+                    .suffix(" ms")
+                    .clamp_to_range(false);
+                if ui.add(slider).changed() {
+                    offsets
+                        .entry(dir.to_path_buf())
+                        .or_default()
+                        .insert(i, offset);
+                    changed = true;
+                }
+                ui.end_row();
+            }
+        });
+    });
+
+    changed
+}
+
+fn sample_rates_editor(
+    ui: &mut Ui,
+    rates: &mut HashMap<PathBuf, HashMap<usize, f64>>,
+    dir: &Path,
+    streams: &[LogStream],
+) -> bool {
+    let mut changed = false;
+
+    CollapsingHeader::new("Sample rates").show(ui, |ui| {
+        let run_rates = rates.entry(dir.to_path_buf()).or_default();
+        Grid::new("sample_rates").show(ui, |ui| {
+            for (i, s) in streams.iter().enumerate() {
+                let Some(name) = stream_name(s) else {
+                    continue;
+                };
+
+                ui.label(RichText::new(name).monospace());
+                let detected = s.sample_rate();
+                let label = match detected {
+                    Some(r) => ui.label(format!("{r:.2} Hz")),
+                    None => ui.weak("unknown"),
+                };
+                label.on_hover_text("Average rate of the samples");
+
+                // the other streams are interpolated at the timestamps of the first one
+                if i > 0 {
+                    ui.end_row();
+                    continue;
+                }
+                let mut custom = run_rates.contains_key(&i);
+                if ui.checkbox(&mut custom, "override").changed() {
+                    if custom {
+                        let rate = detected.unwrap_or(DEFAULT_SAMPLE_RATE);
+                        run_rates.insert(i, rate);
+                    } else {
+                        run_rates.remove(&i);
+                    }
+                    changed = true;
+                }
+                if let Some(rate) = run_rates.get_mut(&i) {
+                    let resp = ui.add(DragValue::new(rate).suffix(" Hz"));
+                    *rate = rate.max(MIN_SAMPLE_RATE);
+                    changed |= resp.changed();
+                }
+                ui.end_row();
+            }
+        });
+        if run_rates.is_empty() {
+            rates.remove(dir);
+        }
+    });
+
+    changed
+}
+
+/// Returns true if a constant changed
+fn constants_editor(ui: &mut Ui, constants: &mut Vec<Constant>) -> bool {
+    let mut changed = false;
+
+    CollapsingHeader::new("Constants").show(ui, |ui| {
+        let mut i = 0;
+        while i < constants.len() {
+            let c = &mut constants[i];
+            let removed = ui
+                .horizontal(|ui| {
+                    let removed = ui.button(" − ").clicked();
+                    let resp = TextEdit::singleline(&mut c.name)
+                        .desired_width(120.0)
+                        .font(TextStyle::Monospace)
+                        .show(ui);
+                    changed |= resp.response.changed();
+                    ui.label("=");
+                    changed |= ui.add(DragValue::new(&mut c.value).speed(0.01)).changed();
+                    removed
+                })
+                .inner;
+
+            if removed {
+                constants.remove(i);
+                changed = true;
+            } else {
+                i += 1;
+            }
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button(" + ").clicked() {
+                constants.push(Constant::new(format!("c{}", constants.len() + 1), 0.0));
+                changed = true;
+            }
+            let add_vehicle = ui.button("vehicle").on_hover_text(
+                "Add the vehicle geometry, wheel radius and track width in m and mass in kg",
+            );
+            if add_vehicle.clicked() {
+                for (name, value) in VEHICLE_CONSTANTS {
+                    if !constants.iter().any(|c| c.name == name) {
+                        constants.push(Constant::new(name, value));
+                        changed = true;
+                    }
+                }
+            }
+        });
     });
+
+    changed
 }
 
 struct ExprInput {
     removed: bool,
     x_changed: bool,
     y_changed: bool,
+    options_changed: bool,
 }
 
 fn expr_inputs(
@@ -631,6 +3383,8 @@ fn expr_inputs(
     values: &PlotValues,
     idx: usize,
     dragged_plot: &mut Option<(usize, Pos2)>,
+    names: &[String],
+    palette: Palette,
 ) -> ExprInput {
     let plot_fill = match dragged_plot {
         Some((i, _)) if idx == *i => Color32::from_rgba_unmultiplied(0x80, 0x80, 0x80, 0x20),
@@ -641,8 +3395,24 @@ fn expr_inputs(
         .fill(plot_fill)
         .inner_margin(PLOT_FRAME_PADDING)
         .show(ui, |ui| {
+            let mut options_changed = false;
             let removed = ui.horizontal(|ui| {
                 let r = ui.add(Button::new(" − ").sense(Sense::click_and_drag()));
+                let mut visible = !plot.hidden;
+                if ui
+                    .checkbox(&mut visible, "")
+                    .on_hover_text("visible")
+                    .changed()
+                {
+                    plot.hidden = !visible;
+                }
+                let mut color = plot.color(idx, palette);
+                if ui.color_edit_button_srgba(&mut color).changed() {
+                    plot.color = Some(color);
+                }
+                let options = ui.menu_button("⚙", |ui| series_options(ui, plot));
+                options_changed = options.inner == Some(true);
+
                 let width = ui.available_width() - ui.spacing().interact_size.x;
                 TextEdit::singleline(&mut plot.name)
                     .desired_width(width)
@@ -656,8 +3426,11 @@ fn expr_inputs(
                 r.clicked()
             });
 
-            let x_action = expr_input(ui, " X ", &mut plot.expr.x, values.x_err());
-            let y_action = expr_input(ui, " Y ", &mut plot.expr.y, values.y_err());
+            let id = Id::new("expr_input").with(idx);
+            let x = &mut plot.expr.x;
+            let x_action = expr_input(ui, id.with("x"), " X ", x, values.x_err(), names);
+            let y = &mut plot.expr.y;
+            let y_action = expr_input(ui, id.with("y"), " Y ", y, values.y_err(), names);
 
             ui.add_space(10.0);
 
@@ -675,12 +3448,129 @@ fn expr_inputs(
                 removed: removed.inner,
                 x_changed: x_action == Some(PlotAction::Changed),
                 y_changed: y_action == Some(PlotAction::Changed),
+                options_changed,
             }
         });
 
     resp.inner
 }
 
+/// Returns true if an option that requires reevaluation changed
+fn series_options(ui: &mut Ui, plot: &mut NamedPlot) -> bool {
+    let mut changed = false;
+
+    changed |= ui.checkbox(&mut plot.derivative, "derivative").changed();
+    ui.horizontal(|ui| {
+        let mut custom_width = plot.line_width.is_some();
+        if ui.checkbox(&mut custom_width, "line width").changed() {
+            plot.line_width = custom_width.then_some(DEFAULT_LINE_WIDTH);
+        }
+        if let Some(width) = &mut plot.line_width {
+            ui.add(
+                DragValue::new(width)
+                    .speed(0.1)
+                    .clamp_range(0.1..=MAX_LINE_WIDTH),
+            );
+        }
+    });
+    ComboBox::from_label("unit")
+        .selected_text(plot.unit.to_string())
+        .show_ui(ui, |ui| {
+            for u in Unit::ALL {
+                changed |= ui
+                    .selectable_value(&mut plot.unit, u, u.to_string())
+                    .changed();
+            }
+        });
+    ComboBox::from_label("line style")
+        .selected_text(plot.line_style.map_or("Default".into(), |s| s.to_string()))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut plot.line_style, None, "Default");
+            for s in LineStyle::ALL {
+                ui.selectable_value(&mut plot.line_style, Some(s), s.to_string());
+            }
+        });
+    ui.horizontal(|ui| {
+        let mut markers = plot.markers.is_some();
+        let checkbox = ui
+            .checkbox(&mut markers, "markers")
+            .on_hover_text("Draw every sample in time mode");
+        if checkbox.changed() {
+            plot.markers = markers.then(Markers::default);
+        }
+        if let Some(m) = &mut plot.markers {
+            ui.add(
+                DragValue::new(&mut m.size)
+                    .speed(0.1)
+                    .clamp_range(0.5..=MAX_MARKER_SIZE),
+            );
+            ui.checkbox(&mut m.line, "line");
+        }
+    });
+    if let Some(m) = &mut plot.markers {
+        ComboBox::from_label("marker shape")
+            .selected_text(m.shape.to_string())
+            .show_ui(ui, |ui| {
+                for s in MarkerShape::ALL {
+                    ui.selectable_value(&mut m.shape, s, s.to_string());
+                }
+            });
+    }
+    ComboBox::from_label("gaps")
+        .selected_text(plot.gap_fill.to_string())
+        .show_ui(ui, |ui| {
+            for g in GapFill::ALL {
+                ui.selectable_value(&mut plot.gap_fill, g, g.to_string());
+            }
+        })
+        .response
+        .on_hover_text(
+            "How the line continues across gaps, when breaking lines at gaps is enabled",
+        );
+    ui.checkbox(&mut plot.fill, "fill under")
+        .on_hover_text("Shade the area under the line, only within the selection if there is one");
+    ui.checkbox(&mut plot.right_axis, "right axis")
+        .on_hover_text("Scale the plot to the right y axis in time and spectrum mode");
+    ui.horizontal(|ui| {
+        let mut custom_range = plot.valid_range.is_some();
+        let checkbox = ui
+            .checkbox(&mut custom_range, "valid range")
+            .on_hover_text("Omit samples outside the range, e.g. sentinel values of a sensor");
+        if checkbox.changed() {
+            plot.valid_range = custom_range.then_some(DEFAULT_VALID_RANGE);
+            changed = true;
+        }
+        if let Some((min, max)) = &mut plot.valid_range {
+            // an empty range would omit every sample
+            if *min > *max {
+                std::mem::swap(min, max);
+                changed = true;
+            }
+            let (lower, upper) = (*min, *max);
+            let drag = DragValue::new(min).speed(0.1);
+            changed |= ui
+                .add(drag.clamp_range(f64::NEG_INFINITY..=upper))
+                .changed();
+            ui.label("..");
+            let drag = DragValue::new(max).speed(0.1);
+            changed |= ui.add(drag.clamp_range(lower..=f64::INFINITY)).changed();
+        }
+    });
+    ui.horizontal(|ui| {
+        let mut smoothing = plot.smoothing.is_some();
+        if ui.checkbox(&mut smoothing, "moving average").changed() {
+            plot.smoothing = smoothing.then_some(DEFAULT_SMOOTHING_WINDOW);
+            changed = true;
+        }
+        if let Some(window) = &mut plot.smoothing {
+            changed |= ui.add(DragValue::new(window).suffix(" samples")).changed();
+            *window = (*window).max(1);
+        }
+    });
+
+    changed
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum PlotAction {
     DragStarted,
@@ -689,12 +3579,20 @@ enum PlotAction {
 
 fn expr_input(
     ui: &mut Ui,
+    id: Id,
     label: &str,
     expr: &mut String,
-    error: Option<&cods::Error>,
+    error: Option<&eval::Error>,
+    names: &[String],
 ) -> Option<PlotAction> {
     let mut action = None;
 
+    // the first completion of the last frame is accepted with tab
+    let completion_id = id.with("completion");
+    let had_focus = ui.memory(|m| m.has_focus(id));
+    let mut accepted = (ui.data(|d| d.get_temp::<String>(completion_id)))
+        .filter(|_| had_focus && ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Tab)));
+
     let mut layouter = |ui: &egui::Ui, string: &str, wrap_width: f32| {
         let format = TextFormat {
             font_id: TextStyle::Monospace.resolve(ui.style()),
@@ -720,12 +3618,50 @@ fn expr_input(
             action = Some(PlotAction::DragStarted);
         }
 
-        let resp = ui.add(
-            TextEdit::multiline(expr)
-                .desired_width(ui.available_width())
-                .desired_rows(1)
-                .layouter(&mut layouter),
-        );
+        let output = TextEdit::multiline(expr)
+            .id(id)
+            .desired_width(ui.available_width())
+            .desired_rows(1)
+            .layouter(&mut layouter)
+            .show(ui);
+
+        let cursor = (output.cursor_range)
+            .filter(|c| c.primary.ccursor == c.secondary.ccursor)
+            .map(|c| c.primary.ccursor.index);
+        let word = cursor.and_then(|c| word_before(expr, c));
+        let candidates: Vec<&String> = match &word {
+            Some(w) => (names.iter())
+                .filter(|n| n.len() > w.len() && n.starts_with(&expr[w.clone()]))
+                .take(MAX_COMPLETIONS)
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let show_completions = had_focus || output.response.has_focus();
+        match candidates.first() {
+            Some(&first) if show_completions => {
+                ui.data_mut(|d| d.insert_temp(completion_id, first.clone()));
+                let clicked = completion_popup(ui, completion_id, &output.response, &candidates);
+                accepted = accepted.or(clicked);
+            }
+            _ => ui.data_mut(|d| d.remove::<String>(completion_id)),
+        }
+
+        if let (Some(name), Some(word)) = (accepted, word) {
+            expr.replace_range(word.clone(), &name);
+            let cursor = expr[..word.start].chars().count() + name.chars().count();
+            let mut state = output.state;
+            (state.cursor).set_char_range(Some(CCursorRange::one(CCursor::new(cursor))));
+            state.store(ui.ctx(), id);
+            ui.memory_mut(|m| m.request_focus(id));
+            action = Some(PlotAction::Changed);
+        }
+
+        let resp = output.response;
+        let resp = match error {
+            Some(e) => resp.on_hover_text(RichText::new(e.to_string()).color(ERROR_RED)),
+            None => resp,
+        };
 
         if resp.changed() {
             action = Some(PlotAction::Changed);
@@ -738,7 +3674,41 @@ fn expr_input(
     action
 }
 
-fn mark_errors(input: &str, error: &cods::Error, format: TextFormat) -> LayoutJob {
+/// Byte range of the identifier that ends at the char index `cursor`
+fn word_before(text: &str, cursor: usize) -> Option<Range<usize>> {
+    let end = (text.char_indices().nth(cursor)).map_or(text.len(), |(i, _)| i);
+    let start = (text[..end].char_indices().rev())
+        .take_while(|(_, c)| c.is_alphanumeric() || *c == '_')
+        .last()
+        .map(|(i, _)| i)?;
+    Some(start..end)
+}
+
+/// Returns the clicked completion
+fn completion_popup(
+    ui: &Ui,
+    id: Id,
+    text_edit: &Response,
+    candidates: &[&String],
+) -> Option<String> {
+    let mut clicked = None;
+    Area::new(id)
+        .order(Order::Foreground)
+        .fixed_pos(text_edit.rect.left_bottom())
+        .show(ui.ctx(), |ui| {
+            Frame::popup(ui.style()).show(ui, |ui| {
+                for (i, &c) in candidates.iter().enumerate() {
+                    let text = RichText::new(c).monospace();
+                    if ui.selectable_label(i == 0, text).clicked() {
+                        clicked = Some(c.clone());
+                    }
+                }
+            });
+        });
+    clicked
+}
+
+fn mark_errors(input: &str, error: &eval::Error, format: TextFormat) -> LayoutJob {
     let spans = error.spans();
 
     let mut sections = Vec::new();
@@ -747,7 +3717,7 @@ fn mark_errors(input: &str, error: &cods::Error, format: TextFormat) -> LayoutJo
     let mut errors = 0;
     for (i, c) in input.char_indices() {
         for s in spans.iter() {
-            if s.start == pos {
+            if s.0 == pos {
                 if errors == 0 && i != 0 {
                     range.end = i;
                     sections.push(normal_section(range.clone(), format.clone()));
@@ -757,7 +3727,7 @@ fn mark_errors(input: &str, error: &cods::Error, format: TextFormat) -> LayoutJo
             }
         }
         for s in spans.iter() {
-            if s.end == pos {
+            if s.1 == pos {
                 errors -= 1;
                 if errors == 0 {
                     range.end = i;
@@ -851,6 +3821,10 @@ fn help_sidebar(ui: &mut Ui, data: &mut PlotData, cfg: &mut Config) {
                         let text = format!("{c}: {} = {}", c.data_type(), c.val());
                         highlight_matches(ui, &text, query);
                     }
+                    for c in cfg.constants.iter() {
+                        let text = format!("{}: float = {}", c.name, c.value);
+                        highlight_matches(ui, &text, query);
+                    }
                 });
 
             CollapsingHeader::new(RichText::new("Datatypes").text_style(TextStyle::Heading))
@@ -902,6 +3876,18 @@ fn help_sidebar(ui: &mut Ui, data: &mut PlotData, cfg: &mut Config) {
                             ui.add_space(5.0);
                         }
                     }
+
+                    for f in SeriesFun::ALL {
+                        let mut text = format!("{f}(float");
+                        for _ in 1..f.num_series() {
+                            let _ = write!(text, ", float");
+                        }
+                        for p in f.params() {
+                            let _ = write!(text, ", {p}: float");
+                        }
+                        let _ = write!(text, ") -> float");
+                        highlight_matches(ui, &text, query);
+                    }
                 });
         });
 }
@@ -972,7 +3958,7 @@ fn highlight_matches(ui: &mut Ui, text: &str, query: &str) -> bool {
     true
 }
 
-fn find_plot_range(values: &[PlotPoint], x_min: f64, x_max: f64) -> std::ops::Range<usize> {
+pub fn find_plot_range(values: &[PlotPoint], x_min: f64, x_max: f64) -> std::ops::Range<usize> {
     let min = values.binary_search_by(|v| v.x.total_cmp(&x_min));
     let min = match min {
         Ok(i) => i,
@@ -987,24 +3973,108 @@ fn find_plot_range(values: &[PlotPoint], x_min: f64, x_max: f64) -> std::ops::Ra
     min..max
 }
 
-fn subsample_plot(values: &[PlotPoint], chunk_size: usize) -> Vec<PlotPoint> {
-    if chunk_size == 1 {
+/// Keeps the points with the lowest and highest y value in each of the `buckets` chunks, so spikes
+/// are preserved
+pub fn decimate(values: &[PlotPoint], buckets: usize) -> Vec<PlotPoint> {
+    if values.len() <= 2 * buckets {
         return values.to_vec();
     }
 
-    let [first, middle @ .., last] = values else {
-        return values.to_vec();
-    };
+    let chunk_size = values.len().div_ceil(buckets);
+    let mut decimated = Vec::with_capacity(2 * buckets);
+    for c in values.chunks(chunk_size) {
+        let (mut min, mut max) = (0, 0);
+        for (i, p) in c.iter().enumerate() {
+            if p.y < c[min].y {
+                min = i;
+            }
+            if p.y > c[max].y {
+                max = i;
+            }
+        }
 
-    let middle = middle.chunks(chunk_size).map(|c| {
-        let x = c.iter().map(|p| p.x).sum::<f64>() / c.len() as f64;
-        let y = c.iter().map(|p| p.y).sum::<f64>() / c.len() as f64;
-        PlotPoint { x, y }
-    });
+        decimated.push(c[min.min(max)]);
+        if min != max {
+            decimated.push(c[min.max(max)]);
+        }
+    }
+    decimated
+}
 
-    Some(*first)
-        .into_iter()
-        .chain(middle)
-        .chain(Some(*last))
+/// Non-positive values can't be displayed and are skipped
+pub fn log_scale(values: Vec<PlotPoint>) -> Vec<PlotPoint> {
+    (values.into_iter())
+        .filter(|p| p.y > 0.0)
+        .map(|p| PlotPoint::new(p.x, p.y.log10()))
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(y: &[f64]) -> Vec<PlotPoint> {
+        (y.iter().enumerate())
+            .map(|(x, &y)| PlotPoint::new(x as f64, y))
+            .collect()
+    }
+
+    fn coords(values: &[PlotPoint]) -> Vec<(f64, f64)> {
+        values.iter().map(|p| (p.x, p.y)).collect()
+    }
+
+    #[test]
+    fn decimate_keeps_min_and_max_in_x_order() {
+        let values = points(&[0.0, 5.0, -1.0, 2.0, 3.0, 3.0, 3.0, 3.0, 9.0, 1.0, 4.0, -2.0]);
+        let decimated = decimate(&values, 3);
+        // a flat bucket only keeps one point
+        let expected = [
+            (1.0, 5.0),
+            (2.0, -1.0),
+            (4.0, 3.0),
+            (8.0, 9.0),
+            (11.0, -2.0),
+        ];
+        assert_eq!(coords(&decimated), expected);
+        assert!(decimated.windows(2).all(|w| w[0].x < w[1].x));
+    }
+
+    #[test]
+    fn decimate_keeps_short_series() {
+        let values = points(&[1.0, 3.0, 2.0, 0.0]);
+        assert_eq!(coords(&decimate(&values, 2)), coords(&values));
+        assert_eq!(coords(&decimate(&values, 10)), coords(&values));
+    }
+
+    #[test]
+    fn split_at_gaps_before_decimating() {
+        let values: Vec<PlotPoint> = (0..100)
+            .chain(200..300)
+            .map(|x| PlotPoint::new(x as f64, (x % 7) as f64))
+            .collect();
+        let mut gaps = Vec::new();
+        let segments = split_at_gaps(&values, Some(10.0), Some(10), &mut gaps);
+        assert_eq!(gaps, [(99.0, 200.0)]);
+        assert_eq!(segments.len(), 2);
+        assert!(segments[0].iter().all(|p| p.x < 100.0));
+        assert!(segments[1].iter().all(|p| p.x >= 200.0));
+        assert!(segments.iter().all(|s| s.len() <= 10));
+    }
+
+    #[test]
+    fn fill_gaps_holds_the_last_value() {
+        let segments = vec![
+            points(&[1.0, 2.0]),
+            Vec::new(),
+            vec![PlotPoint::new(5.0, 3.0)],
+        ];
+        let held = fill_gaps(segments.clone(), GapFill::Hold);
+        assert_eq!(held.len(), 1);
+        assert_eq!(
+            coords(&held[0]),
+            [(0.0, 1.0), (1.0, 2.0), (5.0, 2.0), (5.0, 3.0)]
+        );
+        assert_eq!(fill_gaps(segments.clone(), GapFill::Break).len(), 2);
+        assert_eq!(fill_gaps(segments, GapFill::Interpolate)[0].len(), 3);
+    }
+}
@@ -0,0 +1,465 @@
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::{mpsc, Arc};
+
+use anyhow::{anyhow, bail};
+use egui::{Color32, ColorImage};
+use egui_plot::PlotPoint;
+use resvg::tiny_skia::{Pixmap, Rect, Transform};
+use resvg::usvg;
+
+use crate::analysis::Series;
+use crate::app::PlotValues;
+use crate::data::LogStream;
+use crate::export::{self, DEFAULT_EXPORT_DPI};
+use crate::fs::{self, Files};
+use crate::plot::{self, AxisMap, Config, PlotMode, TabConfig};
+use crate::util::TimeFormat;
+use crate::workspace;
+
+const RENDER_WIDTH: u32 = 1600;
+const RENDER_HEIGHT: u32 = 900;
+const RENDER_POINT_SIZE: f32 = 3.0;
+/// Decimation of the drawn lines, about the number of pixels of a rendered plot
+const SVG_BUCKETS: usize = 1600;
+const SVG_MARGIN: f32 = 70.0;
+const SVG_TICKS: usize = 5;
+const SVG_FONT_SIZE: f32 = 14.0;
+const SVG_LEGEND_ROW: f32 = 20.0;
+/// Opacity of the area below filled plots and of the moving average overlays
+const SVG_OVERLAY_OPACITY: f32 = 0.5;
+const SVG_FILL_OPACITY: f32 = 0.2;
+
+/// Renders the tabs of a saved workspace into `out_dir`, see [`render_plots`]
+pub fn render_workspace(path: &Path, out_dir: &Path) -> anyhow::Result<Vec<String>> {
+    let workspace = workspace::load_workspace(path)?;
+    let files = (workspace.files)
+        .ok_or_else(|| anyhow!("Workspace '{}' has no opened files", path.display()))?;
+    render_plots(&files, &workspace.config, out_dir)
+}
+
+/// Loads the files and writes one PNG per tab of the config into `out_dir`, named after the tab.
+/// The tabs are drawn like the SVG export and rasterized without a window. Returns warnings
+/// about skipped files and plots.
+pub fn render_plots(files: &Files, cfg: &Config, out_dir: &Path) -> anyhow::Result<Vec<String>> {
+    // nobody is waiting for the progress
+    let (progress, _) = mpsc::channel();
+    let selectable_files = fs::open_files(files.clone(), &progress, &AtomicBool::new(false));
+    let mut warnings = fs::loading_warnings(&selectable_files);
+    let (streams, _) = fs::concat_groups(selectable_files.by_header);
+    if streams.is_empty() {
+        bail!("No usable files in '{}'", files.dir.display());
+    }
+    let streams: Arc<[LogStream]> = streams.into();
+
+    std::fs::create_dir_all(out_dir)?;
+    let options = svg_options();
+    let time_format = cfg.run_time_format(&files.dir, &streams);
+    let tabs = plot::start_all_jobs(cfg, &files.dir, &streams);
+    for (tab_cfg, values) in cfg.tabs.iter().zip(tabs) {
+        let mut series = Vec::new();
+        for (i, (v, p)) in values.into_iter().zip(tab_cfg.plots.iter()).enumerate() {
+            let result = match v {
+                PlotValues::Job(j) => j.join(),
+                PlotValues::Result(r) => r,
+            };
+            match result {
+                Ok(_) if p.hidden => (),
+                Ok(s) => series.push((i, s)),
+                Err(_) => warnings.push(format!("Skipped '{}' of '{}'", p.name, tab_cfg.name)),
+            }
+        }
+
+        let path = out_dir.join(file_name(&tab_cfg.name));
+        let series: Vec<_> = series.iter().map(|(i, s)| (*i, s)).collect();
+        let (series, axis_map) = prepare_series(tab_cfg, &series);
+        let svg = render_svg(cfg, tab_cfg, &series, axis_map, time_format);
+        let pixmap = rasterize(&svg, &options)?;
+        let size = [pixmap.width() as usize, pixmap.height() as usize];
+        let image = ColorImage::from_rgba_premultiplied(size, pixmap.data());
+        export::write_png(&path, &image, DEFAULT_EXPORT_DPI)?;
+    }
+
+    Ok(warnings)
+}
+
+fn file_name(tab_name: &str) -> PathBuf {
+    let name: String = (tab_name.chars())
+        .map(|c| if std::path::is_separator(c) { '_' } else { c })
+        .collect();
+    PathBuf::from(format!("{name}.png"))
+}
+
+/// Options to rasterize the SVGs with the fonts embedded in egui, so no system fonts are needed
+fn svg_options() -> usvg::Options {
+    let mut options = usvg::Options::default();
+    let fonts = egui::FontDefinitions::default();
+    let db = options.fontdb_mut();
+    for name in fonts.families[&egui::FontFamily::Proportional].iter() {
+        if let Some(data) = fonts.font_data.get(name) {
+            db.load_font_data(data.font.to_vec());
+        }
+    }
+    if let Some(family) = db.faces().next().and_then(|f| f.families.first()) {
+        options.font_family = family.0.clone();
+    }
+    options
+}
+
+fn rasterize(svg: &str, options: &usvg::Options) -> anyhow::Result<Pixmap> {
+    let tree = usvg::Tree::from_str(svg, options)?;
+    let mut pixmap =
+        Pixmap::new(RENDER_WIDTH, RENDER_HEIGHT).ok_or_else(|| anyhow!("Invalid image size"))?;
+    resvg::render(&tree, Transform::identity(), &mut pixmap.as_mut());
+    Ok(pixmap)
+}
+
+/// A plot as drawn by [`render_svg`], see [`prepare_series`]
+pub struct SvgSeries {
+    /// Index of the plot in its tab
+    pub index: usize,
+    pub points: Vec<PlotPoint>,
+    /// Moving average of the points, drawn as an overlay
+    pub smoothed: Option<Vec<PlotPoint>>,
+}
+
+/// The plots of a tab as they are displayed: limited to the saved view of continuous modes and
+/// decimated to about one point per pixel, histograms in percent if configured, log scaled and
+/// mapped onto the left axis. Also returns the mapping of the right axis, if it's used.
+pub fn prepare_series(
+    tab_cfg: &TabConfig,
+    series: &[(usize, &Series)],
+) -> (Vec<SvgSeries>, Option<AxisMap>) {
+    let axis_map = AxisMap::of(series.iter().map(|(i, s)| (*i, *s)), tab_cfg);
+    let continuous = tab_cfg.mode.is_continuous();
+    let x_range = (tab_cfg.bounds).map(|b| (b[0], b[2])).or(tab_cfg.visible_x);
+    let display = |points: &[PlotPoint], right: bool| {
+        let visible = match x_range {
+            Some((min, max)) if continuous => &points[plot::find_plot_range(points, min, max)],
+            _ => points,
+        };
+        // scatter points aren't sorted, and histograms only have few bins
+        let points = if continuous {
+            plot::decimate(visible, SVG_BUCKETS)
+        } else {
+            visible.to_vec()
+        };
+        let mut points = if tab_cfg.log_y {
+            plot::log_scale(points)
+        } else {
+            points
+        };
+        if let Some(map) = axis_map.filter(|_| right) {
+            map.apply(&mut points);
+        }
+        points
+    };
+
+    let prepared = (series.iter())
+        .map(|(i, s)| {
+            let right = tab_cfg.plots[*i].right_axis;
+            // the share of each bin has to be known before the log scale
+            let points = if tab_cfg.mode == PlotMode::Histogram && tab_cfg.histogram.percent {
+                display(&plot::histogram_percent(&s.points), right)
+            } else {
+                display(&s.points, right)
+            };
+            SvgSeries {
+                index: *i,
+                points,
+                smoothed: s.smoothed.as_deref().map(|p| display(p, right)),
+            }
+        })
+        .collect();
+    (prepared, axis_map)
+}
+
+/// Draws the points of the plots with axes, labels and legend, within the saved bounds of the tab
+/// or else fitted to the points, see [`prepare_series`]
+pub fn render_svg(
+    cfg: &Config,
+    tab_cfg: &TabConfig,
+    series: &[SvgSeries],
+    axis_map: Option<AxisMap>,
+    time_format: TimeFormat,
+) -> String {
+    let (width, height) = (RENDER_WIDTH as f32, RENDER_HEIGHT as f32);
+    let area = Rect::from_ltrb(
+        SVG_MARGIN,
+        SVG_MARGIN,
+        width - SVG_MARGIN,
+        height - SVG_MARGIN,
+    )
+    .expect("valid svg size");
+    let bounds = match tab_cfg.bounds {
+        Some([min_x, min_y, max_x, max_y]) => Bounds {
+            min: PlotPoint::new(min_x, min_y),
+            max: PlotPoint::new(max_x, max_y),
+        },
+        None => Bounds::of(series.iter().flat_map(|s| s.points.iter()), tab_cfg.mode),
+    };
+    let to_screen = |p: &PlotPoint| bounds.to_screen(p, area);
+    let number_format = cfg.number_format;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}" font-family="sans-serif" font-size="{SVG_FONT_SIZE}">"#
+    );
+    let _ = writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#);
+    let (left, top, w, h) = (area.left(), area.top(), area.width(), area.height());
+    let _ = writeln!(
+        svg,
+        r#"<clipPath id="area"><rect x="{left}" y="{top}" width="{w}" height="{h}"/></clipPath>"#
+    );
+
+    // axes with evenly spaced ticks
+    let _ = writeln!(
+        svg,
+        r#"<rect x="{left}" y="{top}" width="{w}" height="{h}" fill="none" stroke="gray"/>"#
+    );
+    for k in 0..=SVG_TICKS {
+        let f = k as f64 / SVG_TICKS as f64;
+        let x = bounds.min.x + f * (bounds.max.x - bounds.min.x);
+        let y = bounds.min.y + f * (bounds.max.y - bounds.min.y);
+        let (sx, sy) = to_screen(&PlotPoint::new(x, y));
+        let x_text = match tab_cfg.mode {
+            PlotMode::Time => time_format.format(x),
+            _ => number_format.format(x),
+        };
+        let y_value = |y: f64| if tab_cfg.log_y { 10f64.powf(y) } else { y };
+        let y_text = number_format.format(y_value(y));
+        let (bottom, right) = (area.bottom(), area.right());
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{sx}" y1="{top}" x2="{sx}" y2="{bottom}" stroke="#e0e0e0"/>"##
+        );
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{left}" y1="{sy}" x2="{right}" y2="{sy}" stroke="#e0e0e0"/>"##
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{sx}" y="{}" text-anchor="middle">{}</text>"#,
+            bottom + SVG_FONT_SIZE + 4.0,
+            escape(&x_text),
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{sy}" text-anchor="end" dominant-baseline="middle">{}</text>"#,
+            left - 6.0,
+            escape(&y_text),
+        );
+        if let Some(map) = axis_map {
+            let _ = writeln!(
+                svg,
+                r#"<text x="{}" y="{sy}" dominant-baseline="middle">{}</text>"#,
+                right + 6.0,
+                escape(&number_format.format(y_value(map.right_y(y)))),
+            );
+        }
+    }
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#,
+        0.5 * (area.left() + area.right()),
+        height - SVG_FONT_SIZE,
+        escape(&tab_cfg.x_label()),
+    );
+    let (label_x, label_y) = (SVG_FONT_SIZE, 0.5 * (area.top() + area.bottom()));
+    let _ = writeln!(
+        svg,
+        r#"<text x="{label_x}" y="{label_y}" text-anchor="middle" transform="rotate(-90 {label_x} {label_y})">{}</text>"#,
+        escape(&tab_cfg.y_label()),
+    );
+    if axis_map.is_some() {
+        let (label_x, label_y) = (width - SVG_FONT_SIZE, label_y);
+        let _ = writeln!(
+            svg,
+            r#"<text x="{label_x}" y="{label_y}" text-anchor="middle" transform="rotate(90 {label_x} {label_y})">{}</text>"#,
+            escape(&tab_cfg.right_label()),
+        );
+    }
+    if !tab_cfg.labels.title.is_empty() {
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{}" text-anchor="middle" font-size="{}">{}</text>"#,
+            0.5 * (area.left() + area.right()),
+            SVG_MARGIN / 2.0,
+            SVG_FONT_SIZE * 1.5,
+            escape(&tab_cfg.labels.title),
+        );
+    }
+
+    let _ = writeln!(svg, r#"<g clip-path="url(#area)">"#);
+    let selection = (tab_cfg.selection).map(|(a, b)| (a.min(b), a.max(b)));
+    for s in series.iter() {
+        let (i, points) = (s.index, &s.points);
+        let p = &tab_cfg.plots[i];
+        let fill = hex(p.color(i, cfg.palette));
+        match tab_cfg.mode {
+            PlotMode::Time | PlotMode::Spectrum => {
+                let style = p.line_style.unwrap_or(cfg.line_style);
+                let dash = match style.dash_pattern() {
+                    Some([on, off]) => format!(r#" stroke-dasharray="{on} {off}""#),
+                    None => String::new(),
+                };
+                let line_width = p.line_width.unwrap_or(cfg.line_width);
+                let coords = |s: &[PlotPoint]| {
+                    let mut coords = String::new();
+                    for v in s.iter() {
+                        let (x, y) = to_screen(v);
+                        let _ = write!(coords, "{x:.2},{y:.2} ");
+                    }
+                    coords.trim_end().to_string()
+                };
+                // lines are split where values are missing
+                let segments = points.split(|v| !v.y.is_finite());
+                for s in segments.filter(|s| !s.is_empty()) {
+                    if p.fill {
+                        // the area down to zero, only within the selection if there is one
+                        let area: Vec<_> = match selection {
+                            Some((start, end)) => (s.iter())
+                                .filter(|v| v.x >= start && v.x <= end)
+                                .copied()
+                                .collect(),
+                            None => s.to_vec(),
+                        };
+                        if let (Some(first), Some(last)) = (area.first(), area.last()) {
+                            let (first_x, zero) = to_screen(&PlotPoint::new(first.x, 0.0));
+                            let (last_x, _) = to_screen(&PlotPoint::new(last.x, 0.0));
+                            let _ = writeln!(
+                                svg,
+                                r#"<polygon points="{first_x:.2},{zero:.2} {} {last_x:.2},{zero:.2}" fill="{fill}" fill-opacity="{SVG_FILL_OPACITY}"/>"#,
+                                coords(&area),
+                            );
+                        }
+                    }
+                    let _ = writeln!(
+                        svg,
+                        r#"<polyline points="{}" fill="none" stroke="{fill}" stroke-width="{line_width}"{dash}/>"#,
+                        coords(s),
+                    );
+                }
+                let smoothed =
+                    (s.smoothed.iter()).flat_map(|points| points.split(|v| !v.y.is_finite()));
+                for s in smoothed.filter(|s| !s.is_empty()) {
+                    let _ = writeln!(
+                        svg,
+                        r#"<polyline points="{}" fill="none" stroke="{fill}" stroke-width="{line_width}" stroke-opacity="{SVG_OVERLAY_OPACITY}"/>"#,
+                        coords(s),
+                    );
+                }
+            }
+            PlotMode::Scatter => {
+                for v in points.iter().filter(|v| v.x.is_finite() && v.y.is_finite()) {
+                    let (x, y) = to_screen(v);
+                    let r = 0.5 * RENDER_POINT_SIZE;
+                    let _ = writeln!(
+                        svg,
+                        r#"<circle cx="{x:.2}" cy="{y:.2}" r="{r}" fill="{fill}"/>"#
+                    );
+                }
+            }
+            PlotMode::Histogram => {
+                let bar_width = match points.as_slice() {
+                    [a, b, ..] => b.x - a.x,
+                    _ => 1.0,
+                };
+                for v in points.iter() {
+                    let (left, top) = to_screen(&PlotPoint::new(v.x - 0.5 * bar_width, v.y));
+                    let (right, bottom) = to_screen(&PlotPoint::new(v.x + 0.5 * bar_width, 0.0));
+                    let _ = writeln!(
+                        svg,
+                        r#"<rect x="{left:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="{fill}"/>"#,
+                        top.min(bottom),
+                        right - left,
+                        (bottom - top).abs(),
+                    );
+                }
+            }
+        }
+    }
+    let _ = writeln!(svg, "</g>");
+
+    // legend in the top right corner, with a row for each moving average
+    let rows = series.iter().flat_map(|s| {
+        let p = &tab_cfg.plots[s.index];
+        let color = hex(p.color(s.index, cfg.palette));
+        let average = (s.smoothed.as_ref()).map(|_| {
+            (
+                format!("{} (average)", p.name),
+                color.clone(),
+                SVG_OVERLAY_OPACITY,
+            )
+        });
+        std::iter::once((p.name.clone(), color, 1.0)).chain(average)
+    });
+    for (row, (name, color, opacity)) in rows.enumerate() {
+        let y = area.top() + 10.0 + row as f32 * SVG_LEGEND_ROW;
+        let x = area.right() - 10.0;
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{y}" text-anchor="end" dominant-baseline="middle">{}</text>"#,
+            x - 24.0,
+            escape(&name),
+        );
+        let _ = writeln!(
+            svg,
+            r#"<line x1="{}" y1="{y}" x2="{x}" y2="{y}" stroke="{color}" stroke-width="3" stroke-opacity="{opacity}"/>"#,
+            x - 18.0,
+        );
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn hex(c: Color32) -> String {
+    let [r, g, b, _] = c.to_srgba_unmultiplied();
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Range of the finite values
+struct Bounds {
+    min: PlotPoint,
+    max: PlotPoint,
+}
+
+impl Bounds {
+    fn of<'a>(points: impl Iterator<Item = &'a PlotPoint>, mode: PlotMode) -> Self {
+        let mut bounds = Self {
+            min: PlotPoint::new(f64::INFINITY, f64::INFINITY),
+            max: PlotPoint::new(f64::NEG_INFINITY, f64::NEG_INFINITY),
+        };
+        for p in points.filter(|p| p.x.is_finite() && p.y.is_finite()) {
+            bounds.min = PlotPoint::new(bounds.min.x.min(p.x), bounds.min.y.min(p.y));
+            bounds.max = PlotPoint::new(bounds.max.x.max(p.x), bounds.max.y.max(p.y));
+        }
+        // bars start at zero
+        if mode == PlotMode::Histogram {
+            bounds.min.y = bounds.min.y.min(0.0);
+        }
+        if bounds.min.x > bounds.max.x {
+            bounds.min = PlotPoint::new(0.0, 0.0);
+            bounds.max = PlotPoint::new(1.0, 1.0);
+        }
+        bounds
+    }
+
+    fn to_screen(&self, p: &PlotPoint, area: Rect) -> (f32, f32) {
+        let w = (self.max.x - self.min.x).max(f64::EPSILON);
+        let h = (self.max.y - self.min.y).max(f64::EPSILON);
+        let x = area.left() as f64 + (p.x - self.min.x) / w * area.width() as f64;
+        let y = area.bottom() as f64 - (p.y - self.min.y) / h * area.height() as f64;
+        (x as f32, y as f32)
+    }
+}
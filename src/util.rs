@@ -1,6 +1,16 @@
+use std::cmp::Ordering;
 use std::path::{Path, PathBuf};
 
+use chrono::{Local, NaiveDateTime, TimeZone};
 use egui::{Slider, Ui};
+use serde::{Deserialize, Serialize};
+
+pub const DATE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+const DEFAULT_SIGNIFICANT_DIGITS: usize = 4;
+pub const MAX_SIGNIFICANT_DIGITS: usize = 15;
+/// Magnitudes outside of this range are written in scientific notation, if enabled
+const PLAIN_RANGE: std::ops::Range<f64> = 1e-3..1e5;
 
 pub fn ratio_slider(ui: &mut Ui, value: &mut f32, default_ratio: f32, range: f32) {
     let min = default_ratio / range;
@@ -26,6 +36,143 @@ pub fn format_time(seconds: f64) -> String {
     }
 }
 
+/// How values are displayed in readouts and axis labels
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NumberFormat {
+    pub significant_digits: usize,
+    pub scientific: bool,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self {
+            significant_digits: DEFAULT_SIGNIFICANT_DIGITS,
+            scientific: false,
+        }
+    }
+}
+
+impl NumberFormat {
+    pub fn format(&self, value: f64) -> String {
+        if !value.is_finite() {
+            return value.to_string();
+        }
+
+        let digits = self.significant_digits.clamp(1, MAX_SIGNIFICANT_DIGITS);
+        let magnitude = value.abs();
+        if self.scientific && value != 0.0 && !PLAIN_RANGE.contains(&magnitude) {
+            return format!("{value:.*e}", digits - 1);
+        }
+
+        let exponent = if value == 0.0 {
+            0
+        } else {
+            magnitude.log10().floor() as i32
+        };
+        let decimals = (digits as i32 - 1 - exponent).max(0) as usize;
+        let text = format!("{value:.decimals$}");
+        // trailing zeros are just noise
+        if text.contains('.') {
+            let text = text.trim_end_matches('0').trim_end_matches('.');
+            match text {
+                "-0" => "0".into(),
+                _ => text.into(),
+            }
+        } else {
+            text
+        }
+    }
+}
+
+/// How the times of time plots, in seconds since the start of the run, are displayed
+#[derive(Clone, Copy)]
+pub enum TimeFormat {
+    Relative,
+    /// Local time of day, the start of the run is a unix timestamp in seconds
+    WallClock(i64),
+}
+
+impl TimeFormat {
+    pub fn format(&self, seconds: f64) -> String {
+        match self {
+            Self::Relative => format_time(seconds),
+            Self::WallClock(start) => {
+                let millis = start * 1000 + (seconds * 1000.0).round() as i64;
+                match Local.timestamp_millis_opt(millis).single() {
+                    Some(time) => time.format("%H:%M:%S%.3f").to_string(),
+                    None => format_time(seconds),
+                }
+            }
+        }
+    }
+}
+
+/// Local date and time of a unix timestamp in seconds, formatted with [`DATE_TIME_FORMAT`]
+pub fn format_date_time(timestamp: i64) -> String {
+    match Local.timestamp_opt(timestamp, 0).single() {
+        Some(time) => time.format(DATE_TIME_FORMAT).to_string(),
+        None => timestamp.to_string(),
+    }
+}
+
+/// Parses a local date and time formatted like [`format_date_time`] into a unix timestamp
+pub fn parse_date_time(text: &str) -> Option<f64> {
+    let time = NaiveDateTime::parse_from_str(text.trim(), DATE_TIME_FORMAT).ok()?;
+    let time = Local.from_local_datetime(&time).earliest()?;
+    Some(time.timestamp() as f64)
+}
+
+/// Parses times formatted like [`format_time`], or plain seconds
+pub fn parse_time(text: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    for part in text.trim().split(':') {
+        let value: f64 = part.trim().parse().ok()?;
+        seconds = seconds * 60.0 + value;
+    }
+    Some(seconds)
+}
+
+/// Compares runs of digits by their numeric value and everything else by character, so `2` sorts
+/// before `10` and `001` next to `1`. Equal values with different padding sort the shorter
+/// one first.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(ca), Some(cb)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+
+        if ca.is_ascii_digit() && cb.is_ascii_digit() {
+            let len_a = a.find(|c: char| !c.is_ascii_digit()).unwrap_or(a.len());
+            let len_b = b.find(|c: char| !c.is_ascii_digit()).unwrap_or(b.len());
+            let (num_a, num_b) = (&a[..len_a], &b[..len_b]);
+            let (trimmed_a, trimmed_b) =
+                (num_a.trim_start_matches('0'), num_b.trim_start_matches('0'));
+            let ord = (trimmed_a.len().cmp(&trimmed_b.len()))
+                .then_with(|| trimmed_a.cmp(trimmed_b))
+                .then_with(|| len_a.cmp(&len_b));
+            if ord != Ordering::Equal {
+                return ord;
+            }
+            a = &a[len_a..];
+            b = &b[len_b..];
+        } else {
+            if ca != cb {
+                return ca.cmp(&cb);
+            }
+            a = &a[ca.len_utf8()..];
+            b = &b[cb.len_utf8()..];
+        }
+    }
+}
+
+/// Orders paths by [`natural_cmp`] of their file names
+pub fn natural_path_cmp(a: &Path, b: &Path) -> Ordering {
+    let name = |p: &Path| p.file_name().map(|n| n.to_string_lossy().into_owned());
+    natural_cmp(&name(a).unwrap_or_default(), &name(b).unwrap_or_default())
+}
+
 pub fn common_parent_dir<'a>(mut files: impl Iterator<Item = &'a PathBuf>) -> Option<&'a Path> {
     let first = files.next()?;
     let parent = first.parent()?;
@@ -38,3 +185,60 @@ pub fn common_parent_dir<'a>(mut files: impl Iterator<Item = &'a PathBuf>) -> Op
 
     Some(parent)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(names: &[&str]) -> Vec<String> {
+        let mut names: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+        names.sort_by(|a, b| natural_cmp(a, b));
+        names
+    }
+
+    #[test]
+    fn natural_cmp_orders_numbers_by_value() {
+        assert_eq!(natural_cmp("run2", "run10"), Ordering::Less);
+        assert_eq!(natural_cmp("run10", "run2"), Ordering::Greater);
+        assert_eq!(natural_cmp("run10", "run10"), Ordering::Equal);
+        assert_eq!(sorted(&["10", "9", "100", "1"]), ["1", "9", "10", "100"]);
+    }
+
+    #[test]
+    fn natural_cmp_leading_zeros() {
+        assert_eq!(natural_cmp("001", "1"), Ordering::Greater);
+        assert_eq!(natural_cmp("01", "2"), Ordering::Less);
+        assert_eq!(natural_cmp("010", "9"), Ordering::Greater);
+        assert_eq!(
+            sorted(&["002.s3lg", "10.s3lg", "1.s3lg", "001.s3lg"]),
+            ["1.s3lg", "001.s3lg", "002.s3lg", "10.s3lg"],
+        );
+    }
+
+    #[test]
+    fn natural_cmp_mixed_case() {
+        // characters are compared as is, so upper case sorts first
+        assert_eq!(natural_cmp("Run10", "run2"), Ordering::Less);
+        assert_eq!(natural_cmp("Run2", "Run10"), Ordering::Less);
+        assert_eq!(
+            sorted(&["b1", "a1", "B1", "A10", "A2"]),
+            ["A2", "A10", "B1", "a1", "b1"]
+        );
+    }
+
+    #[test]
+    fn natural_cmp_non_ascii() {
+        assert_eq!(natural_cmp("läuf2", "läuf10"), Ordering::Less);
+        assert_eq!(natural_cmp("ä1", "ö1"), Ordering::Less);
+        // non-ascii digits are compared like other characters
+        assert_eq!(natural_cmp("٢", "١٠"), Ordering::Greater);
+        assert_eq!(sorted(&["é10", "é9", "e10"]), ["e10", "é9", "é10"]);
+    }
+
+    #[test]
+    fn natural_cmp_prefix_sorts_first() {
+        assert_eq!(natural_cmp("run", "run1"), Ordering::Less);
+        assert_eq!(natural_cmp("", "a"), Ordering::Less);
+        assert_eq!(natural_cmp("1", "1a"), Ordering::Less);
+    }
+}
@@ -0,0 +1,187 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::app::PlotApp;
+use crate::eval::Constant;
+use crate::fs::Files;
+use crate::plot::{self, Config, NamedPlot};
+
+/// Plot configuration and opened files, saved separately from the app state
+#[derive(Deserialize)]
+pub struct Workspace {
+    pub config: Config,
+    pub files: Option<Files>,
+}
+
+#[derive(Serialize)]
+struct WorkspaceRef<'a> {
+    config: &'a Config,
+    files: Option<&'a Files>,
+}
+
+/// Plots and the constants they may reference, to share them between workspaces
+#[derive(Serialize, Deserialize)]
+struct PlotLibrary {
+    plots: Vec<NamedPlot>,
+    constants: Vec<Constant>,
+}
+
+impl PlotApp {
+    pub fn save_workspace_dialog(&mut self) {
+        let dialog = rfd::FileDialog::new()
+            .add_filter("Workspace", &["json"])
+            .set_file_name("workspace.json");
+        let Some(path) = dialog.save_file() else {
+            return;
+        };
+
+        let workspace = WorkspaceRef {
+            config: &self.config,
+            files: self.files.as_ref(),
+        };
+        if let Err(e) = save_workspace(&path, &workspace) {
+            self.error = Some(format!("Error saving workspace '{}': {e}", path.display()));
+        }
+    }
+
+    pub fn load_workspace_dialog(&mut self) {
+        let dialog = rfd::FileDialog::new().add_filter("Workspace", &["json"]);
+        let Some(path) = dialog.pick_file() else {
+            return;
+        };
+
+        let workspace = match load_workspace(&path) {
+            Ok(w) => w,
+            Err(e) => {
+                self.error = Some(format!("Error loading workspace '{}': {e}", path.display()));
+                return;
+            }
+        };
+
+        self.config = workspace.config;
+        if self.config.tabs.is_empty() {
+            self.config = Config::default();
+        }
+        self.config.selected_tab = self.config.selected_tab.min(self.config.tabs.len() - 1);
+
+        // the plots have to match the loaded tabs until the files are loaded
+        if let Some(data) = &mut self.data {
+            self.config
+                .migrate_stream_settings(&data.dir, &data.streams);
+            data.plots = plot::start_all_jobs(&self.config, &data.dir, &data.streams);
+        }
+        let files = workspace
+            .files
+            .filter(|f| f.items.iter().all(|p| p.exists()));
+        if let Some(files) = files.filter(|f| self.files.as_ref() != Some(f)) {
+            self.try_open_files(files, false);
+        }
+    }
+}
+
+impl PlotApp {
+    /// Saves the plots of the selected tab and all constants
+    pub fn export_plots_dialog(&mut self) {
+        let dialog = rfd::FileDialog::new()
+            .add_filter("Plots", &["json"])
+            .set_file_name("plots.json");
+        let Some(path) = dialog.save_file() else {
+            return;
+        };
+
+        let library = PlotLibrary {
+            plots: self.config.tabs[self.config.selected_tab].plots.clone(),
+            constants: self.config.constants.clone(),
+        };
+        if let Err(e) = save_json(&path, &library) {
+            self.error = Some(format!("Error exporting plots '{}': {e}", path.display()));
+        }
+    }
+
+    /// Appends the plots to the selected tab, plots with a taken name are renamed unless they are
+    /// identical. Constants that are already defined are kept, since the expressions reference
+    /// them by name.
+    pub fn import_plots_dialog(&mut self) {
+        let dialog = rfd::FileDialog::new().add_filter("Plots", &["json"]);
+        let Some(path) = dialog.pick_file() else {
+            return;
+        };
+
+        let library: PlotLibrary = match load_json(&path) {
+            Ok(l) => l,
+            Err(e) => {
+                self.error = Some(format!("Error importing plots '{}': {e}", path.display()));
+                return;
+            }
+        };
+
+        let mut constants_changed = false;
+        for c in library.constants {
+            match self.config.constants.iter().find(|o| o.name == c.name) {
+                Some(o) if o.value != c.value => self.warnings.push(format!(
+                    "Kept constant '{}' = {}, the imported one is {}",
+                    o.name, o.value, c.value
+                )),
+                Some(_) => (),
+                None => {
+                    self.config.constants.push(c);
+                    constants_changed = true;
+                }
+            }
+        }
+
+        let tab = self.config.selected_tab;
+        for mut p in library.plots {
+            let existing = &self.config.tabs[tab].plots;
+            if existing.contains(&p) {
+                continue;
+            }
+            p.name = unique_name(&p.name, existing);
+            match &mut self.data {
+                Some(data) => plot::add_plot(data, &mut self.config, p, true),
+                None => self.config.tabs[tab].plots.push(p),
+            }
+        }
+
+        if let Some(data) = &mut self.data {
+            if constants_changed {
+                data.plots = plot::start_all_jobs(&self.config, &data.dir, &data.streams);
+            }
+        }
+    }
+}
+
+/// Appends a number to `name` if a plot with it exists
+fn unique_name(name: &str, plots: &[NamedPlot]) -> String {
+    let taken = |n: &str| plots.iter().any(|p| p.name == n);
+    if !taken(name) {
+        return name.to_string();
+    }
+    (2..)
+        .map(|i| format!("{name} ({i})"))
+        .find(|n| !taken(n))
+        .unwrap()
+}
+
+fn save_json(path: &Path, value: &impl Serialize) -> anyhow::Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(writer, value)?;
+    Ok(())
+}
+
+fn load_json<T: DeserializeOwned>(path: &Path) -> anyhow::Result<T> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(serde_json::from_reader(reader)?)
+}
+
+fn save_workspace(path: &Path, workspace: &WorkspaceRef) -> anyhow::Result<()> {
+    save_json(path, workspace)
+}
+
+pub fn load_workspace(path: &Path) -> anyhow::Result<Workspace> {
+    load_json(path)
+}